@@ -0,0 +1,161 @@
+//! Render a Cornell box, a white room with a red wall and a green wall lit
+//! by a square light in the ceiling, holding a glass ball and a brushed
+//! gold one. Pass `whitted` to use the Whitted integrator instead of the path
+//! tracer and compare how much light each finds.
+//! Output the render as a PNG format image file `cornell.png`.
+//!
+use raytray::bsdf::{Conductor, Dielectric};
+use raytray::camera::Camera;
+use raytray::canvas::{Canvas, Origin};
+use raytray::color::Color;
+use raytray::encode::{self, Format};
+use raytray::integrator::{Integrator, Material, PathTracer, Surface, Whitted, World};
+use raytray::light::AreaLight;
+use raytray::matrix::Matrix;
+use raytray::ray::Ray;
+use raytray::render::Renderer;
+use raytray::sampling::{Pattern, PixelFilter, Sampler};
+use raytray::shape;
+use raytray::tonemap::ToneMap;
+use raytray::units::{Point3D, Tuple, Vector3D};
+use std::env;
+
+const SIZE: usize = 256;
+
+/// A wall where `axis` of a point is `offset`
+struct Wall {
+    axis: usize,
+    offset: f32,
+    material: Material,
+}
+
+struct Ball {
+    center: Point3D,
+    radius: f32,
+    material: Material,
+}
+
+struct Room {
+    walls: Vec<Wall>,
+    balls: Vec<Ball>,
+    lights: Vec<AreaLight>,
+}
+
+fn component(point: Point3D, axis: usize) -> f32 {
+    [point.x(), point.y(), point.z()][axis]
+}
+
+fn along(axis: usize) -> Vector3D {
+    let mut unit = [0.0; 3];
+    unit[axis] = 1.0;
+    Vector3D::new(unit[0], unit[1], unit[2])
+}
+
+impl World for Room {
+    fn intersect<'a>(&'a self, ray: &Ray) -> Option<Surface<'a>> {
+        let mut nearest: Option<Surface> = None;
+        let mut consider = |distance: f32, normal: Vector3D, material: &'a Material| {
+            if distance > 0.0
+                && nearest
+                    .as_ref()
+                    .is_none_or(|surface| distance < surface.distance)
+            {
+                nearest = Some(Surface {
+                    distance,
+                    point: ray.position(distance),
+                    normal,
+                    material,
+                });
+            }
+        };
+        for wall in &self.walls {
+            let origin = component(ray.origin, wall.axis);
+            let direction = [ray.direction.x(), ray.direction.y(), ray.direction.z()][wall.axis];
+            consider(
+                (wall.offset - origin) / direction,
+                along(wall.axis),
+                &wall.material,
+            );
+        }
+        for ball in &self.balls {
+            // The far side, for rays inside the glass ball
+            let crossings = shape::sphere_crossings(ray, ball.center, ball.radius);
+            if let Some(crossing) = crossings.iter().find(|crossing| crossing.t > 0.0) {
+                consider(crossing.t, crossing.normal, &ball.material);
+            }
+        }
+        nearest
+    }
+
+    fn area_lights(&self) -> &[AreaLight] {
+        &self.lights
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let whitted = env::args().nth(1).as_deref() == Some("whitted");
+
+    let white = Material::matte(Color::rgb(0.73, 0.73, 0.73));
+    let wall = |axis, offset, material| Wall {
+        axis,
+        offset,
+        material,
+    };
+    let mut light = AreaLight::rectangle(
+        Point3D::new(-0.25, 0.999, -0.25),
+        Vector3D::new(0.5, 0.0, 0.0),
+        4,
+        Vector3D::new(0.0, 0.0, 0.5),
+        4,
+        Color::rgb(1.0, 0.85, 0.6),
+    );
+    light.set_strength(12.0);
+    let room = Room {
+        walls: vec![
+            wall(0, -1.0, Material::matte(Color::rgb(0.65, 0.05, 0.05))),
+            wall(0, 1.0, Material::matte(Color::rgb(0.12, 0.45, 0.15))),
+            wall(1, -1.0, white.clone()),
+            wall(1, 1.0, white.clone()),
+            wall(2, -1.0, white),
+        ],
+        balls: vec![
+            Ball {
+                center: Point3D::new(-0.4, -0.65, -0.3),
+                radius: 0.35,
+                material: Material::new(Dielectric::glass(0.0)),
+            },
+            Ball {
+                center: Point3D::new(0.45, -0.65, 0.25),
+                radius: 0.35,
+                material: Material::new(Conductor::gold(0.3)),
+            },
+        ],
+        lights: vec![light],
+    };
+
+    let mut camera = Camera::new(SIZE, SIZE, 0.75);
+    camera.set_transform(Matrix::view_transform(
+        Point3D::new(0.0, 0.0, 3.7),
+        Point3D::new(0.0, 0.0, 0.0),
+        Vector3D::new(0.0, 1.0, 0.0),
+    ));
+
+    let (integrator, samples): (Box<dyn Integrator>, usize) = if whitted {
+        (Box::new(Whitted::default()), 4)
+    } else {
+        (Box::new(PathTracer::default()), 64)
+    };
+    let mut canvas = Canvas::with_origin(SIZE, SIZE, Origin::TopLeft);
+    let sampler = Sampler::new(Pattern::Sobol, PixelFilter::Tent, samples);
+    let film = Renderer::new(0).render_world(
+        &canvas,
+        &camera,
+        &room,
+        integrator.as_ref(),
+        &sampler,
+        |_, _| {},
+    );
+    film.resolve_tone_mapped(&mut canvas, ToneMap::Aces);
+
+    encode::save(&canvas, "cornell.png", Format::Png)
+}
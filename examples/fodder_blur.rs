@@ -0,0 +1,97 @@
+//! Use Tuple and its operators to calculate the path of a Projectile, then
+//! render the ball as a sphere flying along it with the camera's shutter
+//! open for a few ticks, so it is smeared along its path by motion blur.
+//! Output the render as a PPM format image file `fodder_blur.ppm`.
+//!
+use raytray::camera::Camera;
+use raytray::canvas::{Canvas, Origin};
+use raytray::color::Color;
+use raytray::encode::{self, Format};
+use raytray::matrix::Matrix;
+use raytray::motion::{Motion, Moving};
+use raytray::projection::Orthographic;
+use raytray::ray::Ray;
+use raytray::render::Renderer;
+use raytray::sampling::{Pattern, PixelFilter, Sampler};
+use raytray::shape::{Shape, Sphere};
+use raytray::units::{Point3D, Tuple, Vector3D};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+struct Projectile {
+    position: Point3D,
+    velocity: Vector3D,
+}
+
+struct World {
+    gravity: Vector3D,
+    wind: Vector3D,
+}
+
+const WIDTH: usize = 900;
+const HEIGHT: usize = 550;
+const RADIUS: f32 = 15.0;
+
+fn main() -> std::io::Result<()> {
+    let env = World {
+        gravity: Vector3D::new(0.0, -0.1, 0.0),
+        wind: Vector3D::new(-0.01, 0.0, 0.0),
+    };
+
+    let mut ball = Projectile {
+        position: Point3D::new(0.0, 1.0, 0.0),
+        velocity: Vector3D::new(1.0, 1.8, 0.0).normalize() * 11.25,
+    };
+
+    // One keyframe for the ball's position at every tick
+    let mut keyframes = Vec::new();
+    for tick in 0.. {
+        let transform = Matrix::translation(ball.position)
+            * Matrix::scaling(Point3D::new(RADIUS, RADIUS, RADIUS));
+        keyframes.push((tick as f32, transform));
+        if ball.position.y() < 0.0 {
+            break;
+        }
+        ball = next(ball, &env);
+    }
+    let sphere = Moving::new(Arc::new(Sphere), Motion::keyframed(keyframes));
+
+    // Looking straight at the plot with the shutter open for four ticks
+    let mut camera = Camera::with_projection(WIDTH, HEIGHT, Orthographic::new(WIDTH as f32));
+    camera.set_transform(Matrix::view_transform(
+        Point3D::new(WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0, 100.0),
+        Point3D::new(WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0, 0.0),
+        Vector3D::new(0.0, 1.0, 0.0),
+    ));
+    camera.set_shutter(20.0, 24.0);
+
+    let mut canvas = Canvas::with_origin(WIDTH, HEIGHT, Origin::TopLeft);
+    let sampler = Sampler::new(Pattern::Jittered, PixelFilter::Tent, 32);
+    let background = Color::rgb(0.1, 0.1, 0.15);
+    Renderer::new(0).render_supersampled(
+        &mut canvas,
+        &sampler,
+        |x, y, rng| match camera.ray_for_sample(x, y, rng) {
+            Some(ray) => shade(&sphere, &ray).unwrap_or(background),
+            None => background,
+        },
+        |_, _| {},
+    );
+
+    encode::save(&canvas, "fodder_blur.ppm", Format::PpmPlain)
+}
+
+fn next(proj: Projectile, env: &World) -> Projectile {
+    Projectile {
+        position: proj.position + proj.velocity,
+        velocity: proj.velocity + env.gravity + env.wind,
+    }
+}
+
+/// The color where `ray` hits `sphere`, lit from the top left
+fn shade(sphere: &Moving, ray: &Ray) -> Option<Color> {
+    let normal = sphere.intersect(ray).first()?.normal;
+    let light = Vector3D::new(-1.0, 1.0, 1.0).normalize();
+    let diffuse = normal.normalize().dot(light).max(0.0);
+    Some(Color::rgb(1.0, 0.5, 0.5) * (0.2 + 0.8 * diffuse))
+}
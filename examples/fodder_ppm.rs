@@ -1,9 +1,10 @@
 //! Use Tuple and its operators to calculate the path of a Projectile and
 //! plot its position at each `tick` until it hits the ground (the Y
 //! coordinate is less than zero). Output the path plot as a PPM format
-//! image file `fodder_plot.ppm`.
+//! image file `fodder_plot.ppm`. Pass `--preview` to also print the plot to
+//! the terminal.
 //!
-use raytray::canvas::{Canvas, Pixel, Ppm};
+use raytray::canvas::{Canvas, Ppm};
 use raytray::color::Color;
 use raytray::units::{Point3D, Vector3D};
 use std::fs::File;
@@ -36,13 +37,18 @@ fn main() -> std::io::Result<()> {
     let mut canvas = Canvas::new(900, 550);
 
     loop {
+        let previous = ball.position;
         ball = tick(ball, &env);
+        // Join each position to the last so the path is a continuous curve
+        canvas.draw_line_aa(previous, ball.position, Color::rgb(1.0, 0.5, 0.5));
         //  Save the plot when the ball moves outside the canvas
         if !canvas.in_bounds(ball.position) {
             write_ppm_file(&canvas)?;
+            if std::env::args().any(|arg| arg == "--preview") {
+                canvas.preview()?;
+            }
             break;
         }
-        canvas.set_pixel(Pixel::from(ball.position), Color::rgb(1.0, 0.5, 0.5));
     }
 
     Ok(())
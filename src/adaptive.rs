@@ -0,0 +1,415 @@
+//! Adaptive sampling. Rather than taking the same number of samples in
+//! every pixel, a few are taken everywhere and then more only where the
+//! samples disagree. Flat areas like sky settle after the first pass while
+//! edges and soft shadows keep being refined.
+
+use crate::canvas::{Canvas, Origin, Pixel};
+use crate::color::{Color, Spectrum};
+use crate::diff::heat;
+use crate::render::{Renderer, Rng};
+use crate::sampling::{Film, PixelFilter};
+use std::sync::Mutex;
+
+/// The running mean and variance of the samples in one pixel, updated with
+/// Welford's method so it stays accurate over many samples.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+struct PixelStats {
+    count: usize,
+    mean: [f32; 3],
+    /// Sum of squared differences from the mean of the luminance
+    m2: f32,
+    mean_luminance: f32,
+}
+
+impl PixelStats {
+    fn add(&mut self, spectrum: Spectrum) {
+        self.count += 1;
+        let n = self.count as f32;
+        let channels = [spectrum.r(), spectrum.g(), spectrum.b()];
+        for (mean, value) in self.mean.iter_mut().zip(channels.iter()) {
+            *mean += (value - *mean) / n;
+        }
+        let value = spectrum.luminance();
+        let delta = value - self.mean_luminance;
+        self.mean_luminance += delta / n;
+        self.m2 += delta * (value - self.mean_luminance);
+    }
+}
+
+/// Per-pixel sample statistics kept alongside a canvas while rendering
+#[derive(Debug, Clone)]
+pub struct Accumulator {
+    width: usize,
+    height: usize,
+    /// The origin of the canvas being rendered, which the sample heatmap
+    /// shares so it lines up with the image
+    origin: Origin,
+    stats: Vec<PixelStats>,
+}
+
+impl Accumulator {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::with_origin(width, height, Origin::default())
+    }
+
+    pub fn with_origin(width: usize, height: usize, origin: Origin) -> Self {
+        Self {
+            width,
+            height,
+            origin,
+            stats: vec![PixelStats::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn stats(&self, position: Pixel) -> &PixelStats {
+        &self.stats[position.y() * self.width + position.x()]
+    }
+
+    /// Add a sample to a pixel
+    pub fn add(&mut self, position: Pixel, color: Color) {
+        self.add_spectrum(position, Spectrum::from(color));
+    }
+
+    /// Add a sample of unclamped light to a pixel
+    pub fn add_spectrum(&mut self, position: Pixel, spectrum: Spectrum) {
+        self.stats[position.y() * self.width + position.x()].add(spectrum);
+    }
+
+    /// The number of samples taken in a pixel
+    pub fn samples(&self, position: Pixel) -> usize {
+        self.stats(position).count
+    }
+
+    /// The average of the samples in a pixel
+    pub fn mean(&self, position: Pixel) -> Color {
+        self.spectrum(position).to_color()
+    }
+
+    /// The unclamped average of the samples in a pixel
+    pub fn spectrum(&self, position: Pixel) -> Spectrum {
+        let mean = self.stats(position).mean;
+        Spectrum::new(mean[0], mean[1], mean[2])
+    }
+
+    /// The sample variance of the luminance of a pixel, zero until it has
+    /// at least two samples
+    pub fn variance(&self, position: Pixel) -> f32 {
+        let stats = self.stats(position);
+        if stats.count < 2 {
+            return 0.0;
+        }
+        stats.m2 / (stats.count - 1) as f32
+    }
+
+    /// How far the mean luminance of a pixel is likely to be from the true
+    /// value, the standard error of the mean
+    pub fn noise(&self, position: Pixel) -> f32 {
+        match self.samples(position) {
+            0 => f32::INFINITY,
+            count => (self.variance(position) / count as f32).sqrt(),
+        }
+    }
+
+    /// The total number of samples taken
+    pub fn total_samples(&self) -> usize {
+        self.stats.iter().map(|stats| stats.count).sum()
+    }
+
+    /// Write the mean of every pixel into `canvas`
+    pub fn resolve(&self, canvas: &mut Canvas) {
+        for y in 0..self.height.min(canvas.height()) {
+            for x in 0..self.width.min(canvas.width()) {
+                let position = Pixel::new(x, y);
+                canvas[position] = self.mean(position);
+            }
+        }
+    }
+
+    /// A film holding the unclamped average of every pixel, to be tone
+    /// mapped like the film of any other render
+    pub fn film(&self) -> Film {
+        let mut film = Film::new(self.width, self.height, PixelFilter::Box);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let position = Pixel::new(x, y);
+                film.set_spectrum(position, self.spectrum(position));
+            }
+        }
+        film
+    }
+
+    /// A heatmap of the number of samples per pixel, from black for the
+    /// fewest through blue, red and yellow to white for the most
+    pub fn sample_heatmap(&self) -> Canvas {
+        let mut heatmap = Canvas::with_origin(self.width, self.height, self.origin);
+        let fewest = self.stats.iter().map(|s| s.count).min().unwrap_or(0);
+        let most = self.stats.iter().map(|s| s.count).max().unwrap_or(0);
+        if most == fewest {
+            return heatmap;
+        }
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let position = Pixel::new(x, y);
+                let amount = (self.samples(position) - fewest) as f32 / (most - fewest) as f32;
+                heatmap[position] = heat(amount);
+            }
+        }
+        heatmap
+    }
+}
+
+/// When to stop sampling a pixel
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct AdaptiveSettings {
+    /// Samples taken in every pixel before checking the noise
+    pub min_samples: usize,
+    /// Samples never taken beyond this many in a pixel
+    pub max_samples: usize,
+    /// Samples added to each noisy pixel per pass
+    pub batch: usize,
+    /// Pixels stop once their `noise` is below this
+    pub threshold: f32,
+}
+
+impl AdaptiveSettings {
+    pub fn new(min_samples: usize, max_samples: usize, threshold: f32) -> Self {
+        if min_samples < 2 || max_samples < min_samples {
+            panic!(
+                "adaptive sampling needs 2 <= min_samples <= max_samples! {} {}",
+                min_samples, max_samples
+            );
+        }
+        Self {
+            min_samples,
+            max_samples,
+            batch: min_samples,
+            threshold,
+        }
+    }
+}
+
+/// The new samples taken in one pixel during a pass
+type PixelSamples = (Pixel, Vec<Spectrum>);
+
+impl Renderer {
+    /// Render `canvas` by taking jittered samples in every pixel with
+    /// `shade`, then more in the pixels which are still noisy until they
+    /// settle or reach the most samples allowed. `progress` is called with
+    /// the pass number and the number of pixels still being sampled after
+    /// each pass. The returned accumulator holds the sample counts.
+    pub fn render_adaptive<F, P>(
+        &self,
+        canvas: &mut Canvas,
+        settings: &AdaptiveSettings,
+        shade: F,
+        progress: P,
+    ) -> Accumulator
+    where
+        F: Fn(f32, f32, &mut Rng) -> Color + Sync,
+        P: Fn(usize, usize),
+    {
+        let shade = |x, y, rng: &mut Rng| Spectrum::from(shade(x, y, rng));
+        let accumulator = self.render_adaptive_radiance(canvas, settings, shade, progress);
+        accumulator.resolve(canvas);
+        accumulator
+    }
+
+    /// Sample the light reaching every pixel of `canvas` adaptively, like
+    /// `render_adaptive` but without clamping or writing to the canvas. The
+    /// accumulator's film can be tone mapped afterwards.
+    pub fn render_adaptive_radiance<F, P>(
+        &self,
+        canvas: &Canvas,
+        settings: &AdaptiveSettings,
+        shade: F,
+        progress: P,
+    ) -> Accumulator
+    where
+        F: Fn(f32, f32, &mut Rng) -> Spectrum + Sync,
+        P: Fn(usize, usize),
+    {
+        let mut accumulator =
+            Accumulator::with_origin(canvas.width(), canvas.height(), canvas.origin());
+        let tiles: Vec<_> = canvas.tiles(self.tile_size(), self.tile_size()).collect();
+
+        for pass in 1.. {
+            let wanted = |position: Pixel, accumulator: &Accumulator| {
+                let taken = accumulator.samples(position);
+                if taken == 0 {
+                    settings.min_samples
+                } else if taken < settings.max_samples
+                    && accumulator.noise(position) > settings.threshold
+                {
+                    settings.batch.min(settings.max_samples - taken)
+                } else {
+                    0
+                }
+            };
+
+            let results: Vec<Mutex<Vec<PixelSamples>>> =
+                tiles.iter().map(|_| Mutex::new(Vec::new())).collect();
+            let shared = &accumulator;
+            self.share_work(tiles.len(), &|_, _| {}, |index| {
+                let tile = tiles[index];
+                let mut samples = Vec::new();
+                for y in tile.y()..tile.y() + tile.height() {
+                    for x in tile.x()..tile.x() + tile.width() {
+                        let position = Pixel::new(x, y);
+                        let taken = shared.samples(position);
+                        let spectra = (taken..taken + wanted(position, shared))
+                            .map(|sample| {
+                                let mut rng = sample_rng(self.seed(), sample, position);
+                                let sx = x as f32 + rng.next_f32();
+                                let sy = y as f32 + rng.next_f32();
+                                shade(sx, sy, &mut rng)
+                            })
+                            .collect::<Vec<_>>();
+                        if !spectra.is_empty() {
+                            samples.push((position, spectra));
+                        }
+                    }
+                }
+                *results[index].lock().unwrap() = samples;
+            });
+
+            let mut active = 0;
+            for result in results {
+                for (position, spectra) in result.into_inner().unwrap() {
+                    active += 1;
+                    for spectrum in spectra {
+                        accumulator.add_spectrum(position, spectrum);
+                    }
+                }
+            }
+            progress(pass, active);
+            if active == 0 {
+                break;
+            }
+        }
+
+        accumulator
+    }
+}
+
+/// The generator for one sample of a pixel, so every sample is the same no
+/// matter which pass or thread takes it
+fn sample_rng(seed: u64, sample: usize, position: Pixel) -> Rng {
+    let mut rng = Rng::for_pixel(seed, position);
+    Rng::new(rng.next_u64() ^ (sample as u64).wrapping_mul(0xd6e8_feb8_6659_fd93))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracks_mean_and_variance() {
+        let mut accumulator = Accumulator::new(1, 1);
+        let pixel = Pixel::new(0, 0);
+        assert_eq!(f32::INFINITY, accumulator.noise(pixel));
+        for &value in &[0.0, 1.0, 0.0, 1.0] {
+            accumulator.add(pixel, Color::rgb(value, value, value));
+        }
+        assert_eq!(4, accumulator.samples(pixel));
+        assert_eq!([128, 128, 128], accumulator.mean(pixel).as_rgb_bytes());
+        assert!((accumulator.variance(pixel) - 1.0 / 3.0).abs() < 1e-5);
+        assert!((accumulator.noise(pixel) - (1.0f32 / 12.0).sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_flat_pixels_stop_after_the_minimum() {
+        let settings = AdaptiveSettings::new(4, 64, 0.01);
+        let mut canvas = Canvas::new(5, 5);
+        let accumulator = Renderer::new(1).render_adaptive(
+            &mut canvas,
+            &settings,
+            |_, _, _| Color::rgb(0.2, 0.4, 0.6),
+            |_, _| {},
+        );
+        assert_eq!(100, accumulator.total_samples());
+        assert_eq!([51, 102, 153], canvas[Pixel::new(2, 2)].as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_sample_heatmaps_line_up_with_the_image() {
+        // Only the top left pixel is noisy, drawn on a canvas counting rows
+        // from the top
+        let shade = |x: f32, y: f32, rng: &mut Rng| {
+            let value = if x < 1.0 && y < 1.0 {
+                rng.next_f32()
+            } else {
+                0.5
+            };
+            Spectrum::gray(value)
+        };
+        let settings = AdaptiveSettings::new(4, 32, 0.001);
+        let canvas = Canvas::with_origin(3, 2, Origin::TopLeft);
+        let accumulator =
+            Renderer::new(2).render_adaptive_radiance(&canvas, &settings, shade, |_, _| {});
+        let heatmap = accumulator.sample_heatmap();
+        assert_eq!(Origin::TopLeft, heatmap.origin());
+        let top = heatmap.visual_row(0) as usize;
+        let bottom = heatmap.visual_row(1) as usize;
+        assert_eq!([255, 255, 255], heatmap[Pixel::new(0, top)].as_rgb_bytes());
+        assert_eq!([0, 0, 0], heatmap[Pixel::new(0, bottom)].as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_noisy_pixels_get_more_samples() {
+        // Only the left column is noisy
+        let shade = |x: f32, _: f32, rng: &mut Rng| {
+            let value = if x < 1.0 { rng.next_f32() } else { 0.5 };
+            Color::rgb(value, value, value)
+        };
+        let settings = AdaptiveSettings::new(4, 32, 0.001);
+        let mut canvas = Canvas::new(3, 2);
+        let accumulator =
+            Renderer::new(2).render_adaptive(&mut canvas, &settings, shade, |_, _| {});
+        assert_eq!(32, accumulator.samples(Pixel::new(0, 1)));
+        assert_eq!(4, accumulator.samples(Pixel::new(2, 1)));
+
+        let heatmap = accumulator.sample_heatmap();
+        assert_eq!([255, 255, 255], heatmap[Pixel::new(0, 0)].as_rgb_bytes());
+        assert_eq!([0, 0, 0], heatmap[Pixel::new(1, 0)].as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_adaptive_render_is_independent_of_threads() {
+        let shade = |x: f32, y: f32, rng: &mut Rng| {
+            let value = if x > y { rng.next_f32() } else { 0.1 };
+            Color::rgb(value, 0.0, 0.0)
+        };
+        let settings = AdaptiveSettings::new(2, 16, 0.05);
+        let render = |threads| {
+            let mut renderer = Renderer::new(3);
+            renderer.set_threads(threads);
+            renderer.set_tile_size(2);
+            let mut canvas = Canvas::new(5, 4);
+            let accumulator = renderer.render_adaptive(&mut canvas, &settings, shade, |_, _| {});
+            (0..20)
+                .map(|i| {
+                    let position = Pixel::new(i % 5, i / 5);
+                    (
+                        canvas[position].as_rgb_bytes(),
+                        accumulator.samples(position),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(render(1), render(3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_needs_two_samples_to_measure_noise() {
+        AdaptiveSettings::new(1, 8, 0.1);
+    }
+}
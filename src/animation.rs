@@ -0,0 +1,345 @@
+//! Keyframe animation. A `Track` holds a value at a few moments, its keys,
+//! and works out the value in between, eased however each key asks. Any
+//! value which can be blended can be animated: numbers for material
+//! properties, colors, spectra for light intensities, points and vectors, and
+//! transform matrices for cameras and objects. A `Timeline` turns frame
+//! numbers into the times the tracks are read at.
+
+use crate::color::{Color, Spectrum};
+use crate::matrix::Matrix;
+use crate::units::{Point3D, Unit3D, Vector3D};
+use std::ops::RangeInclusive;
+
+/// How a value moves from one key to the next
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Easing {
+    /// At a steady rate
+    Linear,
+    /// Holding the value until the next key, then jumping to it
+    Step,
+    /// Starting slowly
+    EaseIn,
+    /// Ending slowly
+    EaseOut,
+    /// Starting and ending slowly
+    EaseInOut,
+    /// A cubic bezier from (0, 0) to (1, 1) with these two control points,
+    /// like CSS's `cubic-bezier(x1, y1, x2, y2)`
+    Bezier(f32, f32, f32, f32),
+}
+
+impl Easing {
+    /// The easing called `name`, as written in a scene file
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "linear" => Some(Easing::Linear),
+            "step" | "hold" => Some(Easing::Step),
+            "ease-in" => Some(Easing::EaseIn),
+            "ease-out" => Some(Easing::EaseOut),
+            "ease-in-out" | "ease" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+
+    /// How far along the value is when `t` of the time between two keys
+    /// has passed, both from 0 to 1
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match *self {
+            Easing::Linear => t,
+            Easing::Step => {
+                if t < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+            Easing::EaseIn => bezier(0.42, 0.0, 1.0, 1.0, t),
+            Easing::EaseOut => bezier(0.0, 0.0, 0.58, 1.0, t),
+            Easing::EaseInOut => bezier(0.42, 0.0, 0.58, 1.0, t),
+            Easing::Bezier(x1, y1, x2, y2) => bezier(x1, y1, x2, y2, t),
+        }
+    }
+}
+
+/// The height of the bezier curve through (0, 0), (`x1`, `y1`), (`x2`,
+/// `y2`) and (1, 1) where it is `x` across
+fn bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+    let curve = |a: f32, b: f32, s: f32| {
+        let r = 1.0 - s;
+        3.0 * r * r * s * a + 3.0 * r * s * s * b + s * s * s
+    };
+    // The curve only ever moves right when x1 and x2 are in [0, 1], so
+    // halving the interval always finds the parameter for `x`
+    let (x1, x2) = (x1.clamp(0.0, 1.0), x2.clamp(0.0, 1.0));
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..32 {
+        let middle = (low + high) / 2.0;
+        if curve(x1, x2, middle) < x {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    curve(y1, y2, (low + high) / 2.0)
+}
+
+/// A value which can be blended with another of its kind
+pub trait Animate: Clone {
+    /// The value `t` of the way from this one to `other`
+    fn blend(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Animate for f32 {
+    fn blend(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Animate for Color {
+    fn blend(&self, other: &Self, t: f32) -> Self {
+        Color::rgba(
+            self.r().blend(&other.r(), t),
+            self.g().blend(&other.g(), t),
+            self.b().blend(&other.b(), t),
+            self.a().blend(&other.a(), t),
+        )
+    }
+}
+
+impl Animate for Spectrum {
+    fn blend(&self, other: &Self, t: f32) -> Self {
+        *self * (1.0 - t) + *other * t
+    }
+}
+
+impl Animate for Point3D {
+    fn blend(&self, other: &Self, t: f32) -> Self {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Animate for Vector3D {
+    fn blend(&self, other: &Self, t: f32) -> Self {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Animate for Matrix {
+    /// Blends the decomposed parts of the matrices so things turn rather
+    /// than shrink, or the elements if either can't be decomposed
+    fn blend(&self, other: &Self, t: f32) -> Self {
+        if let (Some(from), Some(to)) = (self.decompose(), other.decompose()) {
+            return from.interpolate(&to, t).to_matrix();
+        }
+        let mut blended = *self;
+        for row in 0..4 {
+            for col in 0..4 {
+                blended[row][col] = self[row][col].blend(&other[row][col], t);
+            }
+        }
+        blended
+    }
+}
+
+/// A value at a moment, and how to move on to the next key
+#[derive(PartialEq, Debug, Clone)]
+pub struct Key<T> {
+    pub time: Unit3D,
+    pub value: T,
+    pub easing: Easing,
+}
+
+impl<T> Key<T> {
+    pub fn new(time: Unit3D, value: T, easing: Easing) -> Self {
+        Self {
+            time,
+            value,
+            easing,
+        }
+    }
+}
+
+/// A value changing over time. Before the first key and after the last it
+/// holds still.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Track<T> {
+    keys: Vec<Key<T>>,
+}
+
+impl<T: Animate> Track<T> {
+    /// A track which is always `value`
+    pub fn new(value: T) -> Self {
+        Self {
+            keys: vec![Key::new(0.0, value, Easing::Linear)],
+        }
+    }
+
+    /// A track through `keys`, given in any order
+    pub fn from_keys(mut keys: Vec<Key<T>>) -> Self {
+        if keys.is_empty() {
+            panic!("a track needs at least one key!");
+        }
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { keys }
+    }
+
+    /// Add a key, replacing any already at `time`
+    pub fn add(&mut self, time: Unit3D, value: T, easing: Easing) {
+        let key = Key::new(time, value, easing);
+        match self.keys.iter().position(|k| k.time >= time) {
+            Some(i) if self.keys[i].time == time => self.keys[i] = key,
+            Some(i) => self.keys.insert(i, key),
+            None => self.keys.push(key),
+        }
+    }
+
+    pub fn keys(&self) -> &[Key<T>] {
+        &self.keys
+    }
+
+    /// Whether the value ever changes
+    pub fn is_animated(&self) -> bool {
+        self.keys.len() > 1
+    }
+
+    /// The value at `time`
+    pub fn at(&self, time: Unit3D) -> T {
+        let first = &self.keys[0];
+        let last = &self.keys[self.keys.len() - 1];
+        if time <= first.time {
+            return first.value.clone();
+        }
+        if time >= last.time {
+            return last.value.clone();
+        }
+        let next = self.keys.iter().position(|k| k.time > time).unwrap();
+        let (start, end) = (&self.keys[next - 1], &self.keys[next]);
+        let t = (time - start.time) / (end.time - start.time);
+        start.value.blend(&end.value, start.easing.apply(t))
+    }
+}
+
+/// The frames of an animation and how fast they are played
+#[derive(PartialEq, Debug, Clone)]
+pub struct Timeline {
+    frames_per_second: f32,
+    frames: RangeInclusive<usize>,
+}
+
+impl Timeline {
+    /// Frames `first` to `last` shown `frames_per_second`, with frame 0 at
+    /// time 0
+    pub fn new(frames_per_second: f32, first: usize, last: usize) -> Self {
+        if frames_per_second <= 0.0 || last < first {
+            panic!(
+                "a timeline needs a positive frame rate and frames in order! {} {}-{}",
+                frames_per_second, first, last
+            );
+        }
+        Self {
+            frames_per_second,
+            frames: first..=last,
+        }
+    }
+
+    pub fn frames_per_second(&self) -> f32 {
+        self.frames_per_second
+    }
+
+    pub fn frames(&self) -> RangeInclusive<usize> {
+        self.frames.clone()
+    }
+
+    /// The time in seconds at which `frame` is shown
+    pub fn time(&self, frame: usize) -> Unit3D {
+        frame as Unit3D / self.frames_per_second
+    }
+
+    /// How long each frame is shown, which is how long a shutter stays
+    /// open at most
+    pub fn frame_duration(&self) -> Unit3D {
+        1.0 / self.frames_per_second
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.001
+    }
+
+    #[test]
+    fn test_easing_curves() {
+        for &easing in &[
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert!(close(0.0, easing.apply(0.0)));
+            assert!(close(1.0, easing.apply(1.0)));
+        }
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+        assert!(close(0.5, Easing::EaseInOut.apply(0.5)));
+        assert_eq!(0.0, Easing::Step.apply(0.99));
+        // A bezier along the diagonal is linear
+        let straight = Easing::Bezier(0.25, 0.25, 0.75, 0.75);
+        assert!(close(0.3, straight.apply(0.3)));
+        assert_eq!(Some(Easing::EaseInOut), Easing::from_name("ease"));
+        assert_eq!(None, Easing::from_name("bounce"));
+    }
+
+    #[test]
+    fn test_tracks_interpolate_between_keys() {
+        let mut track = Track::new(0.0f32);
+        assert!(!track.is_animated());
+        track.add(2.0, 10.0, Easing::Linear);
+        track.add(4.0, 0.0, Easing::Step);
+        track.add(-1.0, 5.0, Easing::Linear);
+        assert!(track.is_animated());
+        assert_eq!(5.0, track.at(-2.0));
+        assert_eq!(2.5, track.at(-0.5));
+        assert_eq!(5.0, track.at(1.0));
+        assert_eq!(0.0, track.at(6.0));
+
+        // Replacing a key
+        track.add(2.0, 20.0, Easing::Step);
+        assert_eq!(4, track.keys().len());
+        assert_eq!(20.0, track.at(3.0));
+    }
+
+    #[test]
+    fn test_animates_colors_and_transforms() {
+        let fade = Track::from_keys(vec![
+            Key::new(0.0, Color::rgb(1.0, 0.0, 0.0), Easing::Linear),
+            Key::new(1.0, Color::rgb(0.0, 0.0, 1.0), Easing::Linear),
+        ]);
+        assert_eq!([128, 0, 128], fade.at(0.5).as_rgb_bytes());
+
+        let spin = Track::from_keys(vec![
+            Key::new(0.0, Matrix::rotation_y(0.0), Easing::Linear),
+            Key::new(1.0, Matrix::rotation_y(3.0), Easing::Linear),
+        ]);
+        let point = spin.at(0.5) * Point3D::new(0.0, 0.0, 1.0);
+        let expected = Matrix::rotation_y(1.5) * Point3D::new(0.0, 0.0, 1.0);
+        assert!((point - expected).magnitude() < 0.0001);
+    }
+
+    #[test]
+    fn test_timeline_times() {
+        let timeline = Timeline::new(24.0, 1, 48);
+        assert_eq!(48, timeline.frames().count());
+        assert_eq!(0.5, timeline.time(12));
+        assert!(close(1.0 / 24.0, timeline.frame_duration()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tracks_need_a_key() {
+        Track::<f32>::from_keys(Vec::new());
+    }
+}
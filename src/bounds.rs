@@ -0,0 +1,304 @@
+//! Axis aligned bounding boxes. A box is cheap to test a ray against, so
+//! they're used to skip whole groups of objects a ray can't possibly hit.
+
+use crate::matrix::Matrix;
+use crate::units::{Point3D, Tuple, Unit3D, Vector3D};
+
+/// An axis aligned box between two corners. The empty box has its minimum
+/// corner at positive infinity and its maximum at negative infinity so
+/// adding anything to it gives that thing's bounds.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct BoundingBox {
+    min: Point3D,
+    max: Point3D,
+}
+
+impl Default for BoundingBox {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl BoundingBox {
+    /// Create a box between `min` and `max`, swapping coordinates where
+    /// needed so `min` is always the lesser corner.
+    pub fn new(min: Point3D, max: Point3D) -> Self {
+        Self {
+            min: Point3D::new(
+                min.x().min(max.x()),
+                min.y().min(max.y()),
+                min.z().min(max.z()),
+            ),
+            max: Point3D::new(
+                min.x().max(max.x()),
+                min.y().max(max.y()),
+                min.z().max(max.z()),
+            ),
+        }
+    }
+
+    /// A box containing nothing
+    pub fn empty() -> Self {
+        let inf = Unit3D::INFINITY;
+        Self {
+            min: Point3D::new(inf, inf, inf),
+            max: Point3D::new(-inf, -inf, -inf),
+        }
+    }
+
+    /// The smallest box containing every one of `points`
+    pub fn from_points<I: IntoIterator<Item = Point3D>>(points: I) -> Self {
+        let mut bounds = Self::empty();
+        for point in points {
+            bounds.add_point(point);
+        }
+        bounds
+    }
+
+    pub fn min(&self) -> Point3D {
+        self.min
+    }
+
+    pub fn max(&self) -> Point3D {
+        self.max
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x() > self.max.x() || self.min.y() > self.max.y() || self.min.z() > self.max.z()
+    }
+
+    /// Whether the box has an end in every direction, unlike the bounds of
+    /// a plane
+    pub fn is_finite(&self) -> bool {
+        (0..3).all(|i| axis(self.min, i).is_finite() && axis(self.max, i).is_finite())
+    }
+
+    /// Grow the box to include `point`
+    pub fn add_point(&mut self, point: Point3D) {
+        self.min = Point3D::new(
+            self.min.x().min(point.x()),
+            self.min.y().min(point.y()),
+            self.min.z().min(point.z()),
+        );
+        self.max = Point3D::new(
+            self.max.x().max(point.x()),
+            self.max.y().max(point.y()),
+            self.max.z().max(point.z()),
+        );
+    }
+
+    /// The smallest box containing both boxes
+    pub fn merge(&self, other: &BoundingBox) -> Self {
+        let mut merged = *self;
+        if !other.is_empty() {
+            merged.add_point(other.min);
+            merged.add_point(other.max);
+        }
+        merged
+    }
+
+    pub fn contains_point(&self, point: Point3D) -> bool {
+        (0..3).all(|i| axis(self.min, i) <= axis(point, i) && axis(point, i) <= axis(self.max, i))
+    }
+
+    pub fn contains_box(&self, other: &BoundingBox) -> bool {
+        other.is_empty() || (self.contains_point(other.min) && self.contains_point(other.max))
+    }
+
+    /// The point halfway between the corners
+    pub fn center(&self) -> Point3D {
+        Point3D::new(
+            (self.min.x() + self.max.x()) / 2.0,
+            (self.min.y() + self.max.y()) / 2.0,
+            (self.min.z() + self.max.z()) / 2.0,
+        )
+    }
+
+    /// The total area of the six faces of the box, zero if it's empty
+    pub fn surface_area(&self) -> Unit3D {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let size = self.max - self.min;
+        2.0 * (size.x() * size.y() + size.y() * size.z() + size.z() * size.x())
+    }
+
+    /// The axis the box is longest along, 0 for x, 1 for y and 2 for z
+    pub fn longest_axis(&self) -> usize {
+        let size = self.max - self.min;
+        if size.x() >= size.y() && size.x() >= size.z() {
+            0
+        } else if size.y() >= size.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// The box containing this one after it has been transformed. All eight
+    /// corners are transformed so rotated boxes are still fully enclosed.
+    /// Boxes stretching forever could end up anywhere, so they become the
+    /// box around everything.
+    pub fn transform(&self, transform: &Matrix) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        if !self.is_finite() {
+            let inf = Unit3D::INFINITY;
+            return Self::new(Point3D::new(-inf, -inf, -inf), Point3D::new(inf, inf, inf));
+        }
+        let (min, max) = (self.min, self.max);
+        Self::from_points((0..8).map(|corner| {
+            let pick = |bit, a: Unit3D, b: Unit3D| if corner & bit == 0 { a } else { b };
+            *transform
+                * Point3D::new(
+                    pick(1, min.x(), max.x()),
+                    pick(2, min.y(), max.y()),
+                    pick(4, min.z(), max.z()),
+                )
+        }))
+    }
+
+    /// The distances along a ray from `origin` in `direction` where it
+    /// enters and leaves the box, if it hits it at all.
+    pub fn intersect(&self, origin: Point3D, direction: Vector3D) -> Option<(Unit3D, Unit3D)> {
+        let mut tmin = Unit3D::NEG_INFINITY;
+        let mut tmax = Unit3D::INFINITY;
+        for i in 0..3 {
+            let inverse = 1.0 / axis_vector(direction, i);
+            let mut t0 = (axis(self.min, i) - axis(origin, i)) * inverse;
+            let mut t1 = (axis(self.max, i) - axis(origin, i)) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN comes from a ray lying exactly in a face plane, treat it
+            // as not limiting the ray along that axis.
+            if !t0.is_nan() {
+                tmin = tmin.max(t0);
+            }
+            if !t1.is_nan() {
+                tmax = tmax.min(t1);
+            }
+        }
+        if tmin <= tmax && tmax >= 0.0 {
+            Some((tmin, tmax))
+        } else {
+            None
+        }
+    }
+}
+
+/// The coordinate of `point` along axis 0, 1 or 2
+pub(crate) fn axis(point: Point3D, i: usize) -> Unit3D {
+    match i {
+        0 => point.x(),
+        1 => point.y(),
+        _ => point.z(),
+    }
+}
+
+fn axis_vector(vector: Vector3D, i: usize) -> Unit3D {
+    match i {
+        0 => vector.x(),
+        1 => vector.y(),
+        _ => vector.z(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> BoundingBox {
+        BoundingBox::new(Point3D::new(-1.0, -1.0, -1.0), Point3D::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn test_empty_box() {
+        let bounds = BoundingBox::empty();
+        assert!(bounds.is_empty());
+        assert_eq!(0.0, bounds.surface_area());
+        assert_eq!(unit_box(), bounds.merge(&unit_box()));
+    }
+
+    #[test]
+    fn test_box_from_points() {
+        let bounds = BoundingBox::from_points(vec![
+            Point3D::new(-5.0, 2.0, 0.0),
+            Point3D::new(7.0, 0.0, -3.0),
+        ]);
+        assert_eq!(Point3D::new(-5.0, 0.0, -3.0), bounds.min());
+        assert_eq!(Point3D::new(7.0, 2.0, 0.0), bounds.max());
+        assert_eq!(0, bounds.longest_axis());
+    }
+
+    #[test]
+    fn test_merging_boxes() {
+        let a = BoundingBox::new(Point3D::new(-5.0, -2.0, 0.0), Point3D::new(7.0, 4.0, 4.0));
+        let b = BoundingBox::new(Point3D::new(8.0, -7.0, -2.0), Point3D::new(14.0, 2.0, 8.0));
+        let merged = a.merge(&b);
+        assert_eq!(Point3D::new(-5.0, -7.0, -2.0), merged.min());
+        assert_eq!(Point3D::new(14.0, 4.0, 8.0), merged.max());
+        assert!(merged.contains_box(&a));
+        assert!(!a.contains_box(&b));
+    }
+
+    #[test]
+    fn test_surface_area() {
+        assert_eq!(24.0, unit_box().surface_area());
+    }
+
+    #[test]
+    fn test_translating_a_box() {
+        let moved = unit_box().transform(&Matrix::translation(Point3D::new(2.0, 0.0, 0.0)));
+        assert_eq!(Point3D::new(1.0, -1.0, -1.0), moved.min());
+        assert_eq!(Point3D::new(3.0, 1.0, 1.0), moved.max());
+    }
+
+    #[test]
+    fn test_rotated_box_encloses_all_corners() {
+        // A quarter turn about z swaps x and y
+        let rotation = Matrix::new([
+            [0.0, -1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let bounds = BoundingBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(2.0, 1.0, 1.0));
+        let rotated = bounds.transform(&rotation);
+        assert_eq!(Point3D::new(-1.0, 0.0, 0.0), rotated.min());
+        assert_eq!(Point3D::new(0.0, 2.0, 1.0), rotated.max());
+    }
+
+    #[test]
+    fn test_transforming_an_endless_box() {
+        let inf = Unit3D::INFINITY;
+        let plane = BoundingBox::new(Point3D::new(-inf, 0.0, -inf), Point3D::new(inf, 0.0, inf));
+        assert!(!plane.is_finite());
+        assert!(unit_box().is_finite());
+        let moved = plane.transform(&Matrix::translation(Point3D::new(0.0, 2.0, 0.0)));
+        assert!(moved.contains_point(Point3D::new(0.0, 2.0, 0.0)));
+        assert!(moved.contains_box(&unit_box()));
+    }
+
+    #[test]
+    fn test_ray_hits_box() {
+        let bounds = unit_box();
+        let hit = bounds.intersect(Point3D::new(-5.0, 0.5, 0.0), Vector3D::new(1.0, 0.0, 0.0));
+        assert_eq!(Some((4.0, 6.0)), hit);
+        assert_eq!(
+            None,
+            bounds.intersect(Point3D::new(-5.0, 2.0, 0.0), Vector3D::new(1.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            None,
+            bounds.intersect(Point3D::new(5.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_ray_starting_inside_box() {
+        let hit = unit_box().intersect(Point3D::new(0.0, 0.0, 0.0), Vector3D::new(0.0, 0.0, 1.0));
+        assert_eq!(Some((-1.0, 1.0)), hit);
+    }
+}
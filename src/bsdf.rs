@@ -0,0 +1,910 @@
+//! Physically based surface scattering. A `Bsdf` describes how a surface
+//! scatters light arriving from one direction into another: `eval` gives the
+//! fraction scattered per unit solid angle, `sample` picks a direction to
+//! follow roughly in proportion to it, and `pdf` gives the chance of picking
+//! a direction, so integrators can weigh it against other ways of finding
+//! the same light. None of them give back more light than arrives.
+//!
+//! Directions are in the surface's own space given by a `Frame`, with the
+//! normal along +z, and both point away from the surface: `wo` towards the
+//! viewer and `wi` towards where the light comes from. Surfaces which only
+//! reflect look the same from either side, while dielectrics use the side
+//! to tell whether light is going in or coming out.
+//!
+//! Rough surfaces are modelled as tiny mirrors tilted according to the GGX
+//! (Trowbridge-Reitz) distribution. Its width is the square of `roughness`,
+//! which makes equal steps of roughness look about equally different.
+
+use crate::color::{Color, Spectrum};
+use crate::render::Rng;
+use crate::units::{Tuple, Unit3D, Vector3D};
+use std::f32::consts::PI;
+use std::fmt::Debug;
+
+/// Distribution widths below which surfaces are treated as perfectly smooth
+const SMOOTH: f32 = 0.001;
+
+/// A direction picked by a `Bsdf`
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct BsdfSample {
+    /// The direction to follow for light, away from the surface
+    pub direction: Vector3D,
+    /// What the light arriving from `direction` is multiplied by: the
+    /// scattered fraction and cosine over the chance of the pick
+    pub weight: Spectrum,
+    /// The chance of picking `direction` per unit solid angle, or 0 if it
+    /// is `specular`
+    pub pdf: Unit3D,
+    /// Whether `direction` was the only one possible, as for a mirror, so
+    /// nothing else could have found the same light
+    pub specular: bool,
+}
+
+/// How a surface scatters light
+pub trait Bsdf: Debug + Send + Sync {
+    /// The fraction of the light arriving from `wi` scattered towards `wo`,
+    /// per unit solid angle. Always black for specular directions.
+    fn eval(&self, wo: Vector3D, wi: Vector3D) -> Spectrum;
+
+    /// Pick a direction to follow for the light leaving towards `wo`, or
+    /// `None` if the surface absorbs it
+    fn sample(&self, wo: Vector3D, rng: &mut Rng) -> Option<BsdfSample>;
+
+    /// The chance per unit solid angle of `sample` picking `wi`, leaving
+    /// out specular directions
+    fn pdf(&self, wo: Vector3D, wi: Vector3D) -> Unit3D;
+}
+
+/// The space of a surface, with the normal along +z
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Frame {
+    tangent: Vector3D,
+    bitangent: Vector3D,
+    normal: Vector3D,
+}
+
+impl Frame {
+    /// The space around a unit `normal`
+    pub fn new(normal: Vector3D) -> Self {
+        let helper = if normal.x().abs() > 0.9 {
+            Vector3D::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3D::new(1.0, 0.0, 0.0)
+        };
+        let tangent = helper.cross(normal).normalize();
+        Self {
+            tangent,
+            bitangent: normal.cross(tangent),
+            normal,
+        }
+    }
+
+    pub fn normal(&self) -> Vector3D {
+        self.normal
+    }
+
+    /// A world direction in the surface's space
+    pub fn to_local(&self, direction: Vector3D) -> Vector3D {
+        Vector3D::new(
+            direction.dot(self.tangent),
+            direction.dot(self.bitangent),
+            direction.dot(self.normal),
+        )
+    }
+
+    /// A direction in the surface's space in the world
+    pub fn to_world(&self, direction: Vector3D) -> Vector3D {
+        self.tangent * direction.x() + self.bitangent * direction.y() + self.normal * direction.z()
+    }
+}
+
+// ==========================================================================
+// Lambert
+
+/// A matte surface, reflecting light evenly in every direction
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Lambert {
+    /// The fraction of the light reflected
+    pub albedo: Color,
+}
+
+impl Lambert {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Bsdf for Lambert {
+    fn eval(&self, wo: Vector3D, wi: Vector3D) -> Spectrum {
+        let (wo, wi) = upper(wo, wi);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Spectrum::black();
+        }
+        Spectrum::from(self.albedo) / PI
+    }
+
+    fn sample(&self, wo: Vector3D, rng: &mut Rng) -> Option<BsdfSample> {
+        let direction = same_side(wo, cosine_hemisphere(rng));
+        if direction.z() == 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction,
+            weight: Spectrum::from(self.albedo),
+            pdf: direction.z().abs() / PI,
+            specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: Vector3D, wi: Vector3D) -> Unit3D {
+        let (_, wi) = upper(wo, wi);
+        wi.z().max(0.0) / PI
+    }
+}
+
+// ==========================================================================
+// Conductor
+
+/// A metal, reflecting light off GGX microfacets with the Fresnel
+/// reflectance of its complex index of refraction
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Conductor {
+    /// The real part of the index of refraction, per channel
+    pub eta: Spectrum,
+    /// The imaginary part, how strongly the metal absorbs light
+    pub k: Spectrum,
+    /// From 0 for a mirror to 1 for a dull, brushed look
+    pub roughness: f32,
+}
+
+impl Conductor {
+    pub fn new(eta: Spectrum, k: Spectrum, roughness: f32) -> Self {
+        Self { eta, k, roughness }
+    }
+
+    pub fn gold(roughness: f32) -> Self {
+        Self::new(
+            Spectrum::new(0.143, 0.374, 1.442),
+            Spectrum::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f32) -> Self {
+        Self::new(
+            Spectrum::new(0.155, 0.117, 0.138),
+            Spectrum::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f32) -> Self {
+        Self::new(
+            Spectrum::new(0.200, 0.924, 1.102),
+            Spectrum::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f32) -> Self {
+        Self::new(
+            Spectrum::new(1.657, 0.880, 0.521),
+            Spectrum::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    /// The fraction of light reflected at `cosine` to a facet
+    fn fresnel(&self, cosine: f32) -> Spectrum {
+        Spectrum::new(
+            fresnel_conductor(cosine, self.eta.r(), self.k.r()),
+            fresnel_conductor(cosine, self.eta.g(), self.k.g()),
+            fresnel_conductor(cosine, self.eta.b(), self.k.b()),
+        )
+    }
+}
+
+impl Bsdf for Conductor {
+    fn eval(&self, wo: Vector3D, wi: Vector3D) -> Spectrum {
+        let (wo, wi) = upper(wo, wi);
+        let ggx = Ggx::new(self.roughness);
+        if ggx.is_smooth() {
+            return Spectrum::black();
+        }
+        ggx.reflection(wo, wi, |cosine| self.fresnel(cosine))
+    }
+
+    fn sample(&self, wo: Vector3D, rng: &mut Rng) -> Option<BsdfSample> {
+        let up = upper(wo, wo).0;
+        let ggx = Ggx::new(self.roughness);
+        if ggx.is_smooth() {
+            return Some(BsdfSample {
+                direction: mirror(wo),
+                weight: self.fresnel(up.z()),
+                pdf: 0.0,
+                specular: true,
+            });
+        }
+        let wi = ggx.sample_reflection(up, rng)?;
+        let pdf = ggx.reflection_pdf(up, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction: same_side(wo, wi),
+            weight: self.eval(up, wi) * (wi.z() / pdf),
+            pdf,
+            specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: Vector3D, wi: Vector3D) -> Unit3D {
+        let (wo, wi) = upper(wo, wi);
+        let ggx = Ggx::new(self.roughness);
+        if ggx.is_smooth() {
+            return 0.0;
+        }
+        ggx.reflection_pdf(wo, wi)
+    }
+}
+
+// ==========================================================================
+// Dielectric
+
+/// A clear material like glass or water, which reflects some light off its
+/// microfacets and lets the rest through, bent by refraction
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Dielectric {
+    /// The index of refraction inside, on the side the normal points away
+    /// from, relative to outside
+    pub ior: f32,
+    /// From 0 for clear to 1 for frosted
+    pub roughness: f32,
+}
+
+impl Dielectric {
+    pub fn new(ior: f32, roughness: f32) -> Self {
+        Self { ior, roughness }
+    }
+
+    pub fn glass(roughness: f32) -> Self {
+        Self::new(1.5, roughness)
+    }
+
+    pub fn water(roughness: f32) -> Self {
+        Self::new(1.33, roughness)
+    }
+
+    /// The indices of refraction on the side of `wo` and on the other side
+    fn indices(&self, wo: Vector3D) -> (f32, f32) {
+        if wo.z() >= 0.0 {
+            (1.0, self.ior)
+        } else {
+            (self.ior, 1.0)
+        }
+    }
+
+    /// The facet normal scattering `wo` into `wi`, facing up, with the
+    /// indices either side, or `None` if no facet could
+    fn half_vector(&self, wo: Vector3D, wi: Vector3D) -> Option<Vector3D> {
+        let (outside, inside) = self.indices(wo);
+        let half = if wo.z() * wi.z() > 0.0 {
+            wo + wi
+        } else {
+            -(wo * outside + wi * inside)
+        };
+        if half.magnitude() == 0.0 {
+            return None;
+        }
+        let half = half.normalize();
+        Some(if half.z() < 0.0 { -half } else { half })
+    }
+}
+
+impl Bsdf for Dielectric {
+    fn eval(&self, wo: Vector3D, wi: Vector3D) -> Spectrum {
+        let ggx = Ggx::new(self.roughness);
+        let cosines = wo.z() * wi.z();
+        if ggx.is_smooth() || cosines == 0.0 {
+            return Spectrum::black();
+        }
+        let half = match self.half_vector(wo, wi) {
+            Some(half) => half,
+            None => return Spectrum::black(),
+        };
+        let (outside, inside) = self.indices(wo);
+        let (out_cosine, in_cosine) = (wo.dot(half), wi.dot(half));
+        let fresnel = fresnel_dielectric(out_cosine.abs(), outside, inside);
+        let shadowing = ggx.g1(wo, half) * ggx.g1(wi, half);
+        if cosines > 0.0 {
+            let value = fresnel * ggx.d(half) * shadowing / (4.0 * cosines);
+            return Spectrum::gray(value);
+        }
+        // Refraction needs the facet between the two directions
+        if out_cosine * in_cosine >= 0.0 {
+            return Spectrum::black();
+        }
+        let denominator = outside * out_cosine + inside * in_cosine;
+        let value = (in_cosine * out_cosine / cosines).abs()
+            * inside
+            * inside
+            * (1.0 - fresnel)
+            * ggx.d(half)
+            * shadowing
+            / (denominator * denominator);
+        Spectrum::gray(value)
+    }
+
+    fn sample(&self, wo: Vector3D, rng: &mut Rng) -> Option<BsdfSample> {
+        let ggx = Ggx::new(self.roughness);
+        let facet = if ggx.is_smooth() {
+            Vector3D::new(0.0, 0.0, 1.0)
+        } else {
+            ggx.sample_normal(rng)
+        };
+        let facet = same_side(wo, facet);
+        let (outside, inside) = self.indices(wo);
+        let cosine = wo.dot(facet);
+        if cosine <= 0.0 {
+            return None;
+        }
+        let fresnel = fresnel_dielectric(cosine, outside, inside);
+        let direction = if rng.next_f32() < fresnel {
+            reflect(wo, facet)
+        } else {
+            refract(wo, facet, outside / inside)?
+        };
+        let reflected = direction.z() * wo.z() > 0.0;
+        if reflected != (direction.dot(facet) > 0.0) || direction.z() == 0.0 {
+            return None;
+        }
+
+        if ggx.is_smooth() {
+            // Choosing by the Fresnel reflectance leaves nothing to weigh
+            return Some(BsdfSample {
+                direction,
+                weight: Spectrum::gray(1.0),
+                pdf: 0.0,
+                specular: true,
+            });
+        }
+        let pdf = self.pdf(wo, direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction,
+            weight: self.eval(wo, direction) * (direction.z().abs() / pdf),
+            pdf,
+            specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: Vector3D, wi: Vector3D) -> Unit3D {
+        let ggx = Ggx::new(self.roughness);
+        if ggx.is_smooth() || wo.z() * wi.z() == 0.0 {
+            return 0.0;
+        }
+        let half = match self.half_vector(wo, wi) {
+            Some(half) => half,
+            None => return 0.0,
+        };
+        let (outside, inside) = self.indices(wo);
+        let (out_cosine, in_cosine) = (wo.dot(half), wi.dot(half));
+        let fresnel = fresnel_dielectric(out_cosine.abs(), outside, inside);
+        let facet_pdf = ggx.normal_pdf(half);
+        if wo.z() * wi.z() > 0.0 {
+            fresnel * facet_pdf / (4.0 * out_cosine.abs())
+        } else if out_cosine * in_cosine < 0.0 {
+            let denominator = outside * out_cosine + inside * in_cosine;
+            (1.0 - fresnel) * facet_pdf * inside * inside * in_cosine.abs()
+                / (denominator * denominator)
+        } else {
+            0.0
+        }
+    }
+}
+
+// ==========================================================================
+// Principled
+
+/// A layered material covering the usual range of opaque surfaces with a
+/// few intuitive settings. A GGX specular layer sits over a matte base,
+/// and the light it doesn't reflect on the way in and out reaches the base.
+/// Metals have no base and reflect in their own color.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Principled {
+    /// The color of the matte base, or of the reflections of a metal
+    pub base_color: Color,
+    /// From 0 for a dielectric to 1 for a metal
+    pub metallic: f32,
+    /// From 0 for polished to 1 for matte
+    pub roughness: f32,
+    /// How much a dielectric reflects facing the viewer, where 0.5 is the
+    /// 4% of most materials
+    pub specular: f32,
+}
+
+impl Principled {
+    pub fn new(base_color: Color, metallic: f32, roughness: f32) -> Self {
+        Self {
+            base_color,
+            metallic,
+            roughness,
+            specular: 0.5,
+        }
+    }
+
+    /// The reflectance of a dielectric's specular layer facing the viewer
+    fn dielectric_reflectance(&self) -> f32 {
+        0.08 * self.specular
+    }
+
+    /// The specular reflectance facing the viewer
+    fn reflectance(&self) -> Spectrum {
+        let dielectric = Spectrum::gray(self.dielectric_reflectance());
+        dielectric * (1.0 - self.metallic) + Spectrum::from(self.base_color) * self.metallic
+    }
+
+    /// The chance of sampling the specular layer rather than the base
+    fn specular_chance(&self) -> f32 {
+        0.5 + 0.5 * self.metallic
+    }
+
+    /// The matte base, with what the specular layer lets through both ways
+    fn diffuse(&self, wo: Vector3D, wi: Vector3D) -> Spectrum {
+        let reflectance = self.dielectric_reflectance();
+        let through = |cosine: f32| 1.0 - schlick(reflectance, cosine);
+        Spectrum::from(self.base_color)
+            * ((1.0 - self.metallic) / PI)
+            * (through(wo.z()) * through(wi.z()))
+    }
+}
+
+impl Bsdf for Principled {
+    fn eval(&self, wo: Vector3D, wi: Vector3D) -> Spectrum {
+        let (wo, wi) = upper(wo, wi);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Spectrum::black();
+        }
+        let ggx = Ggx::new(self.roughness);
+        let mut value = self.diffuse(wo, wi);
+        if !ggx.is_smooth() {
+            let reflectance = self.reflectance();
+            value += ggx.reflection(wo, wi, |cosine| schlick_spectrum(reflectance, cosine));
+        }
+        value
+    }
+
+    fn sample(&self, wo: Vector3D, rng: &mut Rng) -> Option<BsdfSample> {
+        let up = upper(wo, wo).0;
+        let ggx = Ggx::new(self.roughness);
+        let chance = self.specular_chance();
+        let wi = if rng.next_f32() < chance {
+            if ggx.is_smooth() {
+                let fresnel = schlick_spectrum(self.reflectance(), up.z());
+                return Some(BsdfSample {
+                    direction: mirror(wo),
+                    weight: fresnel / chance,
+                    pdf: 0.0,
+                    specular: true,
+                });
+            }
+            ggx.sample_reflection(up, rng)?
+        } else {
+            cosine_hemisphere(rng)
+        };
+        let pdf = self.pdf(up, wi);
+        if pdf <= 0.0 || wi.z() <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction: same_side(wo, wi),
+            weight: self.eval(up, wi) * (wi.z() / pdf),
+            pdf,
+            specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: Vector3D, wi: Vector3D) -> Unit3D {
+        let (wo, wi) = upper(wo, wi);
+        if wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let ggx = Ggx::new(self.roughness);
+        let chance = self.specular_chance();
+        let specular = if ggx.is_smooth() {
+            0.0
+        } else {
+            chance * ggx.reflection_pdf(wo, wi)
+        };
+        specular + (1.0 - chance) * wi.z() / PI
+    }
+}
+
+// ==========================================================================
+// Microfacets
+
+/// The GGX distribution of microfacet normals
+#[derive(PartialEq, Debug, Clone, Copy)]
+struct Ggx {
+    alpha: f32,
+}
+
+impl Ggx {
+    fn new(roughness: f32) -> Self {
+        Self {
+            alpha: roughness * roughness,
+        }
+    }
+
+    fn is_smooth(&self) -> bool {
+        self.alpha < SMOOTH
+    }
+
+    /// The density of facets facing `facet`
+    fn d(&self, facet: Vector3D) -> f32 {
+        if facet.z() <= 0.0 {
+            return 0.0;
+        }
+        let alpha2 = self.alpha * self.alpha;
+        let t = facet.z() * facet.z() * (alpha2 - 1.0) + 1.0;
+        alpha2 / (PI * t * t)
+    }
+
+    /// The fraction of facets facing `facet` seen from `direction`, rather
+    /// than hidden behind others
+    fn g1(&self, direction: Vector3D, facet: Vector3D) -> f32 {
+        if direction.dot(facet) * direction.z() <= 0.0 {
+            return 0.0;
+        }
+        let cos2 = direction.z() * direction.z();
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        2.0 / (1.0 + (1.0 + self.alpha * self.alpha * tan2).sqrt())
+    }
+
+    /// A facet normal picked in proportion to how much of the surface faces
+    /// that way
+    fn sample_normal(&self, rng: &mut Rng) -> Vector3D {
+        let (u1, u2) = (rng.next_f32(), rng.next_f32());
+        let tan2 = self.alpha * self.alpha * u1 / (1.0 - u1);
+        let cos = 1.0 / (1.0 + tan2).sqrt();
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let (sin_phi, cos_phi) = (2.0 * PI * u2).sin_cos();
+        Vector3D::new(sin * cos_phi, sin * sin_phi, cos)
+    }
+
+    /// The chance per unit solid angle of `sample_normal` picking `facet`
+    fn normal_pdf(&self, facet: Vector3D) -> f32 {
+        self.d(facet) * facet.z().abs()
+    }
+
+    /// The light reflected from `wi` to `wo`, both above the surface, by
+    /// facets reflecting `fresnel` of it at a cosine
+    fn reflection<F: Fn(f32) -> Spectrum>(
+        &self,
+        wo: Vector3D,
+        wi: Vector3D,
+        fresnel: F,
+    ) -> Spectrum {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Spectrum::black();
+        }
+        let half = (wo + wi).normalize();
+        let shadowing = self.g1(wo, half) * self.g1(wi, half);
+        fresnel(wo.dot(half)) * (self.d(half) * shadowing / (4.0 * wo.z() * wi.z()))
+    }
+
+    fn sample_reflection(&self, wo: Vector3D, rng: &mut Rng) -> Option<Vector3D> {
+        let wi = reflect(wo, self.sample_normal(rng));
+        if wi.z() > 0.0 {
+            Some(wi)
+        } else {
+            None
+        }
+    }
+
+    fn reflection_pdf(&self, wo: Vector3D, wi: Vector3D) -> f32 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let half = (wo + wi).normalize();
+        self.normal_pdf(half) / (4.0 * wo.dot(half).abs())
+    }
+}
+
+// ==========================================================================
+// Helpers
+
+/// `v` mirrored through the surface
+fn flip(v: Vector3D) -> Vector3D {
+    Vector3D::new(v.x(), v.y(), -v.z())
+}
+
+/// `wo` and `wi` mirrored together so `wo` is above the surface, for
+/// surfaces which look the same from both sides
+fn upper(wo: Vector3D, wi: Vector3D) -> (Vector3D, Vector3D) {
+    if wo.z() < 0.0 {
+        (flip(wo), flip(wi))
+    } else {
+        (wo, wi)
+    }
+}
+
+/// `v`, picked above the surface, moved to the side of `wo`
+fn same_side(wo: Vector3D, v: Vector3D) -> Vector3D {
+    if wo.z() < 0.0 {
+        flip(v)
+    } else {
+        v
+    }
+}
+
+/// The direction a mirror facing up reflects `wo` into
+fn mirror(wo: Vector3D) -> Vector3D {
+    Vector3D::new(-wo.x(), -wo.y(), wo.z())
+}
+
+/// `wo` reflected about `facet`
+fn reflect(wo: Vector3D, facet: Vector3D) -> Vector3D {
+    facet * (2.0 * wo.dot(facet)) - wo
+}
+
+/// `wo` refracted through `facet`, on its side, where `eta` is the index on
+/// the side of `wo` over the index on the other side, or `None` if it is
+/// all reflected
+fn refract(wo: Vector3D, facet: Vector3D, eta: f32) -> Option<Vector3D> {
+    let cosine = wo.dot(facet);
+    let sin2 = eta * eta * (1.0 - cosine * cosine).max(0.0);
+    if sin2 >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2).sqrt();
+    Some(-wo * eta + facet * (eta * cosine - cos_t))
+}
+
+/// A direction above the surface, picked with a chance in proportion to
+/// its cosine with the normal
+pub fn cosine_hemisphere(rng: &mut Rng) -> Vector3D {
+    let (r1, r2) = (rng.next_f32(), rng.next_f32());
+    let (sin, cos) = (2.0 * PI * r1).sin_cos();
+    let radius = r2.sqrt();
+    Vector3D::new(radius * cos, radius * sin, (1.0 - r2).max(0.0).sqrt())
+}
+
+/// The fraction of unpolarized light reflected at a boundary, arriving at
+/// `cosine` to it from the side with index `outside`
+fn fresnel_dielectric(cosine: f32, outside: f32, inside: f32) -> f32 {
+    let cosine = cosine.clamp(0.0, 1.0);
+    let sin2 = (outside / inside).powi(2) * (1.0 - cosine * cosine);
+    if sin2 >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2).sqrt();
+    let s = (outside * cosine - inside * cos_t) / (outside * cosine + inside * cos_t);
+    let p = (inside * cosine - outside * cos_t) / (inside * cosine + outside * cos_t);
+    (s * s + p * p) / 2.0
+}
+
+/// The fraction of light a metal with complex index `eta` + `k`i reflects
+/// at `cosine`
+fn fresnel_conductor(cosine: f32, eta: f32, k: f32) -> f32 {
+    let cos2 = cosine.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let (eta2, k2) = (eta * eta, k * k);
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cosine.clamp(0.0, 1.0) * a;
+    let s = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let p = s * (t3 - t4) / (t3 + t4);
+    (s + p) / 2.0
+}
+
+/// Schlick's approximation of the reflectance at `cosine` of something
+/// reflecting `facing` head on
+fn schlick(facing: f32, cosine: f32) -> f32 {
+    facing + (1.0 - facing) * (1.0 - cosine.clamp(0.0, 1.0)).powi(5)
+}
+
+fn schlick_spectrum(facing: Spectrum, cosine: f32) -> Spectrum {
+    Spectrum::new(
+        schlick(facing.r(), cosine),
+        schlick(facing.g(), cosine),
+        schlick(facing.b(), cosine),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() <= tolerance
+    }
+
+    fn direction(x: f32, y: f32, z: f32) -> Vector3D {
+        Vector3D::new(x, y, z).normalize()
+    }
+
+    /// The fraction of light from straight above scattered anywhere, found
+    /// by sampling, which is at most 1 for anything physical
+    fn albedo(bsdf: &dyn Bsdf, wo: Vector3D) -> Spectrum {
+        let mut rng = Rng::new(7);
+        let mut total = Spectrum::black();
+        for _ in 0..20000 {
+            if let Some(sample) = bsdf.sample(wo, &mut rng) {
+                total += sample.weight;
+            }
+        }
+        total / 20000.0
+    }
+
+    /// Check that the weights and chances `sample` gives agree with `eval`
+    /// and `pdf`
+    fn check_consistent(bsdf: &dyn Bsdf, wo: Vector3D) {
+        let mut rng = Rng::new(3);
+        for _ in 0..200 {
+            let sample = match bsdf.sample(wo, &mut rng) {
+                Some(sample) if !sample.specular => sample,
+                _ => continue,
+            };
+            let pdf = bsdf.pdf(wo, sample.direction);
+            assert!(close(pdf, sample.pdf, pdf * 0.001), "{:?}", bsdf);
+            let expected = bsdf.eval(wo, sample.direction) * (sample.direction.z().abs() / pdf);
+            assert!(
+                close(
+                    expected.r(),
+                    sample.weight.r(),
+                    expected.r() * 0.001 + 0.0001
+                ),
+                "{:?} {:?} {:?}",
+                bsdf,
+                expected,
+                sample
+            );
+        }
+    }
+
+    #[test]
+    fn test_frames_round_trip() {
+        let frame = Frame::new(direction(1.0, 2.0, -0.5));
+        let v = Vector3D::new(0.3, -0.4, 0.8);
+        let back = frame.to_world(frame.to_local(v));
+        assert!((back - v).magnitude() < 0.0001);
+        assert!(close(1.0, frame.to_local(frame.normal()).z(), 0.0001));
+    }
+
+    #[test]
+    fn test_cosine_samples_favor_the_normal() {
+        let mut rng = Rng::new(2);
+        let mut total = 0.0;
+        for _ in 0..4000 {
+            let direction = cosine_hemisphere(&mut rng);
+            assert!((direction.magnitude() - 1.0).abs() < 0.001);
+            assert!(direction.z() >= 0.0);
+            total += direction.z();
+        }
+        // The average cosine of cosine weighted directions is 2/3
+        assert!(close(total / 4000.0, 2.0 / 3.0, 0.015));
+    }
+
+    #[test]
+    fn test_lambert_reflects_its_albedo() {
+        let lambert = Lambert::new(Color::rgb(0.5, 0.25, 1.0));
+        let wo = direction(0.3, 0.0, 1.0);
+        let reflected = albedo(&lambert, wo);
+        assert!(close(0.25, reflected.g(), 0.0001) && close(1.0, reflected.b(), 0.0001));
+        check_consistent(&lambert, wo);
+        // Lit the same from below
+        let below = flip(wo);
+        assert!(
+            lambert
+                .sample(below, &mut Rng::new(1))
+                .unwrap()
+                .direction
+                .z()
+                < 0.0
+        );
+        assert_eq!(
+            lambert.eval(wo, Vector3D::new(0.0, 0.0, 1.0)),
+            lambert.eval(below, Vector3D::new(0.0, 0.0, -1.0))
+        );
+        assert!(lambert.eval(wo, Vector3D::new(0.0, 0.0, -1.0)).is_black());
+    }
+
+    #[test]
+    fn test_fresnel() {
+        // Glass reflects 4% head on and everything beyond the critical angle
+        assert!(close(0.04, fresnel_dielectric(1.0, 1.0, 1.5), 0.0001));
+        assert_eq!(1.0, fresnel_dielectric(0.1, 1.5, 1.0));
+        assert!(close(1.0, fresnel_dielectric(0.0, 1.0, 1.5), 0.0001));
+        // A conductor without absorption is a dielectric
+        assert!(close(0.04, fresnel_conductor(1.0, 1.5, 0.0), 0.0001));
+        let gold = Conductor::gold(0.0).fresnel(1.0);
+        assert!(gold.r() > 0.9 && gold.b() < 0.5, "{:?}", gold);
+    }
+
+    #[test]
+    fn test_conductors_conserve_energy() {
+        let wo = direction(0.5, 0.2, 1.0);
+        for &roughness in &[0.0, 0.2, 0.5, 1.0] {
+            let silver = Conductor::silver(roughness);
+            let reflected = albedo(&silver, wo);
+            assert!(
+                reflected.max_channel() <= 1.0,
+                "{} {:?}",
+                roughness,
+                reflected
+            );
+            // Light bouncing between facets more than once is lost, which
+            // darkens the roughest metals
+            let least = if roughness > 0.5 { 0.25 } else { 0.8 };
+            assert!(
+                reflected.max_channel() > least,
+                "{} {:?}",
+                roughness,
+                reflected
+            );
+            check_consistent(&silver, wo);
+        }
+        // A polished metal is a mirror
+        let sample = Conductor::gold(0.0).sample(wo, &mut Rng::new(0)).unwrap();
+        assert!(sample.specular);
+        assert!((sample.direction - mirror(wo)).magnitude() < 0.0001);
+    }
+
+    #[test]
+    fn test_dielectrics_let_light_through() {
+        let wo = direction(0.3, 0.0, 1.0);
+        let glass = Dielectric::glass(0.0);
+        let mut rng = Rng::new(2);
+        let samples: Vec<_> = (0..1000)
+            .filter_map(|_| glass.sample(wo, &mut rng))
+            .collect();
+        let reflected = samples.iter().filter(|s| s.direction.z() > 0.0).count();
+        assert!(reflected > 20 && reflected < 80, "{}", reflected);
+        let through = samples.iter().find(|s| s.direction.z() < 0.0).unwrap();
+        // Snell's law
+        let sin_out = (1.0 - wo.z() * wo.z()).sqrt();
+        let sin_in = (1.0 - through.direction.z().powi(2)).sqrt();
+        assert!(close(sin_out, 1.5 * sin_in, 0.0001));
+
+        for &roughness in &[0.1, 0.3, 0.7] {
+            let frosted = Dielectric::glass(roughness);
+            for &wo in &[wo, flip(wo)] {
+                let scattered = albedo(&frosted, wo).r();
+                assert!(
+                    scattered <= 1.0 && scattered > 0.7,
+                    "{} {}",
+                    roughness,
+                    scattered
+                );
+                check_consistent(&frosted, wo);
+            }
+        }
+    }
+
+    #[test]
+    fn test_principled_covers_plastic_and_metal() {
+        let wo = direction(0.4, 0.0, 1.0);
+        let red = Color::rgb(0.8, 0.1, 0.1);
+        for &(metallic, roughness) in &[(0.0, 0.5), (0.0, 0.0), (1.0, 0.3), (0.5, 0.8)] {
+            let material = Principled::new(red, metallic, roughness);
+            let reflected = albedo(&material, wo);
+            assert!(reflected.max_channel() <= 1.0, "{:?}", reflected);
+            check_consistent(&material, wo);
+        }
+        // Plastic has white highlights over a red base, metal red ones
+        let plastic = Principled::new(red, 0.0, 0.2);
+        let metal = Principled::new(red, 1.0, 0.2);
+        let highlight = mirror(wo);
+        let plastic_spec = plastic.eval(wo, highlight);
+        assert!(plastic_spec.g() > 0.5, "{:?}", plastic_spec);
+        let metal_spec = metal.eval(wo, highlight);
+        assert!(metal_spec.r() > 4.0 * metal_spec.g(), "{:?}", metal_spec);
+    }
+}
@@ -0,0 +1,325 @@
+//! A bounding volume hierarchy over anything with bounds. Objects are split
+//! into a binary tree of nested boxes using the surface area heuristic, so
+//! a ray only needs to be tested against the few objects in the leaves
+//! whose boxes it passes through.
+
+use crate::bounds::{axis, BoundingBox};
+use crate::units::{Point3D, Unit3D, Vector3D};
+
+/// Anything which can report the box it fits inside
+pub trait Bounded {
+    fn bounds(&self) -> BoundingBox;
+}
+
+impl Bounded for BoundingBox {
+    fn bounds(&self) -> BoundingBox {
+        *self
+    }
+}
+
+/// Leaves with this many objects or fewer are never split
+const MAX_LEAF_SIZE: usize = 4;
+/// Number of buckets centroids are sorted into when looking for a split
+const BUCKETS: usize = 12;
+/// Cost of visiting an interior node relative to testing one object
+const TRAVERSAL_COST: Unit3D = 1.0;
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf {
+        bounds: BoundingBox,
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        bounds: BoundingBox,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &BoundingBox {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// The shape of a built hierarchy
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct BvhStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    pub depth: usize,
+    pub largest_leaf: usize,
+}
+
+/// A bounding volume hierarchy referring to objects by their index in the
+/// slice it was built from.
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    /// Build a hierarchy over `objects`
+    pub fn build<T: Bounded>(objects: &[T]) -> Self {
+        let bounds: Vec<BoundingBox> = objects.iter().map(|o| o.bounds()).collect();
+        let centers: Vec<Point3D> = bounds.iter().map(|b| b.center()).collect();
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            indices: (0..objects.len()).collect(),
+        };
+        if !objects.is_empty() {
+            bvh.build_node(&bounds, &centers, 0, objects.len());
+        }
+        bvh
+    }
+
+    /// The box around every object in the hierarchy
+    pub fn bounds(&self) -> BoundingBox {
+        self.nodes
+            .first()
+            .map(|node| *node.bounds())
+            .unwrap_or_default()
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats {
+            nodes: self.nodes.len(),
+            leaves: 0,
+            depth: 0,
+            largest_leaf: 0,
+        };
+        if !self.nodes.is_empty() {
+            self.visit_stats(0, 1, &mut stats);
+        }
+        stats
+    }
+
+    /// The indices of the objects whose boxes are hit by a ray from
+    /// `origin` in `direction`. These are only candidates, the objects
+    /// themselves still need to be intersected.
+    pub fn query(&self, origin: Point3D, direction: Vector3D) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.bounds().intersect(origin, direction).is_none() {
+                continue;
+            }
+            match *node {
+                Node::Leaf { first, count, .. } => {
+                    found.extend_from_slice(&self.indices[first..first + count]);
+                }
+                Node::Interior { left, right, .. } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        found
+    }
+
+    /// Build the node for `indices[first..first + count]`, returning its
+    /// position in `nodes`.
+    fn build_node(
+        &mut self,
+        bounds: &[BoundingBox],
+        centers: &[Point3D],
+        first: usize,
+        count: usize,
+    ) -> usize {
+        let items = &self.indices[first..first + count];
+        let node_bounds = items
+            .iter()
+            .fold(BoundingBox::empty(), |acc, &i| acc.merge(&bounds[i]));
+        let index = self.nodes.len();
+        self.nodes.push(Node::Leaf {
+            bounds: node_bounds,
+            first,
+            count,
+        });
+
+        if count <= MAX_LEAF_SIZE {
+            return index;
+        }
+        let split = match self.find_split(bounds, centers, first, count, &node_bounds) {
+            Some(split) => split,
+            None => return index,
+        };
+
+        let left = self.build_node(bounds, centers, first, split);
+        let right = self.build_node(bounds, centers, first + split, count - split);
+        self.nodes[index] = Node::Interior {
+            bounds: node_bounds,
+            left,
+            right,
+        };
+        index
+    }
+
+    /// Partition `indices[first..first + count]` at the cheapest bucket
+    /// boundary, returning how many objects went left, or `None` if
+    /// splitting costs more than leaving the node as a leaf.
+    fn find_split(
+        &mut self,
+        bounds: &[BoundingBox],
+        centers: &[Point3D],
+        first: usize,
+        count: usize,
+        node_bounds: &BoundingBox,
+    ) -> Option<usize> {
+        let items = &mut self.indices[first..first + count];
+        let centroid_bounds = BoundingBox::from_points(items.iter().map(|&i| centers[i]));
+        let dimension = centroid_bounds.longest_axis();
+        let low = axis(centroid_bounds.min(), dimension);
+        let extent = axis(centroid_bounds.max(), dimension) - low;
+        if extent <= 0.0 {
+            return None;
+        }
+
+        let bucket_of = |i: usize| {
+            let offset = (axis(centers[i], dimension) - low) / extent;
+            ((offset * BUCKETS as Unit3D) as usize).min(BUCKETS - 1)
+        };
+        let mut bucket_counts = [0; BUCKETS];
+        let mut bucket_bounds = [BoundingBox::empty(); BUCKETS];
+        for &i in items.iter() {
+            let b = bucket_of(i);
+            bucket_counts[b] += 1;
+            bucket_bounds[b] = bucket_bounds[b].merge(&bounds[i]);
+        }
+
+        // Cost of splitting after each bucket, relative to the parent's area
+        let mut best: Option<(usize, Unit3D)> = None;
+        for boundary in 0..BUCKETS - 1 {
+            let (mut left, mut right) = (BoundingBox::empty(), BoundingBox::empty());
+            let (mut left_count, mut right_count) = (0, 0);
+            for b in 0..=boundary {
+                left = left.merge(&bucket_bounds[b]);
+                left_count += bucket_counts[b];
+            }
+            for b in boundary + 1..BUCKETS {
+                right = right.merge(&bucket_bounds[b]);
+                right_count += bucket_counts[b];
+            }
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + (left.surface_area() * left_count as Unit3D
+                    + right.surface_area() * right_count as Unit3D)
+                    / node_bounds.surface_area().max(Unit3D::MIN_POSITIVE);
+            if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+                best = Some((boundary, cost));
+            }
+        }
+
+        let (boundary, cost) = best?;
+        if cost >= count as Unit3D && count <= 2 * MAX_LEAF_SIZE {
+            return None;
+        }
+        items.sort_by_key(|&i| bucket_of(i));
+        Some(
+            items
+                .iter()
+                .take_while(|&&i| bucket_of(i) <= boundary)
+                .count(),
+        )
+    }
+
+    fn visit_stats(&self, index: usize, depth: usize, stats: &mut BvhStats) {
+        stats.depth = stats.depth.max(depth);
+        match self.nodes[index] {
+            Node::Leaf { count, .. } => {
+                stats.leaves += 1;
+                stats.largest_leaf = stats.largest_leaf.max(count);
+            }
+            Node::Interior { left, right, .. } => {
+                self.visit_stats(left, depth + 1, stats);
+                self.visit_stats(right, depth + 1, stats);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A row of unit cubes along the x axis with a gap between each
+    fn cubes(count: usize) -> Vec<BoundingBox> {
+        (0..count)
+            .map(|i| {
+                let x = i as Unit3D * 3.0;
+                BoundingBox::new(Point3D::new(x, 0.0, 0.0), Point3D::new(x + 1.0, 1.0, 1.0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_hierarchy() {
+        let bvh = Bvh::build::<BoundingBox>(&[]);
+        assert_eq!(0, bvh.stats().nodes);
+        assert!(bvh.bounds().is_empty());
+        assert!(bvh
+            .query(Point3D::new(0.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_small_hierarchies_are_a_single_leaf() {
+        let bvh = Bvh::build(&cubes(3));
+        let stats = bvh.stats();
+        assert_eq!(1, stats.nodes);
+        assert_eq!(1, stats.depth);
+        assert_eq!(3, stats.largest_leaf);
+    }
+
+    #[test]
+    fn test_hierarchy_bounds_contain_every_object() {
+        let objects = cubes(100);
+        let bvh = Bvh::build(&objects);
+        for object in objects.iter() {
+            assert!(bvh.bounds().contains_box(object));
+        }
+    }
+
+    #[test]
+    fn test_large_hierarchies_are_split() {
+        let bvh = Bvh::build(&cubes(100));
+        let stats = bvh.stats();
+        assert_eq!(2 * stats.leaves - 1, stats.nodes);
+        assert!(stats.largest_leaf <= MAX_LEAF_SIZE);
+        assert!(stats.depth < 12, "depth {}", stats.depth);
+    }
+
+    #[test]
+    fn test_query_finds_only_objects_along_the_ray() {
+        let bvh = Bvh::build(&cubes(100));
+        // Only the leaf holding the cube is returned, along with its siblings
+        let hits = bvh.query(Point3D::new(30.5, 0.5, -5.0), Vector3D::new(0.0, 0.0, 1.0));
+        assert!(hits.contains(&10));
+        assert!(hits.len() <= MAX_LEAF_SIZE);
+        assert!(bvh
+            .query(Point3D::new(30.5, 5.0, -5.0), Vector3D::new(0.0, 0.0, 1.0))
+            .is_empty());
+
+        let along = bvh.query(Point3D::new(-1.0, 0.5, 0.5), Vector3D::new(1.0, 0.0, 0.0));
+        assert_eq!(100, along.len());
+    }
+
+    #[test]
+    fn test_identical_objects_stay_in_one_leaf() {
+        let objects = vec![cubes(1)[0]; 10];
+        let stats = Bvh::build(&objects).stats();
+        assert_eq!(1, stats.nodes);
+        assert_eq!(10, stats.largest_leaf);
+    }
+}
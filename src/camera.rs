@@ -0,0 +1,391 @@
+//! A camera turning canvas positions into rays, using any `Projection` to
+//! decide their directions. With no aperture it is a pinhole camera and
+//! everything is sharp. Giving it an aperture makes it a thin lens: rays
+//! start from points sampled across the lens and all meet again at the focal
+//! distance, so only things at that distance are sharp and everything else
+//! is blurred into the shape of the lens.
+
+use crate::matrix::Matrix;
+use crate::projection::{Perspective, Projection};
+use crate::ray::Ray;
+use crate::render::Rng;
+use crate::units::{Tuple, Unit3D, Vector3D};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// The shape of the lens opening, which out of focus highlights take on
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Bokeh {
+    /// A round opening
+    Disk,
+    /// An opening with `sides` straight blades, turned by `rotation`
+    /// radians
+    Polygon { sides: usize, rotation: Unit3D },
+}
+
+impl Bokeh {
+    /// Map `u`, `v` in [0, 1) uniformly onto the shape, scaled to fit in
+    /// the unit circle
+    pub fn sample(&self, u: Unit3D, v: Unit3D) -> (Unit3D, Unit3D) {
+        match *self {
+            Bokeh::Disk => {
+                let radius = u.sqrt();
+                let (sin, cos) = (2.0 * PI * v).sin_cos();
+                (radius * cos, radius * sin)
+            }
+            Bokeh::Polygon { sides, rotation } => {
+                // Pick one of the triangles fanning out from the center,
+                // then a point inside it
+                let scaled = u * sides as Unit3D;
+                let side = (scaled as usize).min(sides - 1);
+                let along = scaled - side as Unit3D;
+                let corner = |i: usize| {
+                    let angle = rotation + 2.0 * PI * i as Unit3D / sides as Unit3D;
+                    (angle.cos(), angle.sin())
+                };
+                let (a, b) = (corner(side), corner(side + 1));
+                let reach = v.sqrt();
+                (
+                    reach * (a.0 * (1.0 - along) + b.0 * along),
+                    reach * (a.1 * (1.0 - along) + b.1 * along),
+                )
+            }
+        }
+    }
+}
+
+/// A camera looking down -z from the origin until moved by its transform
+#[derive(Debug, Clone)]
+pub struct Camera {
+    hsize: usize,
+    vsize: usize,
+    projection: Arc<dyn Projection>,
+    transform: Matrix,
+    inverse: Matrix,
+    aperture: Unit3D,
+    focal_distance: Unit3D,
+    bokeh: Bokeh,
+    shutter: (Unit3D, Unit3D),
+}
+
+impl Camera {
+    /// A pinhole camera for a `hsize` by `vsize` canvas seeing
+    /// `field_of_view` radians across its wider side
+    pub fn new(hsize: usize, vsize: usize, field_of_view: Unit3D) -> Self {
+        Self::with_projection(hsize, vsize, Perspective::new(field_of_view))
+    }
+
+    /// A camera for a `hsize` by `vsize` canvas using `projection`
+    pub fn with_projection<P>(hsize: usize, vsize: usize, projection: P) -> Self
+    where
+        P: Projection + 'static,
+    {
+        if hsize == 0 || vsize == 0 {
+            panic!("a camera needs at least one pixel!");
+        }
+        Self {
+            hsize,
+            vsize,
+            projection: Arc::new(projection),
+            transform: Matrix::identity(),
+            inverse: Matrix::identity(),
+            aperture: 0.0,
+            focal_distance: 1.0,
+            bokeh: Bokeh::Disk,
+            shutter: (0.0, 0.0),
+        }
+    }
+
+    pub fn hsize(&self) -> usize {
+        self.hsize
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.vsize
+    }
+
+    /// Render a `hsize` by `vsize` canvas instead, seeing the same field of
+    /// view across its wider side
+    pub fn set_size(&mut self, hsize: usize, vsize: usize) {
+        if hsize == 0 || vsize == 0 {
+            panic!("a camera needs at least one pixel!");
+        }
+        self.hsize = hsize;
+        self.vsize = vsize;
+    }
+
+    pub fn projection(&self) -> &dyn Projection {
+        self.projection.as_ref()
+    }
+
+    pub fn set_projection<P>(&mut self, projection: P)
+    where
+        P: Projection + 'static,
+    {
+        self.projection = Arc::new(projection);
+    }
+
+    pub fn transform(&self) -> Matrix {
+        self.transform
+    }
+
+    /// Move the camera, usually with `Matrix::view_transform`
+    pub fn set_transform(&mut self, transform: Matrix) {
+        self.inverse = transform.inverse();
+        self.transform = transform;
+    }
+
+    /// The diameter of the lens, 0 for a pinhole
+    pub fn aperture(&self) -> Unit3D {
+        self.aperture
+    }
+
+    pub fn set_aperture(&mut self, aperture: Unit3D) {
+        if aperture < 0.0 {
+            panic!("the aperture can't be negative! {}", aperture);
+        }
+        self.aperture = aperture;
+    }
+
+    /// How far in front of the camera things are perfectly in focus
+    pub fn focal_distance(&self) -> Unit3D {
+        self.focal_distance
+    }
+
+    pub fn set_focal_distance(&mut self, focal_distance: Unit3D) {
+        if focal_distance <= 0.0 {
+            panic!("the focal distance must be positive! {}", focal_distance);
+        }
+        self.focal_distance = focal_distance;
+    }
+
+    pub fn bokeh(&self) -> Bokeh {
+        self.bokeh
+    }
+
+    pub fn set_bokeh(&mut self, bokeh: Bokeh) {
+        if let Bokeh::Polygon { sides, .. } = bokeh {
+            if sides < 3 {
+                panic!("a polygonal lens needs at least 3 sides! {}", sides);
+            }
+        }
+        self.bokeh = bokeh;
+    }
+
+    /// The times the shutter opens and closes
+    pub fn shutter(&self) -> (Unit3D, Unit3D) {
+        self.shutter
+    }
+
+    /// Keep the shutter open from `open` until `close`, so that things
+    /// moving in the meantime are blurred
+    pub fn set_shutter(&mut self, open: Unit3D, close: Unit3D) {
+        if close < open {
+            panic!(
+                "the shutter can't close before it opens! {} {}",
+                open, close
+            );
+        }
+        self.shutter = (open, close);
+    }
+
+    /// The ray through canvas position `x`, `y` from the point `lens` on
+    /// the unit lens, which is scaled to the aperture. Pixel centers are at
+    /// half way positions like 0.5. `None` where the projection sees
+    /// nothing.
+    pub fn ray_through(&self, x: Unit3D, y: Unit3D, lens: (Unit3D, Unit3D)) -> Option<Ray> {
+        let u = x / self.hsize as Unit3D * 2.0 - 1.0;
+        let v = 1.0 - y / self.vsize as Unit3D * 2.0;
+        let aspect = self.hsize as Unit3D / self.vsize as Unit3D;
+        let mut ray = self.projection.ray(u, v, aspect)?;
+
+        // Rays from across the lens meet again where the ray from its
+        // center reaches the plane in focus
+        if self.aperture > 0.0 && ray.direction.z() < 0.0 {
+            let focus = ray.position(self.focal_distance / -ray.direction.z());
+            let radius = self.aperture / 2.0;
+            let start = ray.origin + Vector3D::new(lens.0 * radius, lens.1 * radius, 0.0);
+            ray = Ray::new(start, focus - start);
+        }
+
+        let origin = self.inverse * ray.origin;
+        let direction = (self.inverse * ray.direction).normalize();
+        Some(Ray::new(origin, direction).with_time(self.shutter.0))
+    }
+
+    /// The ray through the center of the pixel at `x`, `y` from the center
+    /// of the lens
+    pub fn ray_for_pixel(&self, x: usize, y: usize) -> Option<Ray> {
+        self.ray_through(x as Unit3D + 0.5, y as Unit3D + 0.5, (0.0, 0.0))
+    }
+
+    /// A ray through canvas position `x`, `y` from a random point on the
+    /// lens at a random time while the shutter is open. Without an aperture
+    /// or a shutter interval `rng` is left alone for them.
+    pub fn ray_for_sample(&self, x: Unit3D, y: Unit3D, rng: &mut Rng) -> Option<Ray> {
+        let lens = if self.aperture == 0.0 {
+            (0.0, 0.0)
+        } else {
+            self.bokeh.sample(rng.next_f32(), rng.next_f32())
+        };
+        let (open, close) = self.shutter;
+        let time = if close > open {
+            open + (close - open) * rng.next_f32()
+        } else {
+            open
+        };
+        Some(self.ray_through(x, y, lens)?.with_time(time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::{Fisheye, Orthographic};
+    use crate::units::Point3D;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    fn close(a: Unit3D, b: Unit3D) -> bool {
+        (a - b).abs() < 0.0001
+    }
+
+    fn point_close(a: Point3D, b: Point3D) -> bool {
+        close(a.x(), b.x()) && close(a.y(), b.y()) && close(a.z(), b.z())
+    }
+
+    fn vector_close(a: Vector3D, b: Vector3D) -> bool {
+        close(a.x(), b.x()) && close(a.y(), b.y()) && close(a.z(), b.z())
+    }
+
+    /// Where `ray` crosses the plane `z` in front of the camera, in the
+    /// camera's own space
+    fn depth(camera: &Camera, ray: &Ray, z: Unit3D) -> Point3D {
+        let local = ray.transform(&camera.transform());
+        local.position((z - local.origin.z()) / local.direction.z())
+    }
+
+    #[test]
+    fn test_rays_through_the_canvas() {
+        let camera = Camera::new(201, 101, FRAC_PI_2);
+        let center = camera.ray_for_pixel(100, 50).unwrap();
+        assert!(point_close(Point3D::new(0.0, 0.0, 0.0), center.origin));
+        assert!(vector_close(
+            Vector3D::new(0.0, 0.0, -1.0),
+            center.direction
+        ));
+
+        let corner = camera.ray_for_pixel(0, 0).unwrap();
+        assert!(vector_close(
+            Vector3D::new(0.66519, 0.33259, -0.66851),
+            corner.direction
+        ));
+    }
+
+    #[test]
+    fn test_rays_from_a_moved_camera() {
+        let mut camera = Camera::new(201, 101, FRAC_PI_2);
+        camera.set_transform(
+            Matrix::rotation_y(FRAC_PI_4) * Matrix::translation(Point3D::new(0.0, -2.0, 5.0)),
+        );
+        let ray = camera.ray_for_pixel(100, 50).unwrap();
+        let half = 2.0f32.sqrt() / 2.0;
+        assert!(point_close(Point3D::new(0.0, 2.0, -5.0), ray.origin));
+        assert!(vector_close(Vector3D::new(half, 0.0, -half), ray.direction));
+    }
+
+    #[test]
+    fn test_lens_rays_meet_at_the_focal_distance() {
+        let mut camera = Camera::new(40, 30, 1.0);
+        camera.set_transform(Matrix::view_transform(
+            Point3D::new(3.0, 0.0, 4.0),
+            Point3D::new(0.0, 0.0, 0.0),
+            Vector3D::new(0.0, 1.0, 0.0),
+        ));
+        camera.set_aperture(0.5);
+        camera.set_focal_distance(4.0);
+
+        let mut rng = Rng::new(5);
+        let pinhole = camera.ray_through(10.25, 20.75, (0.0, 0.0)).unwrap();
+        let focus = depth(&camera, &pinhole, -4.0);
+        let mut origins = Vec::new();
+        for _ in 0..20 {
+            let ray = camera.ray_for_sample(10.25, 20.75, &mut rng).unwrap();
+            assert!((focus - depth(&camera, &ray, -4.0)).magnitude() < 0.001);
+            origins.push(ray.origin);
+            // The lens is 0.5 across, centered on the camera
+            assert!((ray.origin - pinhole.origin).magnitude() <= 0.25 + 0.0001);
+        }
+        // Nearer than the focal plane the rays spread out again
+        let edge = camera.ray_through(10.25, 20.75, (1.0, 0.0)).unwrap();
+        let near = depth(&camera, &edge, -2.0);
+        assert!(!point_close(depth(&camera, &pinhole, -2.0), near));
+        assert!(origins.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn test_pinhole_cameras_ignore_the_lens() {
+        let camera = Camera::new(10, 10, 1.0);
+        let mut rng = Rng::new(1);
+        let ray = camera.ray_for_sample(3.5, 4.5, &mut rng);
+        assert_eq!(camera.ray_for_pixel(3, 4), ray);
+        assert_eq!(Rng::new(1), rng);
+    }
+
+    #[test]
+    fn test_cameras_use_their_projection() {
+        let mut camera = Camera::with_projection(20, 10, Orthographic::new(4.0));
+        camera.set_transform(Matrix::translation(Point3D::new(0.0, 0.0, -5.0)));
+        let corner = camera.ray_for_pixel(0, 0).unwrap();
+        assert!(point_close(Point3D::new(1.9, 0.9, 5.0), corner.origin));
+        assert!(vector_close(
+            Vector3D::new(0.0, 0.0, -1.0),
+            corner.direction
+        ));
+
+        camera.set_projection(Fisheye::new(PI));
+        assert!(camera.ray_for_pixel(0, 0).is_none());
+        assert!(camera.ray_for_pixel(10, 5).is_some());
+    }
+
+    #[test]
+    fn test_rays_are_cast_while_the_shutter_is_open() {
+        let mut camera = Camera::new(10, 10, 1.0);
+        camera.set_shutter(0.25, 0.75);
+        assert_eq!(0.25, camera.ray_for_pixel(1, 1).unwrap().time);
+        let mut rng = Rng::new(6);
+        let times: Vec<_> = (0..50)
+            .map(|_| camera.ray_for_sample(1.5, 1.5, &mut rng).unwrap().time)
+            .collect();
+        assert!(times.iter().all(|time| (0.25..0.75).contains(time)));
+        assert!(times.iter().any(|&time| time < 0.4));
+        assert!(times.iter().any(|&time| time > 0.6));
+    }
+
+    #[test]
+    fn test_bokeh_samples_stay_in_their_shape() {
+        let mut rng = Rng::new(2);
+        let square = Bokeh::Polygon {
+            sides: 4,
+            rotation: 0.0,
+        };
+        for _ in 0..200 {
+            let (u, v) = (rng.next_f32(), rng.next_f32());
+            let (x, y) = Bokeh::Disk.sample(u, v);
+            assert!(x * x + y * y <= 1.0 + 0.0001);
+            // The corners of the square are on the axes
+            let (x, y) = square.sample(u, v);
+            assert!(x.abs() + y.abs() <= 1.0 + 0.0001);
+        }
+        let (x, y) = square.sample(0.0, 0.9999);
+        assert!(close(1.0, x + y.abs()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_polygonal_lenses_need_three_sides() {
+        Camera::new(10, 10, 1.0).set_bokeh(Bokeh::Polygon {
+            sides: 2,
+            rotation: 0.0,
+        });
+    }
+}
@@ -1,5 +1,6 @@
 use crate::color::Color;
-use crate::units::{Tuple, Point3D};
+use crate::units::{Point3D, Tuple};
+use std::error::Error;
 use std::fmt;
 use textwrap;
 
@@ -28,12 +29,24 @@ impl Row {
 
         self.pixels[col] = color;
     }
+
+    pub fn get(&self, col: usize) -> Option<&Color> {
+        self.pixels.get(col)
+    }
+
+    pub fn get_mut(&mut self, col: usize) -> Option<&mut Color> {
+        self.pixels.get_mut(col)
+    }
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub struct Pixel(usize, usize);
 
 impl Pixel {
+    pub fn new(x: usize, y: usize) -> Self {
+        Self(x, y)
+    }
+
     pub fn x(&self) -> usize {
         self.0
     }
@@ -41,6 +54,15 @@ impl Pixel {
     pub fn y(&self) -> usize {
         self.1
     }
+
+    /// Convert a Point3D into a canvas Pixel, refusing points which round to
+    /// negative coordinates rather than saturating them to zero.
+    pub fn try_from_point(point: Point3D) -> Result<Self, OutOfRange> {
+        match signed_coordinates(point) {
+            Some((x, y)) if x >= 0 && y >= 0 => Ok(Pixel(x as usize, y as usize)),
+            _ => Err(OutOfRange { point }),
+        }
+    }
 }
 
 impl From<Point3D> for Pixel {
@@ -50,15 +72,104 @@ impl From<Point3D> for Pixel {
     }
 }
 
+/// Round the x and y coordinates of `point` to the nearest whole numbers,
+/// or `None` if either is not a finite number.
+fn signed_coordinates(point: Point3D) -> Option<(i64, i64)> {
+    if point.x().is_finite() && point.y().is_finite() {
+        Some((point.x().round() as i64, point.y().round() as i64))
+    } else {
+        None
+    }
+}
+
+/// Returned when a point cannot be converted to a pixel on a canvas
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct OutOfRange {
+    pub point: Point3D,
+}
+
+impl fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "point {} does not map to a canvas pixel", self.point)
+    }
+}
+
+impl Error for OutOfRange {}
+
+/// Where a canvas places the point (0, 0) and which way its y axis grows.
+/// Pixels are always addressed by row, the origin decides how rows are laid
+/// out in the output image and how points are converted to pixels.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Origin {
+    /// (0, 0) is the top left corner and y grows down the image
+    TopLeft,
+    /// (0, 0) is the bottom left corner and y grows up the image
+    #[default]
+    BottomLeft,
+    /// (0, 0) is the middle of the canvas and y grows up the image
+    Center,
+}
+
+/// A rectangle in world space, between the `min` and `max` corners, which
+/// is stretched to cover a whole canvas when plotting. Only the x and y
+/// coordinates are used, y always grows upward in world space.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Viewport {
+    min: Point3D,
+    max: Point3D,
+}
+
+impl Viewport {
+    pub fn new(min: Point3D, max: Point3D) -> Self {
+        Self { min, max }
+    }
+
+    pub fn min(&self) -> Point3D {
+        self.min
+    }
+
+    pub fn max(&self) -> Point3D {
+        self.max
+    }
+}
+
+/// Returned when a pixel lies outside the canvas it was used with
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct OutOfBounds {
+    pub position: Pixel,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pixel ({}, {}) is outside the {}x{} canvas",
+            self.position.x(),
+            self.position.y(),
+            self.width,
+            self.height,
+        )
+    }
+}
+
+impl Error for OutOfBounds {}
+
 #[derive(Debug, Clone)]
 pub struct Canvas {
     height: usize,
     width: usize,
+    origin: Origin,
     rows: Vec<Row>,
 }
 
 impl Canvas {
     pub fn new(width: usize, height: usize) -> Self {
+        Self::with_origin(width, height, Origin::default())
+    }
+
+    pub fn with_origin(width: usize, height: usize, origin: Origin) -> Self {
         let mut rows: Vec<Row> = Vec::with_capacity(height);
         for _ in 0..height {
             rows.push(Row::new(width));
@@ -67,6 +178,7 @@ impl Canvas {
         Self {
             height,
             width,
+            origin,
             rows,
         }
     }
@@ -85,6 +197,54 @@ impl Canvas {
         }
     }
 
+    /// Set the pixel at `position` to `color`, or return an `OutOfBounds`
+    /// error leaving the canvas untouched if it lies outside the canvas.
+    pub fn try_set_pixel(&mut self, position: Pixel, color: Color) -> Result<(), OutOfBounds> {
+        match self.get_mut(position) {
+            Some(pixel) => {
+                *pixel = color;
+                Ok(())
+            }
+            None => Err(OutOfBounds {
+                position,
+                width: self.width,
+                height: self.height,
+            }),
+        }
+    }
+
+    /// Set the pixel at `position` to `color`, silently dropping writes which
+    /// fall outside the canvas.
+    pub fn set_pixel_clipped(&mut self, position: Pixel, color: Color) {
+        if let Some(pixel) = self.get_mut(position) {
+            *pixel = color;
+        }
+    }
+
+    /// Composite `color` over the pixel at `position` using its alpha
+    /// channel, silently dropping writes which fall outside the canvas.
+    pub fn blend_pixel(&mut self, position: Pixel, color: Color) {
+        if let Some(pixel) = self.get_mut(position) {
+            *pixel = color.over(*pixel);
+        }
+    }
+
+    /// The color of the pixel at `position`, or `None` if it lies outside
+    /// the canvas.
+    pub fn get(&self, position: Pixel) -> Option<&Color> {
+        self.rows
+            .get(position.y())
+            .and_then(|row| row.get(position.x()))
+    }
+
+    /// A mutable reference to the pixel at `position`, or `None` if it lies
+    /// outside the canvas.
+    pub fn get_mut(&mut self, position: Pixel) -> Option<&mut Color> {
+        self.rows
+            .get_mut(position.y())
+            .and_then(|row| row.get_mut(position.x()))
+    }
+
     pub fn width(&self) -> usize {
         self.width
     }
@@ -93,9 +253,84 @@ impl Canvas {
         self.height
     }
 
+    pub fn origin(&self) -> Origin {
+        self.origin
+    }
+
     pub fn in_bounds(&self, position: Point3D) -> bool {
-        let pixel: Pixel = position.into();
-        pixel.x() < self.width() && pixel.y() < self.height()
+        self.pixel_at(position).is_ok()
+    }
+
+    /// Convert a point in canvas coordinates, relative to the canvas origin,
+    /// to the pixel it falls on. Points which round to a position outside
+    /// the canvas return an `OutOfRange` error.
+    pub fn pixel_at(&self, position: Point3D) -> Result<Pixel, OutOfRange> {
+        let (x, y) = signed_coordinates(position).ok_or(OutOfRange { point: position })?;
+        let (offset_x, offset_y) = self.origin_offset();
+        self.signed_pixel(x + offset_x, y + offset_y)
+            .ok_or(OutOfRange { point: position })
+    }
+
+    /// The pixel position of the canvas origin, the amount added to canvas
+    /// coordinates to get pixel coordinates.
+    pub(crate) fn origin_offset(&self) -> (i64, i64) {
+        match self.origin {
+            Origin::TopLeft | Origin::BottomLeft => (0, 0),
+            Origin::Center => ((self.width / 2) as i64, (self.height / 2) as i64),
+        }
+    }
+
+    /// The pixels of each row of the canvas, first row first
+    pub(crate) fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Color]> {
+        self.rows.iter_mut().map(|row| row.pixels.as_mut_slice())
+    }
+
+    /// The direction rows move in pixel coordinates going down the image
+    pub(crate) fn down(&self) -> i64 {
+        match self.origin {
+            Origin::TopLeft => 1,
+            Origin::BottomLeft | Origin::Center => -1,
+        }
+    }
+
+    /// The pixel row which is `row` rows from the top of the image
+    pub(crate) fn visual_row(&self, row: i64) -> i64 {
+        match self.origin {
+            Origin::TopLeft => row,
+            Origin::BottomLeft | Origin::Center => self.height as i64 - 1 - row,
+        }
+    }
+
+    /// The pixel at signed pixel coordinates `x`, `y` or `None` if they lie
+    /// outside the canvas.
+    pub(crate) fn signed_pixel(&self, x: i64, y: i64) -> Option<Pixel> {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            None
+        } else {
+            Some(Pixel(x as usize, y as usize))
+        }
+    }
+
+    /// Convert a point in world coordinates to the pixel it falls on when
+    /// `viewport` is stretched across the whole canvas. World y grows upward
+    /// whatever the canvas origin.
+    pub fn project(&self, viewport: &Viewport, position: Point3D) -> Result<Pixel, OutOfRange> {
+        let span_x = viewport.max().x() - viewport.min().x();
+        let span_y = viewport.max().y() - viewport.min().y();
+        let u = (position.x() - viewport.min().x()) / span_x;
+        let v = (position.y() - viewport.min().y()) / span_y;
+        let v = match self.origin {
+            Origin::TopLeft => 1.0 - v,
+            Origin::BottomLeft | Origin::Center => v,
+        };
+
+        let last_col = self.width.saturating_sub(1) as f32;
+        let last_row = self.height.saturating_sub(1) as f32;
+        let scaled = Point3D::new(u * last_col, v * last_row, 0.0);
+        match Pixel::try_from_point(scaled) {
+            Ok(pixel) if pixel.x() < self.width && pixel.y() < self.height => Ok(pixel),
+            _ => Err(OutOfRange { point: position }),
+        }
     }
 }
 
@@ -107,6 +342,12 @@ impl std::ops::Index<Pixel> for Canvas {
     }
 }
 
+impl std::ops::IndexMut<Pixel> for Canvas {
+    fn index_mut(&mut self, position: Pixel) -> &mut Self::Output {
+        &mut self.rows[position.y()].pixels[position.x()]
+    }
+}
+
 pub struct Ppm {
     width: usize,
     height: usize,
@@ -122,6 +363,54 @@ impl Ppm {
         }
     }
 
+    /// Parse a plain (P3) or raw (P6) PPM image. Samples with a maximum
+    /// value other than 255 are rescaled to 0-255.
+    pub fn parse(data: &[u8]) -> Result<Self, PpmError> {
+        let mut reader = PpmReader { data, at: 0 };
+        let magic = reader.token().ok_or(PpmError::Truncated)?;
+        let raw = match magic {
+            b"P3" => false,
+            b"P6" => true,
+            _ => return Err(PpmError::UnsupportedFormat),
+        };
+        let width = reader.number()?;
+        let height = reader.number()?;
+        let max_value = reader.number()?;
+        if max_value == 0 || max_value > 65535 {
+            return Err(PpmError::InvalidHeader);
+        }
+
+        let samples = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .ok_or(PpmError::InvalidHeader)?;
+        let mut body = Vec::with_capacity(samples.min(data.len()));
+        if raw {
+            // A single whitespace byte separates the header from the samples
+            reader.at += 1;
+            let size = if max_value > 255 { 2 } else { 1 };
+            for _ in 0..samples {
+                let bytes = reader.bytes(size).ok_or(PpmError::Truncated)?;
+                let value = bytes.iter().fold(0, |v, b| v << 8 | *b as usize);
+                body.push(rescale(value, max_value)?);
+            }
+        } else {
+            for _ in 0..samples {
+                body.push(rescale(reader.number()?, max_value)?);
+            }
+        }
+
+        Ok(Self::new(width, height, body))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
     pub fn header(&self) -> String {
         format!("P3\n{} {}\n255", self.width, self.height)
     }
@@ -130,6 +419,15 @@ impl Ppm {
         self.body.as_slice()
     }
 
+    /// The image as a raw (P6) PPM file, one byte per sample
+    pub fn to_raw(&self) -> Vec<u8> {
+        let header = format!("P6\n{} {}\n255\n", self.width, self.height);
+        let mut data = Vec::with_capacity(header.len() + self.body.len());
+        data.extend_from_slice(header.as_bytes());
+        data.extend_from_slice(&self.body);
+        data
+    }
+
     pub fn len(&self) -> usize {
         self.body.len() / 3
     }
@@ -144,7 +442,12 @@ impl From<&Canvas> for Ppm {
         let width = canvas.width;
         let height = canvas.height;
         let mut data: Vec<u8> = Vec::with_capacity(width * height * 3);
-        for row in canvas.rows.iter().rev() {
+        // PPM images are written top row first
+        let rows: Box<dyn Iterator<Item = &Row>> = match canvas.origin {
+            Origin::TopLeft => Box::new(canvas.rows.iter()),
+            Origin::BottomLeft | Origin::Center => Box::new(canvas.rows.iter().rev()),
+        };
+        for row in rows {
             for pixel in row.pixels.iter() {
                 for color in pixel.as_rgb_bytes().iter() {
                     data.push(*color);
@@ -160,6 +463,104 @@ impl From<&Canvas> for Ppm {
     }
 }
 
+impl From<&Ppm> for Canvas {
+    // The image's top row is the last row of a bottom left origin canvas
+    fn from(ppm: &Ppm) -> Self {
+        let mut canvas = Canvas::new(ppm.width, ppm.height);
+        let rgb = ppm.body.chunks(3).map(|c| {
+            Color::rgb(
+                c[0] as f32 / 255.0,
+                c[1] as f32 / 255.0,
+                c[2] as f32 / 255.0,
+            )
+        });
+        for (index, color) in rgb.enumerate() {
+            let row = ppm.height - 1 - index / ppm.width;
+            canvas.rows[row].pixels[index % ppm.width] = color;
+        }
+        canvas
+    }
+}
+
+/// Returned when bytes can't be parsed as a PPM image
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PpmError {
+    /// The image isn't a P3 or P6 PPM
+    UnsupportedFormat,
+    /// The width, height or maximum value is missing or invalid
+    InvalidHeader,
+    /// A sample is not a number or is larger than the maximum value
+    InvalidSample,
+    /// The image ends before all of its samples
+    Truncated,
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            PpmError::UnsupportedFormat => "not a P3 or P6 PPM image",
+            PpmError::InvalidHeader => "invalid PPM header",
+            PpmError::InvalidSample => "invalid PPM sample value",
+            PpmError::Truncated => "PPM image is truncated",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl Error for PpmError {}
+
+/// A cursor over the bytes of a PPM image
+struct PpmReader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> PpmReader<'a> {
+    /// The next whitespace separated token, skipping `#` comments
+    fn token(&mut self) -> Option<&'a [u8]> {
+        loop {
+            match self.data.get(self.at) {
+                Some(b'#') => {
+                    while !matches!(self.data.get(self.at), Some(b'\n') | None) {
+                        self.at += 1;
+                    }
+                }
+                Some(byte) if byte.is_ascii_whitespace() => self.at += 1,
+                Some(_) => break,
+                None => return None,
+            }
+        }
+
+        let start = self.at;
+        while matches!(self.data.get(self.at), Some(byte) if !byte.is_ascii_whitespace()) {
+            self.at += 1;
+        }
+        Some(&self.data[start..self.at])
+    }
+
+    fn number(&mut self) -> Result<usize, PpmError> {
+        let token = self.token().ok_or(PpmError::Truncated)?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .ok_or(PpmError::InvalidSample)
+    }
+
+    fn bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.at..self.at + count)?;
+        self.at += count;
+        Some(bytes)
+    }
+}
+
+/// Scale a sample from 0-`max_value` to 0-255
+fn rescale(value: usize, max_value: usize) -> Result<u8, PpmError> {
+    if value > max_value {
+        return Err(PpmError::InvalidSample);
+    }
+    Ok(((value * 255 + max_value / 2) / max_value) as u8)
+}
+
 impl fmt::Display for Ppm {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s_values: Vec<_> = self.body.iter().map(|c| format!("{}", c)).collect();
@@ -219,6 +620,79 @@ mod tests {
         assert_eq!(Color::rgb(1.0, 1.0, 1.0), canvas[Pixel(4, 14)]);
     }
 
+    #[test]
+    fn test_can_get_a_pixel_within_the_canvas() {
+        let mut canvas = Canvas::new(20, 10);
+        canvas.set_pixel(Pixel(19, 9), Color::rgb(1.0, 1.0, 1.0));
+        assert_eq!(Some(&Color::rgb(1.0, 1.0, 1.0)), canvas.get(Pixel(19, 9)));
+    }
+
+    #[test]
+    fn test_getting_a_pixel_outside_the_canvas_returns_none() {
+        let mut canvas = Canvas::new(20, 10);
+        assert_eq!(None, canvas.get(Pixel(20, 9)));
+        assert_eq!(None, canvas.get(Pixel(19, 10)));
+        assert_eq!(None, canvas.get_mut(Pixel(20, 10)));
+    }
+
+    #[test]
+    fn test_can_modify_a_pixel_through_get_mut() {
+        let mut canvas = Canvas::new(20, 10);
+        if let Some(pixel) = canvas.get_mut(Pixel(3, 4)) {
+            *pixel = Color::rgb(0.5, 0.5, 0.5);
+        }
+        assert_eq!(Color::rgb(0.5, 0.5, 0.5), canvas[Pixel(3, 4)]);
+    }
+
+    #[test]
+    fn test_try_set_pixel_reports_out_of_bounds_writes() {
+        let mut canvas = Canvas::new(20, 10);
+        assert_eq!(
+            Ok(()),
+            canvas.try_set_pixel(Pixel(0, 0), Color::rgb(1.0, 1.0, 1.0))
+        );
+        assert_eq!(
+            Err(OutOfBounds {
+                position: Pixel(5, 10),
+                width: 20,
+                height: 10
+            }),
+            canvas.try_set_pixel(Pixel(5, 10), Color::rgb(1.0, 1.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn test_clipped_writes_outside_the_canvas_are_dropped() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.set_pixel_clipped(Pixel(1, 1), Color::rgb(1.0, 1.0, 1.0));
+        canvas.set_pixel_clipped(Pixel(2, 1), Color::rgb(1.0, 1.0, 1.0));
+        canvas.set_pixel_clipped(Pixel(1, 2), Color::rgb(1.0, 1.0, 1.0));
+        assert_eq!(Color::rgb(1.0, 1.0, 1.0), canvas[Pixel(1, 1)]);
+    }
+
+    #[test]
+    fn test_blending_a_pixel_composites_over_the_existing_color() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.set_pixel(Pixel(1, 1), Color::rgb(0.0, 0.0, 1.0));
+        canvas.blend_pixel(Pixel(1, 1), Color::rgba(1.0, 0.0, 0.0, 0.5));
+        canvas.blend_pixel(Pixel(2, 1), Color::rgba(1.0, 0.0, 0.0, 0.5));
+        assert_eq!([128, 0, 128], canvas[Pixel(1, 1)].as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_can_set_a_pixel_through_index_mut() {
+        let mut canvas = Canvas::new(20, 20);
+        canvas[Pixel(4, 14)] = Color::rgb(1.0, 1.0, 1.0);
+        assert_eq!(Color::rgb(1.0, 1.0, 1.0), canvas[Pixel(4, 14)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_mut_outside_the_canvas_panics() {
+        let mut canvas = Canvas::new(20, 20);
+        canvas[Pixel(20, 14)] = Color::rgb(1.0, 1.0, 1.0);
+    }
+
     #[test]
     fn test_can_create_a_pixel_from_a_point() {
         assert_eq!(Pixel(4, 14), Pixel::from(Point3D::new(4.0, 14.0, 54.3)));
@@ -245,8 +719,7 @@ mod tests {
         for step in 0..width {
             canvas.set_pixel(Pixel(step, step), Color::rgb(1.0, 1.0, 1.0));
         }
-        let ppm = Ppm::from(&canvas);
-        ppm
+        Ppm::from(&canvas)
     }
 
     #[test]
@@ -286,4 +759,160 @@ mod tests {
         assert!(!canvas.in_bounds(Point3D::new(10.0, 9.0, 9.0)));
         assert!(!canvas.in_bounds(Point3D::new(10.0, 10.0, 10.0)));
     }
+
+    #[test]
+    fn test_converting_a_negative_point_to_a_pixel_fails() {
+        let point = Point3D::new(-3.0, 4.0, 0.0);
+        assert_eq!(Err(OutOfRange { point }), Pixel::try_from_point(point));
+        assert_eq!(
+            Ok(Pixel(0, 4)),
+            Pixel::try_from_point(Point3D::new(-0.4, 4.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_converting_a_non_finite_point_to_a_pixel_fails() {
+        let point = Point3D::new(f32::NAN, 4.0, 0.0);
+        assert!(Pixel::try_from_point(point).is_err());
+    }
+
+    #[test]
+    fn test_canvas_origin_defaults_to_bottom_left() {
+        assert_eq!(Origin::BottomLeft, Canvas::new(10, 10).origin());
+    }
+
+    #[test]
+    fn test_negative_points_are_not_in_bounds() {
+        let canvas = Canvas::new(10, 10);
+        assert!(!canvas.in_bounds(Point3D::new(-3.0, 4.0, 0.0)));
+        assert!(!canvas.in_bounds(Point3D::new(3.0, -4.0, 0.0)));
+    }
+
+    #[test]
+    fn test_center_origin_maps_zero_to_the_middle_of_the_canvas() {
+        let canvas = Canvas::with_origin(10, 6, Origin::Center);
+        assert_eq!(
+            Ok(Pixel(5, 3)),
+            canvas.pixel_at(Point3D::new(0.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            Ok(Pixel(0, 0)),
+            canvas.pixel_at(Point3D::new(-5.0, -3.0, 0.0))
+        );
+        assert!(canvas.in_bounds(Point3D::new(4.0, 2.0, 0.0)));
+        assert!(!canvas.in_bounds(Point3D::new(5.0, 2.0, 0.0)));
+        assert!(!canvas.in_bounds(Point3D::new(-6.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_ppm_rows_follow_the_canvas_origin() {
+        let white = Color::rgb(1.0, 1.0, 1.0);
+        let mut top_left = Canvas::with_origin(1, 2, Origin::TopLeft);
+        top_left.set_pixel(Pixel(0, 0), white);
+        assert_eq!(&[255, 255, 255, 0, 0, 0], Ppm::from(&top_left).body());
+
+        let mut bottom_left = Canvas::with_origin(1, 2, Origin::BottomLeft);
+        bottom_left.set_pixel(Pixel(0, 0), white);
+        assert_eq!(&[0, 0, 0, 255, 255, 255], Ppm::from(&bottom_left).body());
+    }
+
+    #[test]
+    fn test_viewport_corners_map_to_canvas_corners() {
+        let viewport = Viewport::new(Point3D::new(-1.0, 0.0, 0.0), Point3D::new(1.0, 10.0, 0.0));
+        let canvas = Canvas::new(11, 21);
+        assert_eq!(Ok(Pixel(0, 0)), canvas.project(&viewport, viewport.min()));
+        assert_eq!(Ok(Pixel(10, 20)), canvas.project(&viewport, viewport.max()));
+        assert_eq!(
+            Ok(Pixel(5, 10)),
+            canvas.project(&viewport, Point3D::new(0.0, 5.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_viewport_flips_y_for_top_left_canvases() {
+        let viewport = Viewport::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 0.0));
+        let canvas = Canvas::with_origin(11, 11, Origin::TopLeft);
+        assert_eq!(
+            Ok(Pixel(0, 10)),
+            canvas.project(&viewport, Point3D::new(0.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_points_outside_the_viewport_are_out_of_range() {
+        let viewport = Viewport::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 0.0));
+        let canvas = Canvas::new(11, 11);
+        let point = Point3D::new(1.5, 0.5, 0.0);
+        assert_eq!(Err(OutOfRange { point }), canvas.project(&viewport, point));
+        assert!(canvas
+            .project(&viewport, Point3D::new(0.5, -0.5, 0.0))
+            .is_err());
+    }
+
+    #[test]
+    fn test_parses_a_plain_ppm() {
+        let ppm = Ppm::parse(b"P3\n# a comment\n2 1\n255\n255 0 0  0 128 255\n").unwrap();
+        assert_eq!(2, ppm.width());
+        assert_eq!(1, ppm.height());
+        assert_eq!(&[255, 0, 0, 0, 128, 255], ppm.body());
+    }
+
+    #[test]
+    fn test_parses_a_raw_ppm() {
+        let mut data = b"P6 1 2 255\n".to_vec();
+        data.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+        let ppm = Ppm::parse(&data).unwrap();
+        assert_eq!(&[1, 2, 3, 4, 5, 6], ppm.body());
+    }
+
+    #[test]
+    fn test_writes_a_raw_ppm() {
+        let ppm = Ppm::new(1, 2, vec![1, 2, 3, 4, 5, 6]);
+        let data = ppm.to_raw();
+        assert!(data.starts_with(b"P6\n1 2\n255\n"));
+        assert_eq!(ppm.body(), Ppm::parse(&data).unwrap().body());
+    }
+
+    #[test]
+    fn test_rescales_ppm_samples_to_255() {
+        let ppm = Ppm::parse(b"P3 1 1 15 15 0 5").unwrap();
+        assert_eq!(&[255, 0, 85], ppm.body());
+    }
+
+    #[test]
+    fn test_rejects_invalid_ppms() {
+        assert_eq!(
+            Err(PpmError::UnsupportedFormat),
+            Ppm::parse(b"P5 1 1 255 0").map(|_| ())
+        );
+        assert_eq!(
+            Err(PpmError::Truncated),
+            Ppm::parse(b"P3 1 1 255 0 0").map(|_| ())
+        );
+        assert_eq!(
+            Err(PpmError::InvalidSample),
+            Ppm::parse(b"P3 1 1 255 0 0 256").map(|_| ())
+        );
+        assert_eq!(
+            Err(PpmError::InvalidHeader),
+            Ppm::parse(b"P3 1 1 0 0 0 0").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_a_canvas_survives_a_trip_through_ppm() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set_pixel(Pixel(0, 0), Color::rgb(1.0, 0.0, 0.0));
+        canvas.set_pixel(Pixel(2, 1), Color::rgb(0.0, 0.0, 1.0));
+        let text = format!("{}", Ppm::from(&canvas));
+        let copy = Canvas::from(&Ppm::parse(text.as_bytes()).unwrap());
+        for y in 0..2 {
+            for x in 0..3 {
+                assert_eq!(
+                    canvas[Pixel(x, y)].as_rgb_bytes(),
+                    copy[Pixel(x, y)].as_rgb_bytes()
+                );
+            }
+        }
+    }
 }
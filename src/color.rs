@@ -1,4 +1,4 @@
-use std::ops::{Add, Div, Mul, Sub};
+use std::ops::{Add, AddAssign, Div, Mul, Sub};
 
 #[derive(Debug, Copy, Clone)]
 pub struct Color(f32, f32, f32, f32);
@@ -33,6 +33,29 @@ impl Color {
         self.3
     }
 
+    /// The same color with its alpha channel replaced by `a`
+    pub fn with_alpha(&self, a: f32) -> Self {
+        Self::rgba(self.r(), self.g(), self.b(), a)
+    }
+
+    /// Composite this color over a `background` color using its alpha
+    /// channel, the Porter-Duff "source over" operator.
+    pub fn over(&self, background: Color) -> Self {
+        let alpha = self.a() + background.a() * (1.0 - self.a());
+        if alpha <= 0.0 {
+            return Self::rgba(0.0, 0.0, 0.0, 0.0);
+        }
+
+        let blend =
+            |fg: f32, bg: f32| (fg * self.a() + bg * background.a() * (1.0 - self.a())) / alpha;
+        Self::rgba(
+            blend(self.r(), background.r()),
+            blend(self.g(), background.g()),
+            blend(self.b(), background.b()),
+            alpha,
+        )
+    }
+
     pub fn as_rgb_bytes(&self) -> [u8; 3] {
         [
             (self.r() * 255.0).min(255.0).round() as u8,
@@ -93,13 +116,110 @@ impl Div<f32> for Color {
     }
 }
 
+/// An amount of light in red, green and blue, or the fraction of it a
+/// surface passes on. Unlike `Color` it isn't clamped to 1, so bright lights
+/// and the sums of many samples keep their full value until they are tone
+/// mapped.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Spectrum(pub f32, pub f32, pub f32);
+
+impl Spectrum {
+    pub fn new(r: f32, g: f32, b: f32) -> Self {
+        Self(r, g, b)
+    }
+
+    /// The same amount in every channel
+    pub fn gray(value: f32) -> Self {
+        Self(value, value, value)
+    }
+
+    pub fn black() -> Self {
+        Self(0.0, 0.0, 0.0)
+    }
+
+    pub fn r(&self) -> f32 {
+        self.0
+    }
+
+    pub fn g(&self) -> f32 {
+        self.1
+    }
+
+    pub fn b(&self) -> f32 {
+        self.2
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.0 <= 0.0 && self.1 <= 0.0 && self.2 <= 0.0
+    }
+
+    /// The largest of the channels
+    pub fn max_channel(&self) -> f32 {
+        self.0.max(self.1).max(self.2)
+    }
+
+    /// Relative luminance using the Rec. 709 primaries
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.0 + 0.7152 * self.1 + 0.0722 * self.2
+    }
+
+    /// The color this shows as, clamped to what a `Color` can hold
+    pub fn to_color(&self) -> Color {
+        Color::rgb(self.0.max(0.0), self.1.max(0.0), self.2.max(0.0))
+    }
+}
+
+impl From<Color> for Spectrum {
+    fn from(color: Color) -> Self {
+        Self(color.r(), color.g(), color.b())
+    }
+}
+
+impl Add for Spectrum {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0, self.1 + rhs.1, self.2 + rhs.2)
+    }
+}
+
+impl AddAssign for Spectrum {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Mul for Spectrum {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self(self.0 * rhs.0, self.1 * rhs.1, self.2 * rhs.2)
+    }
+}
+
+impl Mul<f32> for Spectrum {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self {
+        Self(self.0 * rhs, self.1 * rhs, self.2 * rhs)
+    }
+}
+
+impl Div<f32> for Spectrum {
+    type Output = Self;
+
+    fn div(self, rhs: f32) -> Self {
+        Self(self.0 / rhs, self.1 / rhs, self.2 / rhs)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_similar_colors_are_equal() {
-        let c1 = Color::rgb(0.3489778009, 1.097864356, 0.03747588);
+        let c1 = Color::rgb(0.3489778, 1.0978644, 0.03747588);
         let c2 = Color::rgb(0.3489, 1.0978, 0.0374);
         assert_eq!(c1, c2);
     }
@@ -139,4 +259,38 @@ mod tests {
     fn test_multiplying_a_color_by_a_scalar_equals_a_color() {
         assert_eq!(Color::rgb(0.4, 0.6, 0.8), Color::rgb(0.2, 0.3, 0.4) * 2.0);
     }
+
+    #[test]
+    fn test_an_opaque_color_over_another_replaces_it() {
+        let fg = Color::rgb(0.2, 0.4, 0.6);
+        let blended = fg.over(Color::rgb(1.0, 1.0, 1.0));
+        assert_eq!(fg.as_rgb_bytes(), blended.as_rgb_bytes());
+        assert_eq!(1.0, blended.a());
+    }
+
+    #[test]
+    fn test_a_transparent_color_over_another_leaves_it_unchanged() {
+        let bg = Color::rgb(0.2, 0.4, 0.6);
+        let blended = Color::rgba(1.0, 1.0, 1.0, 0.0).over(bg);
+        assert_eq!(bg.as_rgb_bytes(), blended.as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_a_translucent_color_over_another_mixes_them() {
+        let blended = Color::rgba(1.0, 0.0, 0.0, 0.25).over(Color::rgb(0.0, 0.0, 1.0));
+        assert_eq!([64, 0, 191], blended.as_rgb_bytes());
+        assert_eq!(1.0, blended.a());
+    }
+
+    #[test]
+    fn test_spectra_are_not_clamped() {
+        let light = Spectrum::from(Color::rgb(0.5, 0.25, 1.0)) * 8.0;
+        assert_eq!(Spectrum::new(4.0, 2.0, 8.0), light);
+        assert_eq!(8.0, light.max_channel());
+        let mut sum = light + Spectrum::gray(1.0);
+        sum += Spectrum::new(1.0, 0.0, 0.0) * light;
+        assert_eq!(Spectrum::new(9.0, 3.0, 9.0), sum);
+        assert_eq!([255, 255, 255], sum.to_color().as_rgb_bytes());
+        assert!(Spectrum::black().is_black());
+    }
 }
@@ -0,0 +1,402 @@
+//! Constructive solid geometry. A `Csg` combines two operands, each a leaf
+//! object, a shape or another `Csg`, and decides which of the surfaces a ray
+//! crosses belong to the combined solid. Leaves are referred to by id and
+//! intersected by the caller, so any kind of object can be used.
+//!
+//! A `Csg` of shapes is a `Shape` itself, so it can be placed in a
+//! `SceneGraph`. A whole `SceneGraph` is a shape as well, the union of its
+//! objects, so a group of transformed shapes can be one operand.
+
+use crate::bounds::BoundingBox;
+use crate::bvh::Bounded;
+use crate::ray::Ray;
+use crate::shape::{Crossing, Shape};
+use crate::units::{Unit3D, EPSILON};
+use std::sync::Arc;
+
+/// How the two operands of a `Csg` are combined
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Operation {
+    /// Everything inside either operand
+    Union,
+    /// Only what is inside both operands
+    Intersection,
+    /// What is inside the left operand but not the right
+    Difference,
+}
+
+impl Operation {
+    /// Whether a surface crossing belongs to the combined solid. `left_hit`
+    /// is true if the surface belongs to the left operand, and `in_left` and
+    /// `in_right` say whether the ray is inside each operand at that point.
+    pub fn allows(&self, left_hit: bool, in_left: bool, in_right: bool) -> bool {
+        match self {
+            Operation::Union => (left_hit && !in_right) || (!left_hit && !in_left),
+            Operation::Intersection => (left_hit && in_right) || (!left_hit && in_left),
+            Operation::Difference => (left_hit && !in_right) || (!left_hit && in_left),
+        }
+    }
+}
+
+/// A ray crossing the surface of leaf object `leaf` at distance `t`
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Hit {
+    pub t: Unit3D,
+    pub leaf: usize,
+}
+
+impl Hit {
+    pub fn new(t: Unit3D, leaf: usize) -> Self {
+        Self { t, leaf }
+    }
+}
+
+/// A surface crossing which can be sorted and filtered by its distance
+pub(crate) trait Crossed: Copy {
+    fn t(&self) -> Unit3D;
+
+    /// The crossing as part of a surface facing the other way, where it
+    /// is carved out of another solid
+    fn inverted(self) -> Self;
+}
+
+impl Crossed for Hit {
+    fn t(&self) -> Unit3D {
+        self.t
+    }
+
+    fn inverted(self) -> Self {
+        self
+    }
+}
+
+impl Crossed for Crossing {
+    fn t(&self) -> Unit3D {
+        self.t
+    }
+
+    fn inverted(self) -> Self {
+        Crossing::new(self.t, -self.normal)
+    }
+}
+
+/// One side of a `Csg`
+#[derive(Debug, Clone)]
+pub enum Operand {
+    /// An object intersected by the caller of `Csg::intersect`
+    Leaf(usize),
+    /// A shape, in the space of the `Csg`
+    Shape(Arc<dyn Shape>),
+    Csg(Box<Csg>),
+}
+
+impl From<Csg> for Operand {
+    fn from(csg: Csg) -> Self {
+        Operand::Csg(Box::new(csg))
+    }
+}
+
+impl From<Arc<dyn Shape>> for Operand {
+    fn from(shape: Arc<dyn Shape>) -> Self {
+        Operand::Shape(shape)
+    }
+}
+
+impl Operand {
+    pub fn includes(&self, leaf: usize) -> bool {
+        match self {
+            Operand::Leaf(id) => *id == leaf,
+            Operand::Shape(_) => false,
+            Operand::Csg(csg) => csg.includes(leaf),
+        }
+    }
+
+    fn crossings<C, F>(&self, leaf: &F) -> Vec<C>
+    where
+        C: Crossed,
+        F: Fn(&Operand) -> Vec<C>,
+    {
+        match self {
+            Operand::Csg(csg) => csg.crossings(leaf),
+            _ => leaf(self),
+        }
+    }
+
+    /// The box around the operand. The caller's leaves aren't known here,
+    /// so they count as empty.
+    fn bounds(&self) -> BoundingBox {
+        match self {
+            Operand::Leaf(_) => BoundingBox::empty(),
+            Operand::Shape(shape) => shape.bounds(),
+            Operand::Csg(csg) => csg.bounds(),
+        }
+    }
+}
+
+/// Two operands combined by an `Operation`
+#[derive(Debug, Clone)]
+pub struct Csg {
+    operation: Operation,
+    left: Operand,
+    right: Operand,
+}
+
+impl Csg {
+    pub fn new<L: Into<Operand>, R: Into<Operand>>(
+        operation: Operation,
+        left: L,
+        right: R,
+    ) -> Self {
+        Self {
+            operation,
+            left: left.into(),
+            right: right.into(),
+        }
+    }
+
+    pub fn union<L: Into<Operand>, R: Into<Operand>>(left: L, right: R) -> Self {
+        Self::new(Operation::Union, left, right)
+    }
+
+    pub fn intersection<L: Into<Operand>, R: Into<Operand>>(left: L, right: R) -> Self {
+        Self::new(Operation::Intersection, left, right)
+    }
+
+    pub fn difference<L: Into<Operand>, R: Into<Operand>>(left: L, right: R) -> Self {
+        Self::new(Operation::Difference, left, right)
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    pub fn left(&self) -> &Operand {
+        &self.left
+    }
+
+    pub fn right(&self) -> &Operand {
+        &self.right
+    }
+
+    /// Whether leaf `leaf` appears anywhere in the tree
+    pub fn includes(&self, leaf: usize) -> bool {
+        self.left.includes(leaf) || self.right.includes(leaf)
+    }
+
+    /// The surfaces of the combined solid crossed by a ray, sorted by
+    /// distance. `hits` returns the distances at which the ray crosses
+    /// the surface of a leaf. Shape operands are ignored, since there is
+    /// no ray to intersect them with.
+    pub fn intersect<F>(&self, hits: &F) -> Vec<Hit>
+    where
+        F: Fn(usize) -> Vec<Unit3D>,
+    {
+        self.crossings(&|operand: &Operand| match operand {
+            Operand::Leaf(id) => hits(*id).into_iter().map(|t| Hit::new(t, *id)).collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    fn crossings<C, F>(&self, leaf: &F) -> Vec<C>
+    where
+        C: Crossed,
+        F: Fn(&Operand) -> Vec<C>,
+    {
+        combine(
+            self.operation,
+            self.left.crossings(leaf),
+            self.right.crossings(leaf),
+        )
+    }
+}
+
+impl Shape for Csg {
+    /// Leaf operands are ignored, since nothing says what they are
+    fn intersect(&self, ray: &Ray) -> Vec<Crossing> {
+        self.crossings(&|operand: &Operand| match operand {
+            Operand::Shape(shape) => shape.intersect(ray),
+            _ => Vec::new(),
+        })
+    }
+}
+
+impl Bounded for Csg {
+    /// The box around both operands, or only the left one for a difference
+    fn bounds(&self) -> BoundingBox {
+        match self.operation {
+            Operation::Difference => self.left.bounds(),
+            _ => self.left.bounds().merge(&self.right.bounds()),
+        }
+    }
+}
+
+/// The crossings of two operands, each sorted by distance, which belong to
+/// the solid `operation` makes of them
+pub(crate) fn combine<C: Crossed>(operation: Operation, left: Vec<C>, right: Vec<C>) -> Vec<C> {
+    let mut merged: Vec<(C, bool)> = left
+        .into_iter()
+        .map(|hit| (hit, true))
+        .chain(right.into_iter().map(|hit| (hit, false)))
+        .collect();
+    // A stable sort keeps left hits ahead of right hits at the same
+    // distance, which the coincident surface handling below relies on.
+    merged.sort_by(|a, b| a.0.t().total_cmp(&b.0.t()));
+
+    let (mut in_left, mut in_right) = (false, false);
+    let mut result: Vec<C> = Vec::new();
+    for &(hit, left_hit) in &merged {
+        if operation.allows(left_hit, in_left, in_right) {
+            // Two crossings at the same distance bound a span of zero
+            // thickness, where operands share a surface, so drop both.
+            match result.last() {
+                Some(last) if (hit.t() - last.t()).abs() < EPSILON => {
+                    result.pop();
+                }
+                // What is cut away by the right operand of a difference
+                // leaves its surface facing into it
+                _ if operation == Operation::Difference && !left_hit => result.push(hit.inverted()),
+                _ => result.push(hit),
+            }
+        }
+        if left_hit {
+            in_left = !in_left;
+        } else {
+            in_right = !in_right;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::SceneGraph;
+    use crate::matrix::Matrix;
+    use crate::shape::{Cube, Sphere};
+    use crate::units::{Point3D, Vector3D};
+
+    /// Leaves are spans along the ray: leaf 0 is [1, 4], leaf 1 is [2, 6],
+    /// leaf 2 is [3, 5], leaf 3 is [1, 4] again and leaf 4 is [4, 7]
+    fn spans(leaf: usize) -> Vec<Unit3D> {
+        match leaf {
+            0 => vec![1.0, 4.0],
+            1 => vec![2.0, 6.0],
+            2 => vec![3.0, 5.0],
+            3 => vec![1.0, 4.0],
+            4 => vec![4.0, 7.0],
+            _ => vec![],
+        }
+    }
+
+    fn distances(hits: Vec<Hit>) -> Vec<Unit3D> {
+        hits.into_iter().map(|hit| hit.t).collect()
+    }
+
+    #[test]
+    fn test_rules_for_union() {
+        let op = Operation::Union;
+        assert!(!op.allows(true, true, true));
+        assert!(op.allows(true, true, false));
+        assert!(!op.allows(false, true, false));
+        assert!(op.allows(false, false, true));
+    }
+
+    #[test]
+    fn test_rules_for_intersection() {
+        let op = Operation::Intersection;
+        assert!(op.allows(true, true, true));
+        assert!(!op.allows(true, false, false));
+        assert!(op.allows(false, true, false));
+        assert!(!op.allows(false, false, true));
+    }
+
+    #[test]
+    fn test_rules_for_difference() {
+        let op = Operation::Difference;
+        assert!(!op.allows(true, true, true));
+        assert!(op.allows(true, false, false));
+        assert!(op.allows(false, true, true));
+        assert!(!op.allows(false, false, false));
+    }
+
+    #[test]
+    fn test_combines_overlapping_spans() {
+        let hits = Csg::union(Operand::Leaf(0), Operand::Leaf(1)).intersect(&spans);
+        assert_eq!(vec![Hit::new(1.0, 0), Hit::new(6.0, 1)], hits);
+        let hits = Csg::intersection(Operand::Leaf(0), Operand::Leaf(1)).intersect(&spans);
+        assert_eq!(vec![Hit::new(2.0, 1), Hit::new(4.0, 0)], hits);
+        let hits = Csg::difference(Operand::Leaf(0), Operand::Leaf(1)).intersect(&spans);
+        assert_eq!(vec![Hit::new(1.0, 0), Hit::new(2.0, 1)], hits);
+    }
+
+    #[test]
+    fn test_a_ray_missing_both_operands_misses() {
+        let hits = Csg::union(Operand::Leaf(9), Operand::Leaf(9)).intersect(&spans);
+        assert!(hits.is_empty());
+    }
+
+    #[test]
+    fn test_nested_csg() {
+        // (0 - 1) + 2 leaves [1, 2] and [3, 5]
+        let csg = Csg::union(
+            Csg::difference(Operand::Leaf(0), Operand::Leaf(1)),
+            Operand::Leaf(2),
+        );
+        assert_eq!(vec![1.0, 2.0, 3.0, 5.0], distances(csg.intersect(&spans)));
+        assert!(csg.includes(1));
+        assert!(!csg.includes(4));
+    }
+
+    #[test]
+    fn test_coincident_surfaces() {
+        let same = |op| Csg::new(op, Operand::Leaf(0), Operand::Leaf(3)).intersect(&spans);
+        assert_eq!(vec![1.0, 4.0], distances(same(Operation::Union)));
+        assert_eq!(vec![1.0, 4.0], distances(same(Operation::Intersection)));
+        assert!(same(Operation::Difference).is_empty());
+    }
+
+    #[test]
+    fn test_touching_operands_share_no_surface() {
+        // Leaf 0 ends exactly where leaf 4 begins
+        let union = Csg::union(Operand::Leaf(0), Operand::Leaf(4)).intersect(&spans);
+        assert_eq!(vec![1.0, 7.0], distances(union));
+        let difference = Csg::difference(Operand::Leaf(0), Operand::Leaf(4)).intersect(&spans);
+        assert_eq!(vec![1.0, 4.0], distances(difference));
+        let intersection = Csg::intersection(Operand::Leaf(0), Operand::Leaf(4)).intersect(&spans);
+        assert!(intersection.is_empty());
+    }
+
+    #[test]
+    fn test_shapes_and_groups_as_operands() {
+        // A cube with a ball at x = 1 scooped out of it
+        let mut graph = SceneGraph::new();
+        graph.add_object(
+            graph.root(),
+            Matrix::translation(Point3D::new(1.0, 0.0, 0.0)),
+            Arc::new(Sphere),
+        );
+        let cube: Arc<dyn Shape> = Arc::new(Cube);
+        let ball: Arc<dyn Shape> = Arc::new(graph);
+        let csg = Csg::difference(cube, ball);
+        assert!(!csg.includes(0));
+
+        let along = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0));
+        let crossings = Shape::intersect(&csg, &along);
+        let ts: Vec<Unit3D> = crossings.iter().map(|crossing| crossing.t).collect();
+        assert_eq!(vec![4.0, 5.0], ts);
+        assert_eq!(Vector3D::new(-1.0, 0.0, 0.0), crossings[0].normal);
+        // The scooped surface faces into the hollow
+        assert_eq!(
+            Vector3D::new(1.0, 0.0, 0.0),
+            crossings[1].normal.normalize()
+        );
+        let bounds = csg.bounds();
+        assert_eq!(Point3D::new(1.0, 1.0, 1.0), bounds.max());
+
+        // Leaves are only seen through `Csg::intersect`, shapes only as a
+        // shape
+        let mixed = Csg::union(Operand::Leaf(0), csg);
+        assert_eq!(vec![1.0, 4.0], distances(mixed.intersect(&spans)));
+        assert_eq!(2, Shape::intersect(&mixed, &along).len());
+    }
+}
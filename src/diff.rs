@@ -0,0 +1,395 @@
+//! Image comparison for catching rendering regressions. Canvases are
+//! compared as they would be written to an 8-bit image, so a render can be
+//! matched exactly against a golden PPM file saved from an earlier run.
+
+use crate::canvas::{Canvas, Origin, Pixel, Ppm, PpmError};
+use crate::color::Color;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// The per-pixel differences between two canvases of the same size along
+/// with summary statistics. Errors are measured in color channel units,
+/// from 0 for identical to 1 for black against white.
+#[derive(Debug, Clone)]
+pub struct Diff {
+    width: usize,
+    height: usize,
+    origin: Origin,
+    errors: Vec<f32>,
+    rmse: f32,
+    max_delta: f32,
+    differing: usize,
+}
+
+impl Diff {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The largest difference in any color channel of the pixel at
+    /// `position`.
+    pub fn error(&self, position: Pixel) -> f32 {
+        self.errors[position.y() * self.width + position.x()]
+    }
+
+    /// Root mean square error over every color channel of every pixel
+    pub fn rmse(&self) -> f32 {
+        self.rmse
+    }
+
+    /// Peak signal to noise ratio in decibels, infinite for identical images
+    pub fn psnr(&self) -> f32 {
+        if self.rmse == 0.0 {
+            f32::INFINITY
+        } else {
+            -20.0 * self.rmse.log10()
+        }
+    }
+
+    /// The largest difference in any color channel of any pixel
+    pub fn max_delta(&self) -> f32 {
+        self.max_delta
+    }
+
+    /// The number of pixels which differ at all
+    pub fn differing_pixels(&self) -> usize {
+        self.differing
+    }
+
+    /// A canvas visualizing where the images differ. Identical pixels are
+    /// black and differing pixels run from blue through red and yellow to
+    /// white for the largest error in the image.
+    pub fn heatmap(&self) -> Canvas {
+        let mut heatmap = Canvas::with_origin(self.width, self.height, self.origin);
+        if self.max_delta == 0.0 {
+            return heatmap;
+        }
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let position = Pixel::new(x, y);
+                heatmap[position] = heat(self.error(position) / self.max_delta);
+            }
+        }
+        heatmap
+    }
+
+    /// Test the diff against `tolerance`, returning an error describing
+    /// which limit was exceeded if any.
+    pub fn check(&self, tolerance: &Tolerance) -> Result<(), ToleranceExceeded> {
+        if self.rmse > tolerance.max_rmse || self.max_delta > tolerance.max_delta {
+            return Err(ToleranceExceeded {
+                rmse: self.rmse,
+                max_delta: self.max_delta,
+                differing: self.differing,
+                tolerance: *tolerance,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Map `amount`, between 0 and 1, onto a black-blue-red-yellow-white ramp
+pub(crate) fn heat(amount: f32) -> Color {
+    let stops = [
+        (0.0, 0.0, 0.0),
+        (0.0, 0.0, 1.0),
+        (1.0, 0.0, 0.0),
+        (1.0, 1.0, 0.0),
+        (1.0, 1.0, 1.0),
+    ];
+    let scaled = amount.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let index = (scaled.floor() as usize).min(stops.len() - 2);
+    let t = scaled - index as f32;
+    let (from, to) = (stops[index], stops[index + 1]);
+    Color::rgb(
+        from.0 + (to.0 - from.0) * t,
+        from.1 + (to.1 - from.1) * t,
+        from.2 + (to.2 - from.2) * t,
+    )
+}
+
+/// The largest differences allowed between two images for them to match
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Tolerance {
+    pub max_rmse: f32,
+    pub max_delta: f32,
+}
+
+impl Tolerance {
+    pub fn new(max_rmse: f32, max_delta: f32) -> Self {
+        Self {
+            max_rmse,
+            max_delta,
+        }
+    }
+
+    /// Only identical images match
+    pub fn exact() -> Self {
+        Self::new(0.0, 0.0)
+    }
+}
+
+/// Returned when comparing canvases of different sizes
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SizeMismatch {
+    pub expected: (usize, usize),
+    pub actual: (usize, usize),
+}
+
+impl fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected a {}x{} image but got {}x{}",
+            self.expected.0, self.expected.1, self.actual.0, self.actual.1,
+        )
+    }
+}
+
+impl Error for SizeMismatch {}
+
+/// Returned when a diff is outside its tolerance
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ToleranceExceeded {
+    pub rmse: f32,
+    pub max_delta: f32,
+    pub differing: usize,
+    pub tolerance: Tolerance,
+}
+
+impl fmt::Display for ToleranceExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} pixels differ, rmse {:.5} (max {:.5}), max delta {:.5} (max {:.5})",
+            self.differing,
+            self.rmse,
+            self.tolerance.max_rmse,
+            self.max_delta,
+            self.tolerance.max_delta,
+        )
+    }
+}
+
+impl Error for ToleranceExceeded {}
+
+/// Returned when a canvas doesn't match a golden image file
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(io::Error),
+    Ppm(PpmError),
+    Size(SizeMismatch),
+    Mismatch(ToleranceExceeded),
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::Io(error) => write!(f, "cannot read golden image: {}", error),
+            GoldenError::Ppm(error) => write!(f, "cannot parse golden image: {}", error),
+            GoldenError::Size(error) => write!(f, "{}", error),
+            GoldenError::Mismatch(error) => write!(f, "image differs from golden: {}", error),
+        }
+    }
+}
+
+impl Error for GoldenError {}
+
+/// Compare `canvas` against the golden PPM image at `path`, for use in
+/// regression tests:
+///
+/// ```no_run
+/// # use raytray::canvas::Canvas;
+/// # use raytray::diff::{compare_to_golden, Tolerance};
+/// # let canvas = Canvas::new(10, 10);
+/// compare_to_golden(&canvas, "golden/scene.ppm", &Tolerance::new(0.01, 0.1)).unwrap();
+/// ```
+pub fn compare_to_golden<P: AsRef<Path>>(
+    canvas: &Canvas,
+    path: P,
+    tolerance: &Tolerance,
+) -> Result<Diff, GoldenError> {
+    let data = std::fs::read(path).map_err(GoldenError::Io)?;
+    let golden = Canvas::from(&Ppm::parse(&data).map_err(GoldenError::Ppm)?);
+    let diff = canvas.diff(&golden).map_err(GoldenError::Size)?;
+    diff.check(tolerance).map_err(GoldenError::Mismatch)?;
+    Ok(diff)
+}
+
+impl Canvas {
+    /// Compare the canvas against an `expected` canvas of the same size.
+    /// Colors are quantized to 8 bits per channel before comparing.
+    pub fn diff(&self, expected: &Canvas) -> Result<Diff, SizeMismatch> {
+        if self.width() != expected.width() || self.height() != expected.height() {
+            return Err(SizeMismatch {
+                expected: (expected.width(), expected.height()),
+                actual: (self.width(), self.height()),
+            });
+        }
+
+        let (width, height) = (self.width(), self.height());
+        let mut errors = Vec::with_capacity(width * height);
+        let mut squared_sum = 0.0_f64;
+        let mut max_delta = 0.0_f32;
+        let mut differing = 0;
+
+        for y in 0..height {
+            for x in 0..width {
+                let actual = self[Pixel::new(x, y)].as_rgb_bytes();
+                let wanted = expected.visual_pixel(self, x, y).as_rgb_bytes();
+                let mut error = 0.0_f32;
+                for channel in 0..3 {
+                    let delta = (actual[channel] as f32 - wanted[channel] as f32).abs() / 255.0;
+                    squared_sum += (delta * delta) as f64;
+                    error = error.max(delta);
+                }
+                if error > 0.0 {
+                    differing += 1;
+                }
+                max_delta = max_delta.max(error);
+                errors.push(error);
+            }
+        }
+
+        let samples = (width * height * 3).max(1) as f64;
+        Ok(Diff {
+            width,
+            height,
+            origin: self.origin(),
+            errors,
+            rmse: (squared_sum / samples).sqrt() as f32,
+            max_delta,
+            differing,
+        })
+    }
+
+    /// The pixel of this canvas which appears at the same place in the
+    /// image as pixel `x`, `y` of `other`, allowing for different origins.
+    fn visual_pixel(&self, other: &Canvas, x: usize, y: usize) -> Color {
+        let row = other.visual_row(y as i64);
+        let y = self.visual_row(row) as usize;
+        self[Pixel::new(x, y)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: usize, height: usize) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let shade = (x + y) as f32 / (width + height) as f32;
+                canvas[Pixel::new(x, y)] = Color::rgb(shade, shade, shade);
+            }
+        }
+        canvas
+    }
+
+    #[test]
+    fn test_identical_canvases_have_no_difference() {
+        let canvas = gradient(8, 6);
+        let diff = canvas.diff(&canvas.clone()).unwrap();
+        assert_eq!(0.0, diff.rmse());
+        assert_eq!(0.0, diff.max_delta());
+        assert_eq!(0, diff.differing_pixels());
+        assert_eq!(f32::INFINITY, diff.psnr());
+        assert!(diff.check(&Tolerance::exact()).is_ok());
+    }
+
+    #[test]
+    fn test_comparing_canvases_of_different_sizes_fails() {
+        let result = Canvas::new(4, 4).diff(&Canvas::new(4, 5));
+        assert_eq!(
+            SizeMismatch {
+                expected: (4, 5),
+                actual: (4, 4)
+            },
+            result.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_measures_the_difference_between_canvases() {
+        let expected = Canvas::new(2, 2);
+        let mut actual = Canvas::new(2, 2);
+        actual[Pixel::new(1, 0)] = Color::rgb(1.0, 1.0, 1.0);
+        let diff = actual.diff(&expected).unwrap();
+
+        assert_eq!(1.0, diff.error(Pixel::new(1, 0)));
+        assert_eq!(0.0, diff.error(Pixel::new(0, 0)));
+        assert_eq!(1.0, diff.max_delta());
+        assert_eq!(1, diff.differing_pixels());
+        assert!((0.5 - diff.rmse()).abs() < 0.0001);
+        assert!((6.0206 - diff.psnr()).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_canvases_with_different_origins_compare_as_viewed() {
+        let mut bottom_left = Canvas::new(1, 2);
+        bottom_left[Pixel::new(0, 1)] = Color::rgb(1.0, 1.0, 1.0);
+        let mut top_left = Canvas::with_origin(1, 2, Origin::TopLeft);
+        top_left[Pixel::new(0, 0)] = Color::rgb(1.0, 1.0, 1.0);
+        assert_eq!(0.0, bottom_left.diff(&top_left).unwrap().max_delta());
+    }
+
+    #[test]
+    fn test_tolerance_allows_small_differences() {
+        let expected = gradient(4, 4);
+        let mut actual = expected.clone();
+        actual[Pixel::new(2, 2)] = Color::rgb(0.52, 0.5, 0.5);
+        let diff = actual.diff(&expected).unwrap();
+
+        assert!(diff.check(&Tolerance::new(0.01, 0.1)).is_ok());
+        let exceeded = diff.check(&Tolerance::exact()).unwrap_err();
+        assert_eq!(1, exceeded.differing);
+    }
+
+    #[test]
+    fn test_heatmap_highlights_differences() {
+        let expected = Canvas::new(3, 1);
+        let mut actual = Canvas::new(3, 1);
+        // Exactly half the largest error once quantized to bytes
+        actual[Pixel::new(0, 0)] = Color::rgb(254.0 / 255.0, 0.0, 0.0);
+        actual[Pixel::new(1, 0)] = Color::rgb(127.0 / 255.0, 0.0, 0.0);
+        let heatmap = actual.diff(&expected).unwrap().heatmap();
+
+        assert_eq!([255, 255, 255], heatmap[Pixel::new(0, 0)].as_rgb_bytes());
+        assert_eq!([255, 0, 0], heatmap[Pixel::new(1, 0)].as_rgb_bytes());
+        assert_eq!([0, 0, 0], heatmap[Pixel::new(2, 0)].as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_compares_a_canvas_to_a_golden_ppm_file() {
+        let canvas = gradient(5, 3);
+        let path = std::env::temp_dir().join(format!("raytray-golden-{}.ppm", std::process::id()));
+        std::fs::write(&path, format!("{}", Ppm::from(&canvas))).unwrap();
+
+        let matched = compare_to_golden(&canvas, &path, &Tolerance::exact());
+        let mut changed = canvas.clone();
+        changed[Pixel::new(0, 0)] = Color::rgb(1.0, 0.0, 0.0);
+        let mismatched = compare_to_golden(&changed, &path, &Tolerance::exact());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matched.is_ok());
+        assert!(matches!(mismatched, Err(GoldenError::Mismatch(_))));
+    }
+
+    #[test]
+    fn test_missing_golden_files_are_reported() {
+        let result = compare_to_golden(
+            &Canvas::new(1, 1),
+            "does/not/exist.ppm",
+            &Tolerance::exact(),
+        );
+        assert!(matches!(result, Err(GoldenError::Io(_))));
+    }
+}
@@ -0,0 +1,289 @@
+//! Writing a `Canvas` out as an image file. PPM is written by `canvas::Ppm`,
+//! PNG and Radiance HDR are encoded here without any outside libraries. The
+//! PNG data isn't compressed, which keeps the encoder tiny at the cost of
+//! larger files. A rendered `Film` can be written as HDR directly, keeping
+//! light brighter than a canvas can hold.
+
+use crate::canvas::{Canvas, Pixel, Ppm};
+use crate::color::Spectrum;
+use crate::sampling::Film;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// An image file format a canvas can be saved as
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Format {
+    /// Plain text (P3) PPM
+    PpmPlain,
+    /// Raw binary (P6) PPM
+    PpmRaw,
+    /// 8 bit RGB PNG
+    Png,
+    /// Radiance RGBE, keeping the full range of each channel
+    Hdr,
+}
+
+impl Format {
+    /// The format called `name`, as given on a command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "p3" | "ppm" => Some(Format::PpmPlain),
+            "p6" => Some(Format::PpmRaw),
+            "png" => Some(Format::Png),
+            "hdr" => Some(Format::Hdr),
+            _ => None,
+        }
+    }
+
+    /// The format matching the extension of `path`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        Self::from_name(extension)
+    }
+
+    /// The usual file extension for the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::PpmPlain | Format::PpmRaw => "ppm",
+            Format::Png => "png",
+            Format::Hdr => "hdr",
+        }
+    }
+}
+
+/// The bytes of `canvas` as an image file in `format`
+pub fn encode(canvas: &Canvas, format: Format) -> Vec<u8> {
+    match format {
+        Format::PpmPlain => Ppm::from(canvas).to_string().into_bytes(),
+        Format::PpmRaw => Ppm::from(canvas).to_raw(),
+        Format::Png => encode_png(canvas),
+        Format::Hdr => encode_hdr(canvas),
+    }
+}
+
+/// Write `canvas` to the file at `path` in `format`
+pub fn save<P: AsRef<Path>>(canvas: &Canvas, path: P, format: Format) -> io::Result<()> {
+    File::create(path)?.write_all(&encode(canvas, format))
+}
+
+/// The bytes of `film` as a Radiance HDR file, top row first as cameras
+/// render them
+pub fn encode_film(film: &Film) -> Vec<u8> {
+    hdr(film.width(), film.height(), |x, row| {
+        film.spectrum(Pixel::new(x, row))
+    })
+}
+
+/// Write `film` to the file at `path` as Radiance HDR
+pub fn save_film<P: AsRef<Path>>(film: &Film, path: P) -> io::Result<()> {
+    File::create(path)?.write_all(&encode_film(film))
+}
+
+fn encode_png(canvas: &Canvas) -> Vec<u8> {
+    let (width, height) = (canvas.width(), canvas.height());
+    let ppm = Ppm::from(canvas);
+
+    // Each row of samples is preceded by its filter type, 0 for none
+    let mut scanlines = Vec::with_capacity(height * (width * 3 + 1));
+    for row in ppm.body().chunks(width * 3).take(height) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per sample, RGB, deflate, adaptive filtering, not interlaced
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wrap `data` in a zlib stream of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 65535;
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+fn encode_hdr(canvas: &Canvas) -> Vec<u8> {
+    hdr(canvas.width(), canvas.height(), |x, row| {
+        let y = canvas.visual_row(row as i64) as usize;
+        Spectrum::from(canvas[Pixel::new(x, y)])
+    })
+}
+
+/// A Radiance HDR image of the light `at` each column and row, counting
+/// rows from the top
+fn hdr<F: Fn(usize, usize) -> Spectrum>(width: usize, height: usize, at: F) -> Vec<u8> {
+    let mut hdr = format!(
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )
+    .into_bytes();
+    for row in 0..height {
+        for x in 0..width {
+            let light = at(x, row);
+            hdr.extend_from_slice(&rgbe(light.r(), light.g(), light.b()));
+        }
+    }
+    hdr
+}
+
+/// Pack a color into three mantissas sharing the exponent of the brightest
+/// channel
+fn rgbe(r: f32, g: f32, b: f32) -> [u8; 4] {
+    let brightest = r.max(g).max(b);
+    if brightest < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    let exponent = brightest.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f32.powi(exponent);
+    let mantissa = |c: f32| (c.max(0.0) * scale).min(255.0) as u8;
+    [
+        mantissa(r),
+        mantissa(g),
+        mantissa(b),
+        (exponent + 128) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Origin;
+    use crate::color::Color;
+    use crate::sampling::PixelFilter;
+
+    /// The decompressed contents of a zlib stream of stored blocks
+    fn unstore(zlib: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut at = 2;
+        loop {
+            let last = zlib[at] & 1 == 1;
+            let len = u16::from_le_bytes([zlib[at + 1], zlib[at + 2]]) as usize;
+            data.extend_from_slice(&zlib[at + 5..at + 5 + len]);
+            at += 5 + len;
+            if last {
+                break;
+            }
+        }
+        assert_eq!(adler32(&data).to_be_bytes(), zlib[at..at + 4]);
+        data
+    }
+
+    #[test]
+    fn test_formats_from_names_and_paths() {
+        assert_eq!(Some(Format::PpmRaw), Format::from_name("P6"));
+        assert_eq!(Some(Format::Png), Format::from_path("out/render.png"));
+        assert_eq!(Some(Format::PpmPlain), Format::from_path("render.ppm"));
+        assert_eq!(None, Format::from_path("render"));
+        assert_eq!(None, Format::from_name("gif"));
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+        assert_eq!(0x11e6_0398, adler32(b"Wikipedia"));
+    }
+
+    #[test]
+    fn test_encodes_a_png() {
+        let mut canvas = Canvas::with_origin(2, 1, Origin::TopLeft);
+        canvas[Pixel::new(1, 0)] = Color::rgb(1.0, 0.0, 0.0);
+        let png = encode(&canvas, Format::Png);
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &png[..8]);
+        assert_eq!(b"IHDR", &png[12..16]);
+        assert_eq!([0, 0, 0, 2, 0, 0, 0, 1, 8, 2], png[16..26]);
+        // IEND is always the same twelve bytes
+        assert_eq!(
+            [0, 0, 0, 0, 73, 69, 78, 68, 0xae, 0x42, 0x60, 0x82],
+            png[png.len() - 12..]
+        );
+
+        let idat_len = u32::from_be_bytes([png[33], png[34], png[35], png[36]]) as usize;
+        assert_eq!(b"IDAT", &png[37..41]);
+        let scanlines = unstore(&png[41..41 + idat_len]);
+        assert_eq!(vec![0, 0, 0, 0, 255, 0, 0], scanlines);
+    }
+
+    #[test]
+    fn test_large_pngs_are_split_into_blocks() {
+        let data = vec![7; 150_000];
+        assert_eq!(data, unstore(&zlib_stored(&data)));
+    }
+
+    #[test]
+    fn test_encodes_an_hdr_top_row_first() {
+        let mut canvas = Canvas::new(1, 2);
+        canvas[Pixel::new(0, 1)] = Color::rgb(1.0, 0.5, 0.0);
+        let hdr = encode(&canvas, Format::Hdr);
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 1\n";
+        assert!(hdr.starts_with(header));
+        assert_eq!([128, 64, 0, 129, 0, 0, 0, 0], hdr[header.len()..]);
+    }
+
+    #[test]
+    fn test_encodes_films_without_clamping() {
+        let mut film = Film::new(1, 2, PixelFilter::Box);
+        film.add_spectrum(0.5, 0.5, Spectrum::new(4.0, 1.0, 0.0));
+        let hdr = encode_film(&film);
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 1\n";
+        assert!(hdr.starts_with(header));
+        assert_eq!([128, 32, 0, 131, 0, 0, 0, 0], hdr[header.len()..]);
+    }
+
+    #[test]
+    fn test_rgbe_shares_the_brightest_exponent() {
+        assert_eq!([128, 128, 128, 128], rgbe(0.5, 0.5, 0.5));
+        assert_eq!([128, 32, 0, 131], rgbe(4.0, 1.0, 0.0));
+    }
+}
@@ -0,0 +1,620 @@
+//! Image resampling and filtering for `Canvas`: resizing with a choice of
+//! reconstruction filters, convolution with arbitrary kernels and bloom.
+//! Every operation returns a new canvas with the same origin, edges are
+//! handled by repeating the outermost pixels. Rendered light brighter than
+//! white can be bloomed on its `Film`, before it is tone mapped.
+
+use crate::canvas::{Canvas, Pixel};
+use crate::color::{Color, Spectrum};
+use crate::sampling::{Film, PixelFilter};
+use std::f32::consts::PI;
+
+/// The reconstruction filter used when resizing a canvas
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Filter {
+    /// Take the nearest source pixel, blocky but exact
+    Nearest,
+    /// Linear interpolation between the two nearest pixels on each axis
+    Bilinear,
+    /// Catmull-Rom cubic interpolation over the four nearest pixels
+    Bicubic,
+    /// Windowed sinc over the six nearest pixels, the sharpest of the lot
+    Lanczos3,
+}
+
+impl Filter {
+    /// How far from its center, in source pixels, the filter reaches
+    fn support(&self) -> f32 {
+        match self {
+            Filter::Nearest => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Bicubic => 2.0,
+            Filter::Lanczos3 => 3.0,
+        }
+    }
+
+    /// The weight of a sample `x` pixels from the filter center
+    fn weight(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Filter::Nearest => {
+                if x < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Bilinear => (1.0 - x).max(0.0),
+            Filter::Bicubic => {
+                // Catmull-Rom, the Keys cubic with a = -0.5
+                let a = -0.5;
+                if x < 1.0 {
+                    (a + 2.0) * x.powi(3) - (a + 3.0) * x.powi(2) + 1.0
+                } else if x < 2.0 {
+                    a * x.powi(3) - 5.0 * a * x.powi(2) + 8.0 * a * x - 4.0 * a
+                } else {
+                    0.0
+                }
+            }
+            Filter::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// A convolution kernel of `width` x `height` weights, laid out in rows
+/// from the top of the image down. Both dimensions must be odd so the
+/// kernel has a center pixel.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Kernel {
+    width: usize,
+    height: usize,
+    weights: Vec<f32>,
+}
+
+impl Kernel {
+    pub fn new(width: usize, height: usize, weights: Vec<f32>) -> Self {
+        if width % 2 != 1 || height % 2 != 1 {
+            panic!("kernel dimensions must be odd! {}x{}", width, height);
+        }
+        if weights.len() != width * height {
+            panic!(
+                "kernel has {} weights but needs {}x{}!",
+                weights.len(),
+                width,
+                height
+            );
+        }
+
+        Self {
+            width,
+            height,
+            weights,
+        }
+    }
+
+    /// A kernel averaging the square of pixels `radius` pixels around each
+    pub fn box_blur(radius: usize) -> Self {
+        let size = 2 * radius + 1;
+        let weight = 1.0 / (size * size) as f32;
+        Self::new(size, size, vec![weight; size * size])
+    }
+
+    /// A normalized gaussian blur kernel with standard deviation `sigma`,
+    /// wide enough to cover three deviations either side of the center. A
+    /// `sigma` of 0 doesn't blur at all.
+    pub fn gaussian(sigma: f32) -> Self {
+        let row = gaussian_weights(sigma);
+        let size = row.len();
+        let mut weights = Vec::with_capacity(size * size);
+        for y in row.iter() {
+            for x in row.iter() {
+                weights.push(x * y);
+            }
+        }
+        Self::new(size, size, weights)
+    }
+
+    /// A kernel which exaggerates the difference between each pixel and
+    /// its neighbours.
+    pub fn sharpen() -> Self {
+        Self::new(3, 3, vec![0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0])
+    }
+
+    /// A Laplacian kernel which is zero over flat areas and bright along
+    /// edges.
+    pub fn edge_detect() -> Self {
+        Self::new(
+            3,
+            3,
+            vec![-1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0],
+        )
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The weight at column `col` and row `row` of the kernel
+    pub fn weight(&self, col: usize, row: usize) -> f32 {
+        self.weights[row * self.width + col]
+    }
+}
+
+/// One dimensional normalized gaussian weights out to three deviations,
+/// just the one weight when there is no deviation
+fn gaussian_weights(sigma: f32) -> Vec<f32> {
+    if !(sigma >= 0.0 && sigma.is_finite()) {
+        panic!(
+            "a gaussian's standard deviation can't be negative or infinite! {}",
+            sigma
+        );
+    }
+    if sigma == 0.0 {
+        return vec![1.0];
+    }
+    let radius = (3.0 * sigma).ceil().max(0.0) as i64;
+    let mut weights: Vec<f32> = (-radius..=radius)
+        .map(|x| (-((x * x) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    for weight in weights.iter_mut() {
+        *weight /= total;
+    }
+    weights
+}
+
+impl Canvas {
+    /// Resample the canvas to `width` x `height` pixels using `filter`.
+    /// When shrinking the filter is widened to cover every source pixel so
+    /// fine detail is averaged rather than aliased.
+    pub fn resize(&self, width: usize, height: usize, filter: Filter) -> Canvas {
+        let source = Channels::from(self);
+        let horizontal = resample_axis(&source, width, filter, Axis::Horizontal);
+        let resized = resample_axis(&horizontal, height, filter, Axis::Vertical);
+        resized.into_canvas(self)
+    }
+
+    /// Apply `kernel` centered on every pixel of the canvas. The kernel is
+    /// laid out as the image is viewed, top row first, whatever the origin.
+    pub fn convolve(&self, kernel: &Kernel) -> Canvas {
+        Channels::from(self)
+            .convolve(kernel, self.down())
+            .into_canvas(self)
+    }
+
+    /// Make highlights glow by blurring every pixel brighter than
+    /// `threshold`, with a gaussian of standard deviation `sigma`, and adding
+    /// the glow back onto the image scaled by `strength`. Brightness is
+    /// measured as relative luminance, between 0 and 1, so nothing can be
+    /// brighter than white; `Film::bloom` sees how bright rendered light is.
+    pub fn bloom(&self, threshold: f32, sigma: f32, strength: f32) -> Canvas {
+        let mut highlights = Channels::new(self.width(), self.height());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let color = self[Pixel::new(x, y)];
+                if luminance(color) > threshold {
+                    highlights.set(x, y, [color.r(), color.g(), color.b(), color.a()]);
+                }
+            }
+        }
+        let glow = highlights.blur(sigma);
+
+        let mut bloomed = self.clone();
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let pixel = Pixel::new(x, y);
+                let original = self[pixel];
+                let [r, g, b, _] = glow.get(x, y);
+                let lit = original + Color::rgb(r, g, b) * strength;
+                bloomed[pixel] = lit.with_alpha(original.a());
+            }
+        }
+        bloomed
+    }
+}
+
+impl Film {
+    /// Make highlights glow like `Canvas::bloom`, but with the unclamped
+    /// light of the film, so a light ten times brighter than white glows ten
+    /// times as much. The result has one sample per pixel, ready to be tone
+    /// mapped.
+    pub fn bloom(&self, threshold: f32, sigma: f32, strength: f32) -> Film {
+        let mut highlights = Channels::new(self.width(), self.height());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let light = self.spectrum(Pixel::new(x, y));
+                if light.luminance() > threshold {
+                    highlights.set(x, y, [light.r(), light.g(), light.b(), 0.0]);
+                }
+            }
+        }
+        let glow = highlights.blur(sigma);
+
+        let mut bloomed = Film::new(self.width(), self.height(), PixelFilter::Box);
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let pixel = Pixel::new(x, y);
+                let [r, g, b, _] = glow.get(x, y);
+                let lit = self.spectrum(pixel) + Spectrum::new(r, g, b) * strength;
+                bloomed.set_spectrum(pixel, lit);
+            }
+        }
+        bloomed
+    }
+}
+
+/// Relative luminance of a color using the Rec. 709 primaries
+pub(crate) fn luminance(color: Color) -> f32 {
+    Spectrum::from(color).luminance()
+}
+
+#[derive(Clone, Copy)]
+enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// A plain buffer of RGBA channels used while filtering, so intermediate
+/// results aren't clamped the way `Color` is.
+struct Channels {
+    width: usize,
+    height: usize,
+    values: Vec<[f32; 4]>,
+}
+
+impl Channels {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            values: vec![[0.0; 4]; width * height],
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> [f32; 4] {
+        self.values[y * self.width + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, value: [f32; 4]) {
+        self.values[y * self.width + x] = value;
+    }
+
+    /// The channels at `x`, `y` with coordinates outside the buffer clamped
+    /// to the nearest edge pixel.
+    fn clamped(&self, x: i64, y: i64) -> [f32; 4] {
+        let x = x.max(0).min(self.width as i64 - 1) as usize;
+        let y = y.max(0).min(self.height as i64 - 1) as usize;
+        self.get(x, y)
+    }
+
+    /// Apply `kernel` centered on every pixel, reading its rows `down` the
+    /// buffer, 1 for top to bottom or -1 for bottom to top
+    fn convolve(&self, kernel: &Kernel, down: i64) -> Channels {
+        let mut output = Channels::new(self.width, self.height);
+        let (center_x, center_y) = ((kernel.width / 2) as i64, (kernel.height / 2) as i64);
+
+        for y in 0..self.height as i64 {
+            for x in 0..self.width as i64 {
+                let mut sum = [0.0; 4];
+                for row in 0..kernel.height {
+                    let sy = y + down * (row as i64 - center_y);
+                    for col in 0..kernel.width {
+                        let sx = x + col as i64 - center_x;
+                        let weight = kernel.weight(col, row);
+                        let sample = self.clamped(sx, sy);
+                        for channel in 0..4 {
+                            sum[channel] += sample[channel] * weight;
+                        }
+                    }
+                }
+                output.set(x as usize, y as usize, sum);
+            }
+        }
+        output
+    }
+
+    /// A gaussian blur of standard deviation `sigma`, one axis at a time
+    fn blur(&self, sigma: f32) -> Channels {
+        let weights = gaussian_weights(sigma);
+        let size = weights.len();
+        self.convolve(&Kernel::new(size, 1, weights.clone()), 1)
+            .convolve(&Kernel::new(1, size, weights), 1)
+    }
+
+    fn into_canvas(self, like: &Canvas) -> Canvas {
+        let mut canvas = Canvas::with_origin(self.width, self.height, like.origin());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let [r, g, b, a] = self.get(x, y);
+                canvas[Pixel::new(x, y)] = Color::rgba(r, g, b, a);
+            }
+        }
+        canvas
+    }
+}
+
+impl From<&Canvas> for Channels {
+    fn from(canvas: &Canvas) -> Self {
+        let mut channels = Channels::new(canvas.width(), canvas.height());
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                let color = canvas[Pixel::new(x, y)];
+                channels.set(x, y, [color.r(), color.g(), color.b(), color.a()]);
+            }
+        }
+        channels
+    }
+}
+
+/// Resample `source` along one axis to `size` pixels
+fn resample_axis(source: &Channels, size: usize, filter: Filter, axis: Axis) -> Channels {
+    let (source_size, width, height) = match axis {
+        Axis::Horizontal => (source.width, size, source.height),
+        Axis::Vertical => (source.height, source.width, size),
+    };
+    let mut output = Channels::new(width, height);
+    if source_size == 0 || size == 0 {
+        return output;
+    }
+
+    let scale = source_size as f32 / size as f32;
+    let filter_scale = scale.max(1.0);
+    let support = filter.support() * filter_scale;
+
+    for out in 0..size {
+        // Work out the source pixels and weights contributing to this
+        // output pixel once, then apply them along every line.
+        let center = (out as f32 + 0.5) * scale;
+        let first = (center - support).floor() as i64;
+        let last = (center + support).ceil() as i64;
+        let mut taps: Vec<(i64, f32)> = (first..=last)
+            .map(|i| (i, filter.weight((i as f32 + 0.5 - center) / filter_scale)))
+            .filter(|&(_, weight)| weight != 0.0)
+            .collect();
+        let total: f32 = taps.iter().map(|&(_, weight)| weight).sum();
+        if total == 0.0 {
+            // Only possible for nearest neighbour exactly between pixels
+            taps = vec![(center.floor() as i64, 1.0)];
+        } else {
+            for tap in taps.iter_mut() {
+                tap.1 /= total;
+            }
+        }
+
+        let lines = match axis {
+            Axis::Horizontal => height,
+            Axis::Vertical => width,
+        };
+        for line in 0..lines {
+            let mut sum = [0.0; 4];
+            for &(index, weight) in taps.iter() {
+                let sample = match axis {
+                    Axis::Horizontal => source.clamped(index, line as i64),
+                    Axis::Vertical => source.clamped(line as i64, index),
+                };
+                for channel in 0..4 {
+                    sum[channel] += sample[channel] * weight;
+                }
+            }
+            match axis {
+                Axis::Horizontal => output.set(out, line, sum),
+                Axis::Vertical => output.set(line, out, sum),
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Origin;
+
+    const FILTERS: [Filter; 4] = [
+        Filter::Nearest,
+        Filter::Bilinear,
+        Filter::Bicubic,
+        Filter::Lanczos3,
+    ];
+
+    fn filled(width: usize, height: usize, color: Color) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                canvas[Pixel::new(x, y)] = color;
+            }
+        }
+        canvas
+    }
+
+    fn bytes(canvas: &Canvas, x: usize, y: usize) -> [u8; 3] {
+        canvas[Pixel::new(x, y)].as_rgb_bytes()
+    }
+
+    #[test]
+    fn test_resizing_a_flat_canvas_keeps_it_flat() {
+        let canvas = filled(7, 5, Color::rgb(0.2, 0.4, 0.6));
+        for filter in FILTERS.iter() {
+            let resized = canvas.resize(13, 3, *filter);
+            assert_eq!(13, resized.width());
+            assert_eq!(3, resized.height());
+            for y in 0..3 {
+                for x in 0..13 {
+                    assert_eq!([51, 102, 153], bytes(&resized, x, y), "{:?}", filter);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_resizing_to_the_same_size_changes_nothing() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas[Pixel::new(1, 2)] = Color::rgb(1.0, 1.0, 1.0);
+        for filter in FILTERS.iter() {
+            let resized = canvas.resize(4, 4, *filter);
+            for y in 0..4 {
+                for x in 0..4 {
+                    assert_eq!(bytes(&canvas, x, y), bytes(&resized, x, y), "{:?}", filter);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_nearest_neighbour_upscaling_repeats_pixels() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas[Pixel::new(1, 0)] = Color::rgb(1.0, 1.0, 1.0);
+        let resized = canvas.resize(4, 2, Filter::Nearest);
+        assert_eq!([0, 0, 0], bytes(&resized, 1, 1));
+        assert_eq!([255, 255, 255], bytes(&resized, 2, 0));
+        assert_eq!([255, 255, 255], bytes(&resized, 3, 1));
+    }
+
+    #[test]
+    fn test_bilinear_downscaling_averages_pixels() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas[Pixel::new(0, 0)] = Color::rgb(1.0, 1.0, 1.0);
+        canvas[Pixel::new(1, 1)] = Color::rgb(1.0, 1.0, 1.0);
+        let resized = canvas.resize(1, 1, Filter::Bilinear);
+        assert_eq!([128, 128, 128], bytes(&resized, 0, 0));
+    }
+
+    #[test]
+    fn test_resizing_keeps_the_canvas_origin() {
+        let canvas = Canvas::with_origin(4, 4, Origin::Center);
+        assert_eq!(
+            Origin::Center,
+            canvas.resize(2, 2, Filter::Bicubic).origin()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_kernels_must_have_odd_dimensions() {
+        Kernel::new(2, 3, vec![0.0; 6]);
+    }
+
+    #[test]
+    fn test_gaussians_without_deviation_change_nothing() {
+        let kernel = Kernel::gaussian(0.0);
+        assert_eq!((1, 1), (kernel.width(), kernel.height()));
+        assert_eq!(1.0, kernel.weight(0, 0));
+
+        // Highlights are added back onto themselves, without spreading
+        let mut canvas = filled(3, 3, Color::rgb(0.1, 0.1, 0.1));
+        canvas[Pixel::new(1, 1)] = Color::rgb(0.9, 0.9, 0.9);
+        let bloomed = canvas.bloom(0.8, 0.0, 0.1);
+        assert!(bytes(&bloomed, 1, 1)[0] > bytes(&canvas, 1, 1)[0]);
+        assert_eq!(bytes(&canvas, 0, 1), bytes(&bloomed, 0, 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_gaussians_cant_have_negative_deviations() {
+        Kernel::gaussian(-1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bloom_cant_have_a_negative_deviation() {
+        Film::new(3, 3, PixelFilter::Box).bloom(1.0, -0.5, 1.0);
+    }
+
+    #[test]
+    fn test_gaussian_kernels_are_normalized() {
+        let kernel = Kernel::gaussian(1.0);
+        assert_eq!(7, kernel.width());
+        let total: f32 = kernel.weights.iter().sum();
+        assert!((1.0 - total).abs() < 0.0001);
+        assert!(kernel.weight(3, 3) > kernel.weight(2, 3));
+    }
+
+    #[test]
+    fn test_convolving_with_an_identity_kernel_changes_nothing() {
+        let mut canvas = Canvas::new(3, 3);
+        canvas[Pixel::new(1, 0)] = Color::rgb(0.5, 0.25, 1.0);
+        let identity = Kernel::new(3, 3, vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+        let convolved = canvas.convolve(&identity);
+        assert_eq!([128, 64, 255], bytes(&convolved, 1, 0));
+        assert_eq!([0, 0, 0], bytes(&convolved, 1, 1));
+    }
+
+    #[test]
+    fn test_box_blur_spreads_a_pixel_over_its_neighbours() {
+        let mut canvas = Canvas::new(5, 5);
+        canvas[Pixel::new(2, 2)] = Color::rgb(0.9, 0.9, 0.9);
+        let blurred = canvas.convolve(&Kernel::box_blur(1));
+        assert_eq!([26, 26, 26], bytes(&blurred, 1, 1));
+        assert_eq!([26, 26, 26], bytes(&blurred, 2, 2));
+        assert_eq!([0, 0, 0], bytes(&blurred, 0, 0));
+    }
+
+    #[test]
+    fn test_kernel_rows_run_down_the_image() {
+        // A kernel that copies the pixel visually above, on a canvas whose
+        // rows are stored bottom up.
+        let mut canvas = Canvas::new(1, 3);
+        canvas[Pixel::new(0, 2)] = Color::rgb(1.0, 1.0, 1.0);
+        let from_above = Kernel::new(1, 3, vec![1.0, 0.0, 0.0]);
+        let shifted = canvas.convolve(&from_above);
+        assert_eq!([255, 255, 255], bytes(&shifted, 0, 1));
+        assert_eq!([0, 0, 0], bytes(&shifted, 0, 0));
+    }
+
+    #[test]
+    fn test_edge_detection_is_dark_over_flat_areas() {
+        let canvas = filled(4, 4, Color::rgb(0.5, 0.5, 0.5));
+        let edges = canvas.convolve(&Kernel::edge_detect());
+        assert_eq!([0, 0, 0], bytes(&edges, 1, 1));
+    }
+
+    #[test]
+    fn test_bloom_makes_highlights_glow() {
+        let mut canvas = filled(9, 9, Color::rgb(0.1, 0.1, 0.1));
+        canvas[Pixel::new(4, 4)] = Color::rgb(1.0, 1.0, 1.0);
+        let bloomed = canvas.bloom(0.8, 1.0, 1.0);
+        assert!(bytes(&bloomed, 5, 4)[0] > bytes(&canvas, 5, 4)[0]);
+        assert_eq!(bytes(&canvas, 0, 0), bytes(&bloomed, 0, 0));
+    }
+
+    #[test]
+    fn test_bloom_leaves_dim_images_alone() {
+        let canvas = filled(5, 5, Color::rgb(0.3, 0.3, 0.3));
+        let bloomed = canvas.bloom(0.8, 1.0, 1.0);
+        assert_eq!(bytes(&canvas, 2, 2), bytes(&bloomed, 2, 2));
+    }
+
+    #[test]
+    fn test_films_bloom_with_their_full_brightness() {
+        let glow_beside = |brightness| {
+            let mut film = Film::new(9, 9, PixelFilter::Box);
+            film.add_spectrum(4.5, 4.5, Spectrum::gray(brightness));
+            let bloomed = film.bloom(1.5, 1.0, 1.0);
+            assert_eq!(Spectrum::black(), bloomed.spectrum(Pixel::new(0, 0)));
+            bloomed.spectrum(Pixel::new(5, 4)).r()
+        };
+        // Too dim to glow, though it is brighter than white
+        assert_eq!(0.0, glow_beside(1.2));
+        let (bright, brighter) = (glow_beside(2.0), glow_beside(20.0));
+        assert!(bright > 0.0);
+        assert!((brighter / bright - 10.0).abs() < 1e-3);
+    }
+}
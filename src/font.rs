@@ -0,0 +1,370 @@
+//! A built-in 5x7 monospace bitmap font covering printable ASCII. Each glyph
+//! is seven rows from top to bottom, the low five bits of each row are the
+//! pixels from left to right with the most significant bit leftmost.
+
+/// Width of a glyph in pixels
+pub const GLYPH_WIDTH: usize = 5;
+
+/// Height of a glyph in pixels
+pub const GLYPH_HEIGHT: usize = 7;
+
+/// Horizontal distance from the start of one character to the next
+pub const ADVANCE: usize = GLYPH_WIDTH + 1;
+
+/// Vertical distance from the top of one line of text to the next
+pub const LINE_HEIGHT: usize = GLYPH_HEIGHT + 2;
+
+const FIRST: char = ' ';
+
+const GLYPHS: [[u8; GLYPH_HEIGHT]; 95] = [
+    [
+        0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000,
+    ], // ' '
+    [
+        0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100,
+    ], // '!'
+    [
+        0b01010, 0b01010, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000,
+    ], // '"'
+    [
+        0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010,
+    ], // '#'
+    [
+        0b00100, 0b01111, 0b10100, 0b01110, 0b00101, 0b11110, 0b00100,
+    ], // '$'
+    [
+        0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011,
+    ], // '%'
+    [
+        0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101,
+    ], // '&'
+    [
+        0b00100, 0b00100, 0b00100, 0b00000, 0b00000, 0b00000, 0b00000,
+    ], // '\''
+    [
+        0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010,
+    ], // '('
+    [
+        0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000,
+    ], // ')'
+    [
+        0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000,
+    ], // '*'
+    [
+        0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000,
+    ], // '+'
+    [
+        0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000,
+    ], // ','
+    [
+        0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000,
+    ], // '-'
+    [
+        0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100,
+    ], // '.'
+    [
+        0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000,
+    ], // '/'
+    [
+        0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110,
+    ], // '0'
+    [
+        0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+    ], // '1'
+    [
+        0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111,
+    ], // '2'
+    [
+        0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110,
+    ], // '3'
+    [
+        0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010,
+    ], // '4'
+    [
+        0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110,
+    ], // '5'
+    [
+        0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110,
+    ], // '6'
+    [
+        0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000,
+    ], // '7'
+    [
+        0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110,
+    ], // '8'
+    [
+        0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100,
+    ], // '9'
+    [
+        0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000,
+    ], // ':'
+    [
+        0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b00100, 0b01000,
+    ], // ';'
+    [
+        0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010,
+    ], // '<'
+    [
+        0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000,
+    ], // '='
+    [
+        0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000,
+    ], // '>'
+    [
+        0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100,
+    ], // '?'
+    [
+        0b01110, 0b10001, 0b00001, 0b01101, 0b10101, 0b10101, 0b01110,
+    ], // '@'
+    [
+        0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
+    ], // 'A'
+    [
+        0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110,
+    ], // 'B'
+    [
+        0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110,
+    ], // 'C'
+    [
+        0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100,
+    ], // 'D'
+    [
+        0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111,
+    ], // 'E'
+    [
+        0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000,
+    ], // 'F'
+    [
+        0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111,
+    ], // 'G'
+    [
+        0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
+    ], // 'H'
+    [
+        0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+    ], // 'I'
+    [
+        0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100,
+    ], // 'J'
+    [
+        0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001,
+    ], // 'K'
+    [
+        0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111,
+    ], // 'L'
+    [
+        0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001,
+    ], // 'M'
+    [
+        0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001,
+    ], // 'N'
+    [
+        0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
+    ], // 'O'
+    [
+        0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000,
+    ], // 'P'
+    [
+        0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101,
+    ], // 'Q'
+    [
+        0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001,
+    ], // 'R'
+    [
+        0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110,
+    ], // 'S'
+    [
+        0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100,
+    ], // 'T'
+    [
+        0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
+    ], // 'U'
+    [
+        0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100,
+    ], // 'V'
+    [
+        0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010,
+    ], // 'W'
+    [
+        0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001,
+    ], // 'X'
+    [
+        0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100,
+    ], // 'Y'
+    [
+        0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111,
+    ], // 'Z'
+    [
+        0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110,
+    ], // '['
+    [
+        0b00000, 0b10000, 0b01000, 0b00100, 0b00010, 0b00001, 0b00000,
+    ], // '\\'
+    [
+        0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110,
+    ], // ']'
+    [
+        0b00100, 0b01010, 0b10001, 0b00000, 0b00000, 0b00000, 0b00000,
+    ], // '^'
+    [
+        0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111,
+    ], // '_'
+    [
+        0b01000, 0b00100, 0b00010, 0b00000, 0b00000, 0b00000, 0b00000,
+    ], // '`'
+    [
+        0b00000, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111,
+    ], // 'a'
+    [
+        0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b11110,
+    ], // 'b'
+    [
+        0b00000, 0b00000, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110,
+    ], // 'c'
+    [
+        0b00001, 0b00001, 0b01101, 0b10011, 0b10001, 0b10001, 0b01111,
+    ], // 'd'
+    [
+        0b00000, 0b00000, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110,
+    ], // 'e'
+    [
+        0b00110, 0b01001, 0b01000, 0b11100, 0b01000, 0b01000, 0b01000,
+    ], // 'f'
+    [
+        0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110,
+    ], // 'g'
+    [
+        0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001,
+    ], // 'h'
+    [
+        0b00100, 0b00000, 0b01100, 0b00100, 0b00100, 0b00100, 0b01110,
+    ], // 'i'
+    [
+        0b00010, 0b00000, 0b00110, 0b00010, 0b00010, 0b10010, 0b01100,
+    ], // 'j'
+    [
+        0b10000, 0b10000, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010,
+    ], // 'k'
+    [
+        0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+    ], // 'l'
+    [
+        0b00000, 0b00000, 0b11010, 0b10101, 0b10101, 0b10001, 0b10001,
+    ], // 'm'
+    [
+        0b00000, 0b00000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001,
+    ], // 'n'
+    [
+        0b00000, 0b00000, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110,
+    ], // 'o'
+    [
+        0b00000, 0b00000, 0b11110, 0b10001, 0b11110, 0b10000, 0b10000,
+    ], // 'p'
+    [
+        0b00000, 0b00000, 0b01101, 0b10011, 0b01111, 0b00001, 0b00001,
+    ], // 'q'
+    [
+        0b00000, 0b00000, 0b10110, 0b11001, 0b10000, 0b10000, 0b10000,
+    ], // 'r'
+    [
+        0b00000, 0b00000, 0b01110, 0b10000, 0b01110, 0b00001, 0b11110,
+    ], // 's'
+    [
+        0b01000, 0b01000, 0b11100, 0b01000, 0b01000, 0b01001, 0b00110,
+    ], // 't'
+    [
+        0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b10011, 0b01101,
+    ], // 'u'
+    [
+        0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100,
+    ], // 'v'
+    [
+        0b00000, 0b00000, 0b10001, 0b10001, 0b10101, 0b10101, 0b01010,
+    ], // 'w'
+    [
+        0b00000, 0b00000, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001,
+    ], // 'x'
+    [
+        0b00000, 0b00000, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110,
+    ], // 'y'
+    [
+        0b00000, 0b00000, 0b11111, 0b00010, 0b00100, 0b01000, 0b11111,
+    ], // 'z'
+    [
+        0b00010, 0b00100, 0b00100, 0b01000, 0b00100, 0b00100, 0b00010,
+    ], // '{'
+    [
+        0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100,
+    ], // '|'
+    [
+        0b01000, 0b00100, 0b00100, 0b00010, 0b00100, 0b00100, 0b01000,
+    ], // '}'
+    [
+        0b00000, 0b00000, 0b01000, 0b10101, 0b00010, 0b00000, 0b00000,
+    ], // '~'
+];
+
+/// The glyph for `c`, characters the font doesn't cover are shown as `?`
+pub fn glyph(c: char) -> &'static [u8; GLYPH_HEIGHT] {
+    let index = (c as u32).wrapping_sub(FIRST as u32) as usize;
+    GLYPHS
+        .get(index)
+        .unwrap_or(&GLYPHS['?' as usize - FIRST as usize])
+}
+
+/// Test whether the pixel at column `col` and row `row` of a glyph is set
+pub fn is_set(glyph: &[u8; GLYPH_HEIGHT], col: usize, row: usize) -> bool {
+    col < GLYPH_WIDTH && glyph[row] & (1 << (GLYPH_WIDTH - 1 - col)) != 0
+}
+
+/// The width and height in pixels of `text` drawn at `scale`, accounting
+/// for newlines.
+pub fn measure(text: &str, scale: usize) -> (usize, usize) {
+    let lines = text.split('\n').collect::<Vec<_>>();
+    let columns = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    if columns == 0 && lines.len() == 1 {
+        return (0, 0);
+    }
+
+    let width = (columns * ADVANCE).saturating_sub(1) * scale;
+    let height = ((lines.len() - 1) * LINE_HEIGHT + GLYPH_HEIGHT) * scale;
+    (width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyphs_are_indexed_by_ascii_code() {
+        assert_eq!(&[0; GLYPH_HEIGHT], glyph(' '));
+        assert_eq!(
+            &[0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+            glyph('A')
+        );
+        assert_eq!(0b11111, glyph('_')[6]);
+    }
+
+    #[test]
+    fn test_unknown_characters_use_the_question_mark_glyph() {
+        assert_eq!(glyph('?'), glyph('\u{e9}'));
+        assert_eq!(glyph('?'), glyph('\t'));
+    }
+
+    #[test]
+    fn test_glyph_pixels_read_left_to_right() {
+        let l = glyph('L');
+        assert!(is_set(l, 0, 0));
+        assert!(!is_set(l, 4, 0));
+        assert!(is_set(l, 4, 6));
+        assert!(!is_set(l, 5, 6));
+    }
+
+    #[test]
+    fn test_measures_text() {
+        assert_eq!((0, 0), measure("", 1));
+        assert_eq!((5, 7), measure("A", 1));
+        assert_eq!((17, 7), measure("ABC", 1));
+        assert_eq!((34, 32), measure("ABC\nA", 2));
+    }
+}
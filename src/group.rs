@@ -0,0 +1,534 @@
+//! A scene graph of groups, each with a transform relative to the group it
+//! belongs to. Moving a group moves everything inside it, so articulated
+//! models like a robot arm or a solar system can be posed by changing a
+//! single transform rather than baking it into every child.
+//!
+//! Objects are nodes holding a `Shape`. Rays are intersected with a shape
+//! in its object space, and the normal where they hit is carried back out
+//! through every group to world space. A `Bvh` over the world bounds of the
+//! objects is built the first time a ray is cast, and again after the graph
+//! changes, so rays only test the objects they pass near.
+//!
+//! A whole graph is a `Shape` too, the union of its objects in world space,
+//! so a group can be one operand of a `Csg`.
+
+use crate::bounds::BoundingBox;
+use crate::bvh::{Bounded, Bvh};
+use crate::csg::{self, Operation};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{Crossing, Shape};
+use crate::units::{Point3D, Unit3D, Vector3D};
+use std::sync::{Arc, OnceLock};
+
+/// A handle to a node in a `SceneGraph`
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone)]
+struct Node {
+    transform: Matrix,
+    inverse: Matrix,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// The shape of an object, `None` for a plain group
+    shape: Option<Arc<dyn Shape>>,
+}
+
+/// Where a ray meets an object in a `SceneGraph`
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct GraphHit {
+    pub node: NodeId,
+    /// How far along the ray the object is
+    pub distance: Unit3D,
+    pub point: Point3D,
+    /// The unit normal of the object at `point`, in world space
+    pub normal: Vector3D,
+}
+
+/// An object placed in world space, as the hierarchy sees it
+#[derive(Debug, Clone)]
+struct Placed {
+    node: NodeId,
+    /// The transform taking world space to the object's space
+    inverse: Matrix,
+    bounds: BoundingBox,
+}
+
+impl Bounded for Placed {
+    fn bounds(&self) -> BoundingBox {
+        self.bounds
+    }
+}
+
+/// The objects of a graph sorted for intersecting rays
+#[derive(Debug, Clone)]
+struct Index {
+    bounded: Vec<Placed>,
+    bvh: Bvh,
+    /// Objects without finite bounds, like planes, which every ray is
+    /// tested against
+    unbounded: Vec<Placed>,
+}
+
+/// A tree of transformed nodes under a root group whose transform places
+/// the whole scene in world space.
+#[derive(Debug, Clone)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+    /// Built when first needed and thrown away whenever the graph changes
+    index: OnceLock<Index>,
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneGraph {
+    /// Create a graph holding only an untransformed root group
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                transform: Matrix::identity(),
+                inverse: Matrix::identity(),
+                parent: None,
+                children: Vec::new(),
+                shape: None,
+            }],
+            index: OnceLock::new(),
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// Add an empty group to `parent`
+    pub fn add_group(&mut self, parent: NodeId, transform: Matrix) -> NodeId {
+        self.add_node(parent, transform, None)
+    }
+
+    /// Add an object with `shape` to `parent`. Shapes are shared, so one
+    /// shape can be placed many times.
+    pub fn add_object(
+        &mut self,
+        parent: NodeId,
+        transform: Matrix,
+        shape: Arc<dyn Shape>,
+    ) -> NodeId {
+        self.add_node(parent, transform, Some(shape))
+    }
+
+    fn add_node(
+        &mut self,
+        parent: NodeId,
+        transform: Matrix,
+        shape: Option<Arc<dyn Shape>>,
+    ) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.index = OnceLock::new();
+        self.nodes.push(Node {
+            transform,
+            inverse: transform.inverse(),
+            parent: Some(parent),
+            children: Vec::new(),
+            shape,
+        });
+        self.nodes[parent.0].children.push(id);
+        id
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id.0].children
+    }
+
+    /// The shape of the node, `None` for a group
+    pub fn shape(&self, id: NodeId) -> Option<&Arc<dyn Shape>> {
+        self.nodes[id.0].shape.as_ref()
+    }
+
+    /// Every node holding a shape, in the order they were added
+    pub fn objects(&self) -> Vec<NodeId> {
+        (0..self.nodes.len())
+            .map(NodeId)
+            .filter(|&id| self.nodes[id.0].shape.is_some())
+            .collect()
+    }
+
+    /// The transform of the node relative to its parent
+    pub fn transform(&self, id: NodeId) -> Matrix {
+        self.nodes[id.0].transform
+    }
+
+    /// Replace the transform of the node relative to its parent. Panics if
+    /// the transform can't be inverted.
+    pub fn set_transform(&mut self, id: NodeId, transform: Matrix) {
+        self.index = OnceLock::new();
+        let node = &mut self.nodes[id.0];
+        node.inverse = transform.inverse();
+        node.transform = transform;
+    }
+
+    /// The transform taking points in the node's object space all the way
+    /// to world space, composing the transforms of every parent.
+    pub fn world_transform(&self, id: NodeId) -> Matrix {
+        let node = &self.nodes[id.0];
+        match node.parent {
+            Some(parent) => self.world_transform(parent) * node.transform,
+            None => node.transform,
+        }
+    }
+
+    /// The transform taking points in world space to the node's object
+    /// space
+    pub fn inverse_world_transform(&self, id: NodeId) -> Matrix {
+        let node = &self.nodes[id.0];
+        match node.parent {
+            Some(parent) => node.inverse * self.inverse_world_transform(parent),
+            None => node.inverse,
+        }
+    }
+
+    /// Convert a point in world space to the node's object space
+    pub fn world_to_object(&self, id: NodeId, point: Point3D) -> Point3D {
+        let node = &self.nodes[id.0];
+        let point = match node.parent {
+            Some(parent) => self.world_to_object(parent, point),
+            None => point,
+        };
+        node.inverse * point
+    }
+
+    /// Convert a normal in the node's object space to a normalized normal
+    /// in world space. Normals are transformed by the transposed inverse so
+    /// they stay perpendicular to surfaces under non-uniform scaling.
+    pub fn normal_to_world(&self, id: NodeId, normal: Vector3D) -> Vector3D {
+        let node = &self.nodes[id.0];
+        let normal = (node.inverse.transpose() * normal).normalize();
+        match node.parent {
+            Some(parent) => self.normal_to_world(parent, normal),
+            None => normal,
+        }
+    }
+
+    /// The box around the node and everything beneath it, in the node's
+    /// object space.
+    pub fn bounds(&self, id: NodeId) -> BoundingBox {
+        let node = &self.nodes[id.0];
+        let contents = node
+            .shape
+            .as_ref()
+            .map_or(BoundingBox::empty(), |shape| shape.bounds());
+        node.children.iter().fold(contents, |bounds, &child| {
+            let child_bounds = self.bounds(child).transform(&self.nodes[child.0].transform);
+            bounds.merge(&child_bounds)
+        })
+    }
+
+    /// The box around the node and everything beneath it, in world space
+    pub fn world_bounds(&self, id: NodeId) -> BoundingBox {
+        self.bounds(id).transform(&self.world_transform(id))
+    }
+
+    /// Where `ray` first meets the shape of node `id` in front of its
+    /// origin, ignoring the node's children
+    pub fn intersect_object(&self, id: NodeId, ray: &Ray) -> Option<GraphHit> {
+        let crossing = self.crossing(id, &self.inverse_world_transform(id), ray)?;
+        Some(self.hit(id, ray, crossing))
+    }
+
+    /// Where `ray` first meets any object in the graph in front of its
+    /// origin
+    pub fn intersect(&self, ray: &Ray) -> Option<GraphHit> {
+        let (node, crossing) = self
+            .candidates(ray)
+            .filter_map(|placed| {
+                let crossing = self.crossing(placed.node, &placed.inverse, ray)?;
+                Some((placed.node, crossing))
+            })
+            .min_by(|a, b| a.1.t.total_cmp(&b.1.t))?;
+        Some(self.hit(node, ray, crossing))
+    }
+
+    /// The objects whose boxes `ray` passes through, and those without a
+    /// box
+    fn candidates<'a>(&'a self, ray: &Ray) -> impl Iterator<Item = &'a Placed> {
+        let index = self.index.get_or_init(|| self.build_index());
+        index
+            .bvh
+            .query(ray.origin, ray.direction)
+            .into_iter()
+            .map(move |i| &index.bounded[i])
+            .chain(index.unbounded.iter())
+    }
+
+    /// Every crossing of `ray` with the shape of node `id`, given the
+    /// transform into its space
+    fn crossings(&self, id: NodeId, inverse: &Matrix, ray: &Ray) -> Vec<Crossing> {
+        match &self.nodes[id.0].shape {
+            // The direction isn't normalized in object space, so distances
+            // along the moved ray are the same as along the original
+            Some(shape) => shape.intersect(&ray.transform(inverse)),
+            None => Vec::new(),
+        }
+    }
+
+    /// The first crossing of `ray` with the shape of node `id` in front of
+    /// its origin
+    fn crossing(&self, id: NodeId, inverse: &Matrix, ray: &Ray) -> Option<Crossing> {
+        self.crossings(id, inverse, ray)
+            .into_iter()
+            .find(|crossing| crossing.t > 0.0)
+    }
+
+    fn hit(&self, id: NodeId, ray: &Ray, crossing: Crossing) -> GraphHit {
+        GraphHit {
+            node: id,
+            distance: crossing.t,
+            point: ray.position(crossing.t),
+            normal: self.normal_to_world(id, crossing.normal),
+        }
+    }
+
+    fn build_index(&self) -> Index {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = self
+            .objects()
+            .into_iter()
+            .map(|node| Placed {
+                node,
+                inverse: self.inverse_world_transform(node),
+                bounds: self.world_bounds(node),
+            })
+            .partition(|placed| placed.bounds.is_finite());
+        Index {
+            bvh: Bvh::build(&bounded),
+            bounded,
+            unbounded,
+        }
+    }
+}
+
+impl Shape for SceneGraph {
+    /// The crossings of the union of every object. Objects whose boxes lie
+    /// wholly behind the ray are skipped, which leaves out crossings behind
+    /// its origin but not whether the origin is inside.
+    fn intersect(&self, ray: &Ray) -> Vec<Crossing> {
+        self.candidates(ray).fold(Vec::new(), |union, placed| {
+            let crossings = self
+                .crossings(placed.node, &placed.inverse, ray)
+                .into_iter()
+                .map(|crossing| {
+                    let normal = self.normal_to_world(placed.node, crossing.normal);
+                    Crossing::new(crossing.t, normal)
+                })
+                .collect();
+            csg::combine(Operation::Union, union, crossings)
+        })
+    }
+}
+
+impl Bounded for SceneGraph {
+    fn bounds(&self) -> BoundingBox {
+        self.world_bounds(self.root())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{Cube, Plane, Sphere};
+    use crate::units::{Tuple, EPSILON};
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    fn close(a: Unit3D, b: Unit3D) -> bool {
+        (a - b).abs() < 0.0001
+    }
+
+    fn point_close(a: Point3D, b: Point3D) -> bool {
+        close(a.x(), b.x()) && close(a.y(), b.y()) && close(a.z(), b.z())
+    }
+
+    fn vector_close(a: Vector3D, b: Vector3D) -> bool {
+        close(a.x(), b.x()) && close(a.y(), b.y()) && close(a.z(), b.z())
+    }
+
+    #[test]
+    fn test_new_groups_belong_to_their_parent() {
+        let mut graph = SceneGraph::new();
+        let group = graph.add_group(graph.root(), Matrix::identity());
+        let child = graph.add_group(group, Matrix::identity());
+        assert_eq!(Some(group), graph.parent(child));
+        assert_eq!(&[child], graph.children(group));
+        assert_eq!(None, graph.parent(graph.root()));
+    }
+
+    #[test]
+    fn test_converts_a_point_from_world_to_object_space() {
+        let mut graph = SceneGraph::new();
+        let g1 = graph.add_group(graph.root(), Matrix::rotation_y(FRAC_PI_2));
+        let g2 = graph.add_group(g1, Matrix::scaling(Point3D::new(2.0, 2.0, 2.0)));
+        let s = graph.add_object(
+            g2,
+            Matrix::translation(Point3D::new(5.0, 0.0, 0.0)),
+            Arc::new(Sphere),
+        );
+        let point = graph.world_to_object(s, Point3D::new(-2.0, 0.0, -10.0));
+        assert!(
+            point_close(Point3D::new(0.0, 0.0, -1.0), point),
+            "{}",
+            point
+        );
+    }
+
+    #[test]
+    fn test_converts_a_normal_from_object_to_world_space() {
+        let mut graph = SceneGraph::new();
+        let g1 = graph.add_group(graph.root(), Matrix::rotation_y(FRAC_PI_2));
+        let g2 = graph.add_group(g1, Matrix::scaling(Point3D::new(1.0, 2.0, 3.0)));
+        let s = graph.add_object(
+            g2,
+            Matrix::translation(Point3D::new(5.0, 0.0, 0.0)),
+            Arc::new(Sphere),
+        );
+        let third = (3.0 as Unit3D).sqrt() / 3.0;
+        let normal = graph.normal_to_world(s, Vector3D::new(third, third, third));
+        assert!(
+            vector_close(Vector3D::new(0.2857, 0.4286, -0.8571), normal),
+            "{}",
+            normal
+        );
+        assert!((normal.magnitude() - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_world_transform_composes_parents() {
+        let mut graph = SceneGraph::new();
+        let arm = graph.add_group(
+            graph.root(),
+            Matrix::translation(Point3D::new(0.0, 1.0, 0.0)),
+        );
+        let hand = graph.add_group(arm, Matrix::translation(Point3D::new(2.0, 0.0, 0.0)));
+        let origin = graph.world_transform(hand) * Point3D::new(0.0, 0.0, 0.0);
+        assert!(point_close(Point3D::new(2.0, 1.0, 0.0), origin));
+
+        // Swinging the arm carries the hand with it
+        graph.set_transform(
+            arm,
+            Matrix::translation(Point3D::new(0.0, 1.0, 0.0)) * Matrix::rotation_z(PI),
+        );
+        let origin = graph.world_transform(hand) * Point3D::new(0.0, 0.0, 0.0);
+        assert!(point_close(Point3D::new(-2.0, 1.0, 0.0), origin));
+    }
+
+    #[test]
+    fn test_group_bounds_contain_transformed_children() {
+        let mut graph = SceneGraph::new();
+        let group = graph.add_group(
+            graph.root(),
+            Matrix::translation(Point3D::new(0.0, 0.0, 10.0)),
+        );
+        graph.add_object(
+            group,
+            Matrix::translation(Point3D::new(4.0, 0.0, 0.0)),
+            Arc::new(Cube),
+        );
+        graph.add_object(
+            group,
+            Matrix::scaling(Point3D::new(1.0, 3.0, 1.0)),
+            Arc::new(Sphere),
+        );
+
+        let bounds = graph.bounds(group);
+        assert_eq!(Point3D::new(-1.0, -3.0, -1.0), bounds.min());
+        assert_eq!(Point3D::new(5.0, 3.0, 1.0), bounds.max());
+
+        let world = graph.world_bounds(group);
+        assert_eq!(Point3D::new(-1.0, -3.0, 9.0), world.min());
+        assert_eq!(Point3D::new(5.0, 3.0, 11.0), world.max());
+    }
+
+    #[test]
+    fn test_empty_group_has_empty_bounds() {
+        let graph = SceneGraph::new();
+        assert!(graph.bounds(graph.root()).is_empty());
+        assert!(graph.objects().is_empty());
+    }
+
+    #[test]
+    fn test_rays_hit_transformed_objects() {
+        let mut graph = SceneGraph::new();
+        let group = graph.add_group(graph.root(), Matrix::scaling(Point3D::new(2.0, 2.0, 2.0)));
+        let ball = graph.add_object(
+            group,
+            Matrix::translation(Point3D::new(5.0, 0.0, 0.0)),
+            Arc::new(Sphere),
+        );
+        let ray = Ray::new(Point3D::new(10.0, 0.0, -10.0), Vector3D::new(0.0, 0.0, 1.0));
+        let hit = graph.intersect(&ray).unwrap();
+        assert_eq!(ball, hit.node);
+        assert!(close(8.0, hit.distance), "{}", hit.distance);
+        assert!(point_close(Point3D::new(10.0, 0.0, -2.0), hit.point));
+        assert!(vector_close(Vector3D::new(0.0, 0.0, -1.0), hit.normal));
+
+        let past = Ray::new(Point3D::new(0.0, 0.0, -10.0), Vector3D::new(0.0, 0.0, 1.0));
+        assert_eq!(None, graph.intersect(&past));
+        assert_eq!(None, graph.intersect_object(group, &ray));
+
+        // Moving the group after casting rays moves what they hit
+        graph.set_transform(group, Matrix::identity());
+        assert_eq!(None, graph.intersect(&ray));
+        let moved = Ray::new(Point3D::new(5.0, 0.0, -10.0), Vector3D::new(0.0, 0.0, 1.0));
+        assert_eq!(9.0, graph.intersect(&moved).unwrap().distance);
+    }
+
+    #[test]
+    fn test_many_objects_are_found_through_the_hierarchy() {
+        let mut graph = SceneGraph::new();
+        let balls: Vec<NodeId> = (0..100)
+            .map(|i| {
+                let transform = Matrix::translation(Point3D::new(i as Unit3D * 3.0, 0.0, 0.0));
+                graph.add_object(graph.root(), transform, Arc::new(Sphere))
+            })
+            .collect();
+        for &i in &[0, 37, 99] {
+            let x = i as Unit3D * 3.0;
+            let ray = Ray::new(Point3D::new(x, 0.0, -5.0), Vector3D::new(0.0, 0.0, 1.0));
+            let hit = graph.intersect(&ray).unwrap();
+            assert_eq!(balls[i], hit.node);
+            assert_eq!(4.0, hit.distance);
+        }
+        let between = Ray::new(Point3D::new(1.5, 0.0, -5.0), Vector3D::new(0.0, 0.0, 1.0));
+        assert_eq!(None, graph.intersect(&between));
+        let along = Ray::new(Point3D::new(400.0, 0.0, 0.0), Vector3D::new(-1.0, 0.0, 0.0));
+        assert_eq!(balls[99], graph.intersect(&along).unwrap().node);
+    }
+
+    #[test]
+    fn test_rays_hit_the_nearest_object() {
+        let mut graph = SceneGraph::new();
+        let floor = graph.add_object(graph.root(), Matrix::identity(), Arc::new(Plane));
+        let ball = graph.add_object(
+            graph.root(),
+            Matrix::translation(Point3D::new(0.0, 1.0, 0.0)),
+            Arc::new(Sphere),
+        );
+        assert_eq!(vec![floor, ball], graph.objects());
+        let down = Ray::new(Point3D::new(0.0, 5.0, 0.0), Vector3D::new(0.0, -1.0, 0.0));
+        assert_eq!(ball, graph.intersect(&down).unwrap().node);
+        let beside = Ray::new(Point3D::new(3.0, 5.0, 0.0), Vector3D::new(0.0, -1.0, 0.0));
+        let hit = graph.intersect(&beside).unwrap();
+        assert_eq!(floor, hit.node);
+        assert_eq!(5.0, hit.distance);
+        // Rays starting inside an object hit its far side
+        let inside = Ray::new(Point3D::new(0.0, 1.0, 0.0), Vector3D::new(1.0, 0.0, 0.0));
+        let hit = graph.intersect(&inside).unwrap();
+        assert!(close(1.0, hit.distance));
+        assert!(vector_close(Vector3D::new(1.0, 0.0, 0.0), hit.normal));
+    }
+}
@@ -0,0 +1,740 @@
+//! Integrators, which work out the light arriving along a ray by following
+//! it into a `World`. The `Whitted` integrator lights each surface straight
+//! from the lights and follows mirror reflections and refractions, which is
+//! quick but misses light bouncing between matte or rough surfaces. The
+//! `PathTracer` follows rays bouncing at random until they run out of
+//! energy, which converges on all of the light as more samples are taken.
+//!
+//! Neither knows what the world is made of: the world reports the nearest
+//! surface a ray hits and lists its lights, so any kind of object can be
+//! used. Surfaces scatter light by their material's `Bsdf`. Light is added
+//! up as `Spectrum`s, unclamped until the image is tone mapped.
+
+use crate::bsdf::{Bsdf, Frame, Lambert};
+use crate::camera::Camera;
+use crate::canvas::Canvas;
+use crate::color::{Color, Spectrum};
+use crate::light::{AreaLight, AreaShape, Light};
+use crate::ray::Ray;
+use crate::render::{Renderer, Rng};
+use crate::sampling::{Film, Sampler};
+use crate::units::{Point3D, Tuple, Unit3D, Vector3D};
+use std::f32::consts::PI;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// How far rays leaving a surface start above it, so they don't hit the
+/// surface they are leaving
+const SURFACE_OFFSET: Unit3D = 0.0001;
+
+/// What a surface is made of
+#[derive(Debug, Clone)]
+pub struct Material {
+    /// How the surface scatters light, shared between every surface made
+    /// of the material
+    pub bsdf: Arc<dyn Bsdf>,
+    /// Light the surface gives off itself
+    pub emission: Spectrum,
+}
+
+impl Material {
+    /// A surface scattering light by `bsdf`
+    pub fn new<B: Bsdf + 'static>(bsdf: B) -> Self {
+        Self {
+            bsdf: Arc::new(bsdf),
+            emission: Spectrum::black(),
+        }
+    }
+
+    /// A surface reflecting `color` evenly in every direction
+    pub fn matte(color: Color) -> Self {
+        Self::new(Lambert::new(color))
+    }
+
+    /// A black surface giving off `emission`
+    pub fn glowing(emission: Spectrum) -> Self {
+        Self {
+            emission,
+            ..Self::matte(Color::rgb(0.0, 0.0, 0.0))
+        }
+    }
+}
+
+/// Where a ray meets a surface of the world
+#[derive(Debug, Clone, Copy)]
+pub struct Surface<'a> {
+    /// How far along the ray the surface is
+    pub distance: Unit3D,
+    pub point: Point3D,
+    /// The unit normal of the surface, which may face either way. Surfaces
+    /// which let light through take it to point out of them.
+    pub normal: Vector3D,
+    pub material: &'a Material,
+}
+
+/// Everything about a scene an integrator needs to ask
+pub trait World: Sync {
+    /// The nearest surface `ray` meets in front of its origin
+    fn intersect(&self, ray: &Ray) -> Option<Surface<'_>>;
+
+    /// Lights at a point or infinitely far away, which rays never hit
+    fn lights(&self) -> &[Box<dyn Light + Send + Sync>] {
+        &[]
+    }
+
+    /// Lights with a surface, which rays can hit. They don't cast shadows
+    /// themselves.
+    fn area_lights(&self) -> &[AreaLight] {
+        &[]
+    }
+
+    /// The light arriving from far away along `direction`, which is seen by
+    /// rays leaving the scene
+    fn background(&self, _direction: Vector3D) -> Spectrum {
+        Spectrum::black()
+    }
+}
+
+/// A way of working out the light arriving along a ray
+pub trait Integrator: Debug + Send + Sync {
+    /// The light arriving at the origin of `ray` from along it
+    fn radiance(&self, world: &dyn World, ray: &Ray, rng: &mut Rng) -> Spectrum;
+}
+
+/// The integrators which can be picked by name, in a scene or on the
+/// command line
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Method {
+    Whitted,
+    PathTracer,
+}
+
+impl Method {
+    /// The method called `name`, `whitted` or `path`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "whitted" => Some(Method::Whitted),
+            "path" | "path-tracer" => Some(Method::PathTracer),
+            _ => None,
+        }
+    }
+
+    /// The integrator with its default settings
+    pub fn integrator(&self) -> Box<dyn Integrator> {
+        match self {
+            Method::Whitted => Box::new(Whitted::default()),
+            Method::PathTracer => Box::new(PathTracer::default()),
+        }
+    }
+}
+
+impl Renderer {
+    /// Render `world` as `camera` sees it into a film the size of `canvas`,
+    /// using `integrator` for every sample. Cameras count rows from the
+    /// top, so the canvas should have its origin there.
+    pub fn render_world<P>(
+        &self,
+        canvas: &Canvas,
+        camera: &Camera,
+        world: &dyn World,
+        integrator: &dyn Integrator,
+        sampler: &Sampler,
+        progress: P,
+    ) -> Film
+    where
+        P: Fn(usize, usize) + Sync,
+    {
+        self.render_radiance(
+            canvas,
+            sampler,
+            |x, y, rng| match camera.ray_for_sample(x, y, rng) {
+                Some(ray) => integrator.radiance(world, &ray, rng),
+                None => Spectrum::black(),
+            },
+            progress,
+        )
+    }
+}
+
+// ==========================================================================
+// Whitted
+
+/// Classic recursive ray tracing. Surfaces are lit only by the lights,
+/// averaged over their samples, and perfectly smooth ones reflect or refract
+/// up to `max_depth` times. Rough reflections only show the lights.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Whitted {
+    pub max_depth: usize,
+}
+
+impl Whitted {
+    pub fn new(max_depth: usize) -> Self {
+        Self { max_depth }
+    }
+
+    fn trace(&self, world: &dyn World, ray: &Ray, rng: &mut Rng, depth: usize) -> Spectrum {
+        let surface = match nearest(world, ray) {
+            None => return world.background(ray.direction),
+            Some(Hit::Light(light, t)) => return emitted(light, ray.position(t), ray.direction),
+            Some(Hit::Surface(surface)) => surface,
+        };
+        let bsdf = surface.material.bsdf.as_ref();
+        let frame = Frame::new(surface.normal);
+        let wo = frame.to_local(-ray.direction);
+
+        let mut total = surface.material.emission;
+        let lights = world
+            .lights()
+            .iter()
+            .map(|light| light.as_ref() as &dyn Light);
+        let area_lights = world.area_lights().iter().map(|light| light as &dyn Light);
+        for light in lights.chain(area_lights) {
+            total += light_from(light, world, surface.point, ray.time, bsdf, &frame, wo, rng);
+        }
+        if depth < self.max_depth {
+            // Smooth glass picks reflection or refraction at random, which
+            // averages out over the samples of a pixel
+            match bsdf.sample(wo, rng) {
+                Some(sample) if sample.specular => {
+                    let direction = frame.to_world(sample.direction);
+                    let next =
+                        Ray::new(leaving(surface.point, surface.normal, direction), direction)
+                            .with_time(ray.time);
+                    total += self.trace(world, &next, rng, depth + 1) * sample.weight;
+                }
+                _ => {}
+            }
+        }
+        total
+    }
+}
+
+impl Default for Whitted {
+    fn default() -> Self {
+        Self::new(5)
+    }
+}
+
+impl Integrator for Whitted {
+    fn radiance(&self, world: &dyn World, ray: &Ray, rng: &mut Rng) -> Spectrum {
+        self.trace(world, ray, rng, 0)
+    }
+}
+
+// ==========================================================================
+// PathTracer
+
+/// Unidirectional path tracing. Each ray scatters off surfaces in a random
+/// direction picked by their `Bsdf`, more often where they scatter the most
+/// light, and off mirrors in the mirrored one. At every surface the lights
+/// are also sampled directly, and light found both ways is weighted by
+/// multiple importance sampling so neither is counted twice. After
+/// `roulette_depth` bounces paths carrying little light are stopped at
+/// random, and the survivors carry more to make up for them.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct PathTracer {
+    /// The most bounces a path can take
+    pub max_depth: usize,
+    /// The bounces before paths may be stopped at random
+    pub roulette_depth: usize,
+}
+
+impl PathTracer {
+    pub fn new(max_depth: usize, roulette_depth: usize) -> Self {
+        Self {
+            max_depth,
+            roulette_depth,
+        }
+    }
+
+    /// The light from the lights scattered towards `wo` by `surface`,
+    /// whose space is `frame`, at `time`
+    fn direct_light(
+        &self,
+        world: &dyn World,
+        surface: &Surface,
+        time: Unit3D,
+        frame: &Frame,
+        wo: Vector3D,
+        rng: &mut Rng,
+    ) -> Spectrum {
+        // Lights without a surface are counted as the Whitted integrator
+        // counts them, so a matte surface looks the same lit by either
+        let bsdf = surface.material.bsdf.as_ref();
+        let mut total = Spectrum::black();
+        for light in world.lights() {
+            let point = surface.point;
+            total += light_from(light.as_ref(), world, point, time, bsdf, frame, wo, rng);
+        }
+
+        for light in world.area_lights() {
+            let light_point = light.point_on(rng.next_f32(), rng.next_f32());
+            let offset = light_point - surface.point;
+            let distance = offset.magnitude();
+            let direction = offset / distance;
+            let wi = frame.to_local(direction);
+            let scattered = bsdf.eval(wo, wi);
+            let light_pdf = match light_pdf(light, surface.point, light_point) {
+                Some(pdf) if !scattered.is_black() => pdf,
+                _ => continue,
+            };
+            let point = leaving(surface.point, surface.normal, direction);
+            if is_shadowed(world, point, direction, distance, time) {
+                continue;
+            }
+            let weight = power_heuristic(light_pdf, bsdf.pdf(wo, wi));
+            total += scattered * light.radiance() * (wi.z().abs() * weight / light_pdf);
+        }
+        total
+    }
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        Self::new(16, 3)
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, world: &dyn World, ray: &Ray, rng: &mut Rng) -> Spectrum {
+        let mut ray = *ray;
+        let mut total = Spectrum::black();
+        // The fraction of the light arriving along `ray` which reaches the
+        // camera
+        let mut throughput = Spectrum::gray(1.0);
+        // The chance of the bounce casting `ray` having picked it, or `None`
+        // if it came from the camera or a mirror and so never sampled the
+        // lights
+        let mut bounce_pdf = None;
+
+        for depth in 0..=self.max_depth {
+            let surface = match nearest(world, &ray) {
+                None => {
+                    total += throughput * world.background(ray.direction);
+                    break;
+                }
+                Some(Hit::Light(light, t)) => {
+                    let light_point = ray.position(t);
+                    let weight = match (bounce_pdf, light_pdf(light, ray.origin, light_point)) {
+                        (Some(bounce_pdf), Some(light_pdf)) => {
+                            power_heuristic(bounce_pdf, light_pdf)
+                        }
+                        _ => 1.0,
+                    };
+                    total += throughput * emitted(light, light_point, ray.direction) * weight;
+                    break;
+                }
+                Some(Hit::Surface(surface)) => surface,
+            };
+            let frame = Frame::new(surface.normal);
+            let wo = frame.to_local(-ray.direction);
+            total += throughput * surface.material.emission;
+            if depth == self.max_depth {
+                break;
+            }
+
+            let direct = self.direct_light(world, &surface, ray.time, &frame, wo, rng);
+            total += throughput * direct;
+
+            let sample = match surface.material.bsdf.sample(wo, rng) {
+                Some(sample) => sample,
+                None => break,
+            };
+            let direction = frame.to_world(sample.direction);
+            ray = Ray::new(leaving(surface.point, surface.normal, direction), direction)
+                .with_time(ray.time);
+            throughput = throughput * sample.weight;
+            bounce_pdf = if sample.specular {
+                None
+            } else {
+                Some(sample.pdf)
+            };
+
+            if depth + 1 >= self.roulette_depth {
+                let survival = throughput.max_channel().min(0.95);
+                if rng.next_f32() >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+        }
+        total
+    }
+}
+
+// ==========================================================================
+// Helpers
+
+/// The nearest thing a ray hits
+enum Hit<'a> {
+    Surface(Surface<'a>),
+    Light(&'a AreaLight, Unit3D),
+}
+
+fn nearest<'a>(world: &'a dyn World, ray: &Ray) -> Option<Hit<'a>> {
+    let surface = world.intersect(ray);
+    let mut distance = surface
+        .as_ref()
+        .map_or(Unit3D::INFINITY, |surface| surface.distance);
+    let mut hit = surface.map(Hit::Surface);
+    for light in world.area_lights() {
+        match light.intersect(ray) {
+            Some(t) if t < distance => {
+                distance = t;
+                hit = Some(Hit::Light(light, t));
+            }
+            _ => {}
+        }
+    }
+    hit
+}
+
+/// Whether anything lies between `point` and `distance` along `direction`
+fn is_shadowed(
+    world: &dyn World,
+    point: Point3D,
+    direction: Vector3D,
+    distance: Unit3D,
+    time: Unit3D,
+) -> bool {
+    world
+        .intersect(&Ray::new(point, direction).with_time(time))
+        .is_some_and(|surface| surface.distance < distance - SURFACE_OFFSET)
+}
+
+/// The light from `light` scattered towards `wo` by `bsdf` at `point` at
+/// `time`, averaged over the light's samples. Lights are taken to give a matte
+/// surface its color times their intensity where they shine straight at it,
+/// as they always have, which is π times what a `Bsdf` scatters.
+#[allow(clippy::too_many_arguments)]
+fn light_from(
+    light: &dyn Light,
+    world: &dyn World,
+    point: Point3D,
+    time: Unit3D,
+    bsdf: &dyn Bsdf,
+    frame: &Frame,
+    wo: Vector3D,
+    rng: &mut Rng,
+) -> Spectrum {
+    light.reflected(
+        point,
+        rng,
+        &|point, sample| {
+            let start = leaving(point, frame.normal(), sample.direction);
+            is_shadowed(world, start, sample.direction, sample.distance, time)
+        },
+        &|sample| {
+            let wi = frame.to_local(sample.direction);
+            bsdf.eval(wo, wi) * sample.intensity * (PI * wi.z().abs())
+        },
+    )
+}
+
+/// The light `light` gives off at `point` towards a ray heading along
+/// `direction`. Spheres only shine outwards.
+fn emitted(light: &AreaLight, point: Point3D, direction: Vector3D) -> Spectrum {
+    match light.shape() {
+        AreaShape::Sphere { .. } if light.normal_at(point).dot(direction) > 0.0 => {
+            Spectrum::black()
+        }
+        _ => light.radiance(),
+    }
+}
+
+/// The chance per unit solid angle of picking `light_point` when picking a
+/// point evenly over the area of `light` as seen from `point`, or `None` if
+/// the light faces away
+fn light_pdf(light: &AreaLight, point: Point3D, light_point: Point3D) -> Option<Unit3D> {
+    let offset = light_point - point;
+    let distance_squared = offset.dot(offset);
+    let cosine = light.normal_at(light_point).dot(offset) / distance_squared.sqrt();
+    let cosine = match light.shape() {
+        AreaShape::Rectangle { .. } => cosine.abs(),
+        AreaShape::Sphere { .. } => -cosine,
+    };
+    if cosine <= 0.0 {
+        None
+    } else {
+        Some(distance_squared / (light.area() * cosine))
+    }
+}
+
+/// Veach's power heuristic, the weight for a sample taken with chance `pdf`
+/// which another strategy would have taken with chance `other`
+fn power_heuristic(pdf: Unit3D, other: Unit3D) -> Unit3D {
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b == 0.0 {
+        0.0
+    } else {
+        a / (a + b)
+    }
+}
+
+/// `point` moved off its surface with `normal` to the side `direction`
+/// leaves by, so rays starting there don't hit the surface they are leaving
+fn leaving(point: Point3D, normal: Vector3D, direction: Vector3D) -> Point3D {
+    if normal.dot(direction) < 0.0 {
+        point - normal * SURFACE_OFFSET
+    } else {
+        point + normal * SURFACE_OFFSET
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsdf::{Conductor, Dielectric, Principled};
+    use crate::light::PointLight;
+    use crate::matrix::Matrix;
+    use crate::motion::{Motion, Moving};
+    use crate::shape::{Shape, Sphere};
+
+    /// Either the floor at y = 0 or the inside of a unit sphere, with lights
+    struct TestWorld {
+        inside: bool,
+        material: Material,
+        lights: Vec<Box<dyn Light + Send + Sync>>,
+        area_lights: Vec<AreaLight>,
+    }
+
+    impl TestWorld {
+        fn floor(material: Material) -> Self {
+            Self {
+                inside: false,
+                material,
+                lights: Vec::new(),
+                area_lights: Vec::new(),
+            }
+        }
+    }
+
+    impl World for TestWorld {
+        fn intersect(&self, ray: &Ray) -> Option<Surface<'_>> {
+            let (distance, normal) = if self.inside {
+                let far = *Sphere.intersect(ray).last()?;
+                (far.t, far.normal)
+            } else {
+                (
+                    -ray.origin.y() / ray.direction.y(),
+                    Vector3D::new(0.0, 1.0, 0.0),
+                )
+            };
+            if distance > 0.0 && distance.is_finite() {
+                Some(Surface {
+                    distance,
+                    point: ray.position(distance),
+                    normal,
+                    material: &self.material,
+                })
+            } else {
+                None
+            }
+        }
+
+        fn lights(&self) -> &[Box<dyn Light + Send + Sync>] {
+            &self.lights
+        }
+
+        fn area_lights(&self) -> &[AreaLight] {
+            &self.area_lights
+        }
+
+        fn background(&self, _: Vector3D) -> Spectrum {
+            Spectrum::new(0.0, 0.0, 0.25)
+        }
+    }
+
+    fn looking_down() -> Ray {
+        Ray::new(Point3D::new(0.0, 1.0, 0.0), Vector3D::new(0.0, -1.0, 0.0))
+    }
+
+    fn average(
+        integrator: &dyn Integrator,
+        world: &dyn World,
+        ray: &Ray,
+        count: usize,
+    ) -> Spectrum {
+        let mut rng = Rng::new(5);
+        let mut total = Spectrum::black();
+        for _ in 0..count {
+            total += integrator.radiance(world, ray, &mut rng);
+        }
+        total / count as f32
+    }
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() <= b.abs() * tolerance
+    }
+
+    #[test]
+    fn test_methods_from_names() {
+        assert_eq!(Some(Method::Whitted), Method::from_name("Whitted"));
+        assert_eq!(Some(Method::PathTracer), Method::from_name("path"));
+        assert_eq!(None, Method::from_name("photon"));
+        assert_eq!(
+            format!("{:?}", PathTracer::default()),
+            format!("{:?}", Method::PathTracer.integrator())
+        );
+    }
+
+    #[test]
+    fn test_point_lights_look_the_same_to_both_integrators() {
+        let mut world = TestWorld::floor(Material::matte(Color::rgb(0.5, 0.5, 0.5)));
+        world.lights.push(Box::new(PointLight::new(
+            Point3D::new(0.0, 2.0, 0.0),
+            Color::rgb(1.0, 1.0, 1.0),
+        )));
+        let mut rng = Rng::new(0);
+        let whitted = Whitted::default().radiance(&world, &looking_down(), &mut rng);
+        assert_eq!(Spectrum::gray(0.5), whitted);
+        // Every bounce off the floor escapes to the blue background
+        let traced = average(&PathTracer::default(), &world, &looking_down(), 2000);
+        assert!(close(traced.r(), 0.5, 0.0001), "{:?}", traced);
+        assert!(close(traced.b(), 0.5 + 0.5 * 0.25, 0.02), "{:?}", traced);
+
+        let up = Ray::new(Point3D::new(0.0, 1.0, 0.0), Vector3D::new(0.0, 1.0, 0.0));
+        assert_eq!(Spectrum::new(0.0, 0.0, 0.25), whitted_radiance(&world, &up));
+    }
+
+    /// The floor under a ball which moves out from between it and the
+    /// light while the shutter is open
+    struct Occluded {
+        floor: TestWorld,
+        ball: Moving,
+    }
+
+    impl World for Occluded {
+        fn intersect(&self, ray: &Ray) -> Option<Surface<'_>> {
+            let floor = self.floor.intersect(ray);
+            let ball = self.ball.intersect(ray);
+            match ball.iter().find(|crossing| crossing.t > 0.0) {
+                Some(crossing) if floor.is_none_or(|floor| crossing.t < floor.distance) => {
+                    Some(Surface {
+                        distance: crossing.t,
+                        point: ray.position(crossing.t),
+                        normal: crossing.normal.normalize(),
+                        material: &self.floor.material,
+                    })
+                }
+                _ => floor,
+            }
+        }
+
+        fn lights(&self) -> &[Box<dyn Light + Send + Sync>] {
+            self.floor.lights()
+        }
+    }
+
+    #[test]
+    fn test_shadows_are_cast_where_things_are_at_the_time() {
+        let mut floor = TestWorld::floor(Material::matte(Color::rgb(0.5, 0.5, 0.5)));
+        floor.lights.push(Box::new(PointLight::new(
+            Point3D::new(0.0, 2.0, 0.0),
+            Color::rgb(1.0, 1.0, 1.0),
+        )));
+        let ball = |x| {
+            Matrix::translation(Point3D::new(x, 1.5, 0.0))
+                * Matrix::scaling(Point3D::new(0.2, 0.2, 0.2))
+        };
+        let world = Occluded {
+            floor,
+            ball: Moving::new(Arc::new(Sphere), Motion::linear(ball(0.0), ball(10.0))),
+        };
+        let at = |time| looking_down().with_time(time);
+        assert_eq!(Spectrum::black(), whitted_radiance(&world, &at(0.0)));
+        assert_eq!(Spectrum::gray(0.5), whitted_radiance(&world, &at(1.0)));
+
+        let shaded = average(&PathTracer::default(), &world, &at(0.0), 200);
+        let lit = average(&PathTracer::default(), &world, &at(1.0), 200);
+        assert!(shaded.r() < 0.1, "{:?}", shaded);
+        assert!(close(lit.r(), 0.5, 0.0001), "{:?}", lit);
+    }
+
+    fn whitted_radiance(world: &dyn World, ray: &Ray) -> Spectrum {
+        Whitted::default().radiance(world, ray, &mut Rng::new(0))
+    }
+
+    #[test]
+    fn test_mirrors_reflect_the_background() {
+        let mirror = Principled::new(Color::rgb(1.0, 1.0, 1.0), 1.0, 0.0);
+        let world = TestWorld::floor(Material::new(mirror));
+        let expected = Spectrum::new(0.0, 0.0, 0.25);
+        assert_eq!(expected, whitted_radiance(&world, &looking_down()));
+        let mut rng = Rng::new(0);
+        let traced = PathTracer::default().radiance(&world, &looking_down(), &mut rng);
+        assert_eq!(expected, traced);
+    }
+
+    #[test]
+    fn test_glass_and_metal_floors() {
+        // Whatever smooth glass doesn't reflect it lets through, and both
+        // ways lead to the background
+        let world = TestWorld::floor(Material::new(Dielectric::glass(0.0)));
+        let expected = Spectrum::new(0.0, 0.0, 0.25);
+        for seed in 0..8 {
+            let mut rng = Rng::new(seed);
+            assert_eq!(expected, whitted_radiance(&world, &looking_down()));
+            let traced = PathTracer::default().radiance(&world, &looking_down(), &mut rng);
+            assert_eq!(expected, traced);
+        }
+
+        // Rough gold catches a light off to the side in its own color
+        let mut world = TestWorld::floor(Material::new(Conductor::gold(0.4)));
+        world.lights.push(Box::new(PointLight::new(
+            Point3D::new(-0.5, 1.0, 0.0),
+            Color::rgb(1.0, 1.0, 1.0),
+        )));
+        let ray = Ray::new(
+            Point3D::new(0.5, 1.0, 0.0),
+            Vector3D::new(-0.5, -1.0, 0.0).normalize(),
+        );
+        let lit = whitted_radiance(&world, &ray);
+        assert!(lit.r() > lit.b() && lit.b() > 0.0, "{:?}", lit);
+    }
+
+    #[test]
+    fn test_area_lights_are_sampled_both_ways() {
+        // A small bright square above a white floor, which lights it about
+        // as much as a point light of the same power would
+        let mut world = TestWorld::floor(Material::matte(Color::rgb(1.0, 1.0, 1.0)));
+        let mut light = AreaLight::rectangle(
+            Point3D::new(-0.1, 2.0, -0.1),
+            Vector3D::new(0.2, 0.0, 0.0),
+            1,
+            Vector3D::new(0.0, 0.0, 0.2),
+            1,
+            Color::rgb(1.0, 1.0, 1.0),
+        );
+        light.set_strength(100.0);
+        world.area_lights.push(light);
+        let irradiance = 100.0 * 0.04 / 4.0;
+        let traced = average(&PathTracer::default(), &world, &looking_down(), 4000);
+        assert!(close(traced.r(), irradiance / PI, 0.03), "{:?}", traced);
+
+        // Seen directly the light is as bright as it is
+        let at_light = Ray::new(Point3D::new(0.0, 1.0, 0.0), Vector3D::new(0.0, 1.0, 0.0));
+        let mut rng = Rng::new(0);
+        let seen = PathTracer::default().radiance(&world, &at_light, &mut rng);
+        assert_eq!(Spectrum::gray(100.0), seen);
+    }
+
+    #[test]
+    fn test_light_bounces_around_a_glowing_room() {
+        // Inside a sphere reflecting half the light and giving off 1, the
+        // light bouncing around adds up to 1 + 1/2 + 1/4 + ... = 2
+        let mut material = Material::matte(Color::rgb(0.5, 0.5, 0.5));
+        material.emission = Spectrum::gray(1.0);
+        let world = TestWorld {
+            inside: true,
+            material,
+            lights: Vec::new(),
+            area_lights: Vec::new(),
+        };
+        let ray = Ray::new(Point3D::new(0.0, 0.0, 0.0), Vector3D::new(0.0, 0.0, 1.0));
+        let traced = average(&PathTracer::new(64, 3), &world, &ray, 4000);
+        assert!(close(traced.r(), 2.0, 0.03), "{:?}", traced);
+        // The Whitted integrator only sees the glow
+        assert_eq!(Spectrum::gray(1.0), whitted_radiance(&world, &ray));
+    }
+}
@@ -1,6 +1,34 @@
 extern crate textwrap;
 
+pub mod adaptive;
+pub mod animation;
+pub mod bounds;
+pub mod bvh;
+pub mod camera;
+pub mod bsdf;
 pub mod canvas;
 pub mod color;
+pub mod csg;
+pub mod diff;
+pub mod encode;
+pub mod filter;
+pub mod font;
+pub mod group;
+pub mod integrator;
+pub mod light;
+pub mod loader;
 pub mod matrix;
+pub mod motion;
+pub mod pattern;
+pub mod projection;
+pub mod raster;
+pub mod ray;
+pub mod render;
+pub mod sampling;
+pub mod scene;
+pub mod shape;
+pub mod terminal;
+pub mod text;
+pub mod tonemap;
 pub mod units;
+pub mod view;
@@ -0,0 +1,758 @@
+//! Lights. Every kind of light implements `Light`, which hands out samples
+//! of the light arriving at a point: the direction it comes from, how far
+//! away it is for shadow rays and how bright it is once attenuated.
+//!
+//! A point light casts hard edged shadows because a point is either visible
+//! from a surface or it isn't. Area lights are sampled at several jittered
+//! points instead, and the fraction of those a surface can see gives soft
+//! penumbrae at the edges of their shadows.
+//!
+//! Area lights are also surfaces rays can hit, so integrators which follow
+//! light bouncing around a scene can find them by chance as well as by
+//! sampling them.
+
+use crate::color::Spectrum;
+use crate::ray::Ray;
+use crate::render::Rng;
+use crate::shape;
+use crate::units::{Point3D, Unit3D, Vector3D, EPSILON};
+use std::f32::consts::PI;
+
+/// How light fades with the distance from its source
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Attenuation {
+    /// Light keeps its full intensity however far it goes
+    None,
+    /// Light falls off with the square of the distance, as it really does
+    InverseSquare,
+    /// `1 / (constant + linear * d + quadratic * d * d)`
+    Custom {
+        constant: f32,
+        linear: f32,
+        quadratic: f32,
+    },
+}
+
+impl Attenuation {
+    /// The fraction of the light left after `distance`, never more than 1
+    pub fn factor(&self, distance: Unit3D) -> f32 {
+        let falloff = match *self {
+            Attenuation::None => return 1.0,
+            Attenuation::InverseSquare => distance * distance,
+            Attenuation::Custom {
+                constant,
+                linear,
+                quadratic,
+            } => constant + linear * distance + quadratic * distance * distance,
+        };
+        if falloff <= 1.0 {
+            1.0
+        } else {
+            1.0 / falloff
+        }
+    }
+}
+
+/// Light arriving at a point from one sample of a light
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct LightSample {
+    /// The unit vector from the point towards the light
+    pub direction: Vector3D,
+    /// How far away the light is, infinite for directional lights
+    pub distance: Unit3D,
+    /// The light arriving, after attenuation and any spot light cone
+    pub intensity: Spectrum,
+}
+
+impl LightSample {
+    fn between(point: Point3D, light_point: Point3D, intensity: Spectrum) -> Option<Self> {
+        let offset = light_point - point;
+        let distance = offset.magnitude();
+        if distance == 0.0 {
+            return None;
+        }
+        Some(Self {
+            direction: offset / distance,
+            distance,
+            intensity,
+        })
+    }
+}
+
+/// Something which gives off light
+pub trait Light {
+    /// Samples of the light arriving at `point`. Lights with a size return
+    /// several, jittered with `rng`.
+    fn samples(&self, point: Point3D, rng: &mut Rng) -> Vec<LightSample>;
+
+    /// The fraction of the light which reaches `point`, from 0 in full
+    /// shadow to 1 in full light. `is_shadowed` is asked whether anything
+    /// blocks the shadow ray from `point` towards each sample.
+    fn intensity_at(
+        &self,
+        point: Point3D,
+        rng: &mut Rng,
+        is_shadowed: &dyn Fn(Point3D, &LightSample) -> bool,
+    ) -> f32 {
+        let samples = self.samples(point, rng);
+        if samples.is_empty() {
+            return 0.0;
+        }
+        let lit = samples
+            .iter()
+            .filter(|sample| !is_shadowed(point, sample))
+            .count();
+        lit as f32 / samples.len() as f32
+    }
+
+    /// The diffuse light falling on a surface at `point` facing `normal`,
+    /// averaged over the samples of the light. Samples blocked by
+    /// `is_shadowed` or behind the surface add nothing, so a point in a
+    /// penumbra is lit partially.
+    fn lighting(
+        &self,
+        point: Point3D,
+        normal: Vector3D,
+        rng: &mut Rng,
+        is_shadowed: &dyn Fn(Point3D, &LightSample) -> bool,
+    ) -> Spectrum {
+        self.reflected(point, rng, is_shadowed, &|sample| {
+            sample.intensity * sample.direction.dot(normal).max(0.0)
+        })
+    }
+
+    /// The light `reflect` passes on from each sample arriving at `point`,
+    /// averaged over the samples of the light. Shadow rays are only cast
+    /// for samples which reflect something.
+    fn reflected(
+        &self,
+        point: Point3D,
+        rng: &mut Rng,
+        is_shadowed: &dyn Fn(Point3D, &LightSample) -> bool,
+        reflect: &dyn Fn(&LightSample) -> Spectrum,
+    ) -> Spectrum {
+        let samples = self.samples(point, rng);
+        let mut total = Spectrum::black();
+        for sample in &samples {
+            let light = reflect(sample);
+            if !light.is_black() && !is_shadowed(point, sample) {
+                total += light;
+            }
+        }
+        total / samples.len().max(1) as f32
+    }
+}
+
+// ==========================================================================
+// PointLight
+
+/// Light given off in every direction from a single point
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct PointLight {
+    position: Point3D,
+    intensity: Spectrum,
+    attenuation: Attenuation,
+}
+
+impl PointLight {
+    pub fn new<I: Into<Spectrum>>(position: Point3D, intensity: I) -> Self {
+        Self {
+            position,
+            intensity: intensity.into(),
+            attenuation: Attenuation::None,
+        }
+    }
+
+    pub fn position(&self) -> Point3D {
+        self.position
+    }
+
+    pub fn intensity(&self) -> Spectrum {
+        self.intensity
+    }
+
+    pub fn attenuation(&self) -> Attenuation {
+        self.attenuation
+    }
+
+    pub fn set_attenuation(&mut self, attenuation: Attenuation) {
+        self.attenuation = attenuation;
+    }
+}
+
+impl Light for PointLight {
+    fn samples(&self, point: Point3D, _: &mut Rng) -> Vec<LightSample> {
+        let distance = (self.position - point).magnitude();
+        let intensity = self.intensity * self.attenuation.factor(distance);
+        LightSample::between(point, self.position, intensity)
+            .into_iter()
+            .collect()
+    }
+}
+
+// ==========================================================================
+// DirectionalLight
+
+/// Parallel light from very far away, like the sun. It never fades and its
+/// shadow rays never end.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    direction: Vector3D,
+    intensity: Spectrum,
+}
+
+impl DirectionalLight {
+    /// A light shining along `direction`
+    pub fn new<I: Into<Spectrum>>(direction: Vector3D, intensity: I) -> Self {
+        Self {
+            direction: direction.normalize(),
+            intensity: intensity.into(),
+        }
+    }
+
+    pub fn direction(&self) -> Vector3D {
+        self.direction
+    }
+
+    pub fn intensity(&self) -> Spectrum {
+        self.intensity
+    }
+}
+
+impl Light for DirectionalLight {
+    fn samples(&self, _: Point3D, _: &mut Rng) -> Vec<LightSample> {
+        vec![LightSample {
+            direction: -self.direction,
+            distance: Unit3D::INFINITY,
+            intensity: self.intensity,
+        }]
+    }
+}
+
+// ==========================================================================
+// SpotLight
+
+/// A point light shining only into a cone. Points inside the inner angle
+/// get the full intensity, which fades smoothly to nothing at the outer
+/// angle. Like a `PointLight` it doesn't fade with distance unless given an
+/// `Attenuation`.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SpotLight {
+    position: Point3D,
+    direction: Vector3D,
+    inner: f32,
+    outer: f32,
+    intensity: Spectrum,
+    attenuation: Attenuation,
+}
+
+impl SpotLight {
+    /// A spot light at `position` shining along `direction`, with the cone
+    /// angles in radians from its center
+    pub fn new<I: Into<Spectrum>>(
+        position: Point3D,
+        direction: Vector3D,
+        inner: f32,
+        outer: f32,
+        intensity: I,
+    ) -> Self {
+        if !(0.0 <= inner && inner <= outer && outer <= PI) {
+            panic!(
+                "spot lights need 0 <= inner <= outer <= pi! {} {}",
+                inner, outer
+            );
+        }
+        Self {
+            position,
+            direction: direction.normalize(),
+            inner,
+            outer,
+            intensity: intensity.into(),
+            attenuation: Attenuation::None,
+        }
+    }
+
+    pub fn position(&self) -> Point3D {
+        self.position
+    }
+
+    pub fn direction(&self) -> Vector3D {
+        self.direction
+    }
+
+    pub fn inner(&self) -> f32 {
+        self.inner
+    }
+
+    pub fn outer(&self) -> f32 {
+        self.outer
+    }
+
+    pub fn intensity(&self) -> Spectrum {
+        self.intensity
+    }
+
+    pub fn attenuation(&self) -> Attenuation {
+        self.attenuation
+    }
+
+    pub fn set_attenuation(&mut self, attenuation: Attenuation) {
+        self.attenuation = attenuation;
+    }
+
+    /// How much of the light's intensity shines towards `point`, from 1
+    /// inside the inner cone to 0 outside the outer one
+    pub fn cone(&self, point: Point3D) -> f32 {
+        let offset = point - self.position;
+        let distance = offset.magnitude();
+        if distance == 0.0 {
+            return 1.0;
+        }
+        let cos_angle = (offset / distance).dot(self.direction);
+        let (cos_inner, cos_outer) = (self.inner.cos(), self.outer.cos());
+        if cos_angle >= cos_inner {
+            1.0
+        } else if cos_angle <= cos_outer {
+            0.0
+        } else {
+            let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+impl Light for SpotLight {
+    fn samples(&self, point: Point3D, _: &mut Rng) -> Vec<LightSample> {
+        let distance = (self.position - point).magnitude();
+        let intensity = self.intensity * (self.cone(point) * self.attenuation.factor(distance));
+        LightSample::between(point, self.position, intensity)
+            .into_iter()
+            .collect()
+    }
+}
+
+// ==========================================================================
+// AreaLight
+
+/// The shape of an area light
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AreaShape {
+    /// The parallelogram spanned by two edges from a corner
+    Rectangle {
+        corner: Point3D,
+        u: Vector3D,
+        v: Vector3D,
+    },
+    /// The surface of a sphere
+    Sphere { center: Point3D, radius: Unit3D },
+}
+
+/// A light with a size, which casts soft shadows
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct AreaLight {
+    shape: AreaShape,
+    intensity: Spectrum,
+    usteps: usize,
+    vsteps: usize,
+    attenuation: Attenuation,
+    strength: f32,
+}
+
+impl AreaLight {
+    /// A rectangular light with `corner` at one corner and the edges `u` and
+    /// `v`, sampled once in each of `usteps` by `vsteps` cells
+    pub fn rectangle<I: Into<Spectrum>>(
+        corner: Point3D,
+        u: Vector3D,
+        usteps: usize,
+        v: Vector3D,
+        vsteps: usize,
+        intensity: I,
+    ) -> Self {
+        Self::new(
+            AreaShape::Rectangle { corner, u, v },
+            usteps,
+            vsteps,
+            intensity.into(),
+        )
+    }
+
+    /// A spherical light, sampled once in each of `usteps` bands of latitude
+    /// by `vsteps` of longitude
+    pub fn sphere<I: Into<Spectrum>>(
+        center: Point3D,
+        radius: Unit3D,
+        usteps: usize,
+        vsteps: usize,
+        intensity: I,
+    ) -> Self {
+        Self::new(
+            AreaShape::Sphere { center, radius },
+            usteps,
+            vsteps,
+            intensity.into(),
+        )
+    }
+
+    fn new(shape: AreaShape, usteps: usize, vsteps: usize, intensity: Spectrum) -> Self {
+        if usteps == 0 || vsteps == 0 {
+            panic!("area lights need at least one sample in each direction!");
+        }
+        Self {
+            shape,
+            intensity,
+            usteps,
+            vsteps,
+            attenuation: Attenuation::None,
+            strength: 1.0,
+        }
+    }
+
+    pub fn shape(&self) -> AreaShape {
+        self.shape
+    }
+
+    pub fn intensity(&self) -> Spectrum {
+        self.intensity
+    }
+
+    pub fn attenuation(&self) -> Attenuation {
+        self.attenuation
+    }
+
+    pub fn set_attenuation(&mut self, attenuation: Attenuation) {
+        self.attenuation = attenuation;
+    }
+
+    /// The number of points sampled on the light
+    pub fn sample_count(&self) -> usize {
+        self.usteps * self.vsteps
+    }
+
+    /// The middle of the light
+    pub fn center(&self) -> Point3D {
+        match self.shape {
+            AreaShape::Rectangle { .. } => self.point_on(0.5, 0.5),
+            AreaShape::Sphere { center, .. } => center,
+        }
+    }
+
+    /// The point at `u`, `v` in [0, 1] across the light
+    pub fn point_on(&self, u: Unit3D, v: Unit3D) -> Point3D {
+        match self.shape {
+            AreaShape::Rectangle {
+                corner,
+                u: edge_u,
+                v: edge_v,
+            } => corner + edge_u * u + edge_v * v,
+            AreaShape::Sphere { center, radius } => {
+                let z = 1.0 - 2.0 * u;
+                let ring = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * v;
+                center + Vector3D::new(ring * phi.cos(), ring * phi.sin(), z) * radius
+            }
+        }
+    }
+
+    /// One jittered point in every cell of the light
+    pub fn sample_points(&self, rng: &mut Rng) -> Vec<Point3D> {
+        let mut points = Vec::with_capacity(self.sample_count());
+        for v in 0..self.vsteps {
+            for u in 0..self.usteps {
+                let su = (u as Unit3D + rng.next_f32()) / self.usteps as Unit3D;
+                let sv = (v as Unit3D + rng.next_f32()) / self.vsteps as Unit3D;
+                points.push(self.point_on(su, sv));
+            }
+        }
+        points
+    }
+
+    /// How many times its intensity the light gives off when it is treated
+    /// as a glowing surface
+    pub fn strength(&self) -> f32 {
+        self.strength
+    }
+
+    pub fn set_strength(&mut self, strength: f32) {
+        if strength < 0.0 {
+            panic!("a light can't have a negative strength! {}", strength);
+        }
+        self.strength = strength;
+    }
+
+    /// The light given off by every point of the surface, in every
+    /// direction it faces
+    pub fn radiance(&self) -> Spectrum {
+        self.intensity * self.strength
+    }
+
+    /// The area of the surface
+    pub fn area(&self) -> Unit3D {
+        match self.shape {
+            AreaShape::Rectangle { u, v, .. } => u.cross(v).magnitude(),
+            AreaShape::Sphere { radius, .. } => 4.0 * PI * radius * radius,
+        }
+    }
+
+    /// The normal of the surface at `point` on it. Rectangles shine from
+    /// both sides, so either way their normal faces is as good.
+    pub fn normal_at(&self, point: Point3D) -> Vector3D {
+        match self.shape {
+            AreaShape::Rectangle { u, v, .. } => u.cross(v).normalize(),
+            AreaShape::Sphere { center, .. } => (point - center).normalize(),
+        }
+    }
+
+    /// The distance along `ray` to the nearest point where it meets the
+    /// light in front of its origin, if it does
+    pub fn intersect(&self, ray: &Ray) -> Option<Unit3D> {
+        match self.shape {
+            AreaShape::Rectangle { corner, u, v } => {
+                let normal = u.cross(v);
+                let facing = ray.direction.dot(normal);
+                if facing.abs() < EPSILON {
+                    return None;
+                }
+                let t = (corner - ray.origin).dot(normal) / facing;
+                // Where the ray meets the plane, in multiples of the edges
+                let local = ray.position(t) - corner;
+                let area = normal.dot(normal);
+                let a = local.cross(v).dot(normal) / area;
+                let b = u.cross(local).dot(normal) / area;
+                if t > EPSILON && (0.0..=1.0).contains(&a) && (0.0..=1.0).contains(&b) {
+                    Some(t)
+                } else {
+                    None
+                }
+            }
+            AreaShape::Sphere { center, radius } => shape::sphere_crossings(ray, center, radius)
+                .into_iter()
+                .map(|crossing| crossing.t)
+                .find(|&t| t > EPSILON),
+        }
+    }
+}
+
+impl Light for AreaLight {
+    fn samples(&self, point: Point3D, rng: &mut Rng) -> Vec<LightSample> {
+        self.sample_points(rng)
+            .into_iter()
+            .filter_map(|light_point| {
+                let distance = (light_point - point).magnitude();
+                let intensity = self.intensity * self.attenuation.factor(distance);
+                LightSample::between(point, light_point, intensity)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::units::Tuple;
+
+    fn white() -> Color {
+        Color::rgb(1.0, 1.0, 1.0)
+    }
+
+    fn square() -> AreaLight {
+        AreaLight::rectangle(
+            Point3D::new(-1.0, 5.0, -1.0),
+            Vector3D::new(2.0, 0.0, 0.0),
+            4,
+            Vector3D::new(0.0, 0.0, 2.0),
+            4,
+            white(),
+        )
+    }
+
+    /// A wall at x = 0 blocking every shadow ray which crosses it
+    fn wall(point: Point3D, sample: &LightSample) -> bool {
+        let t = -point.x() / sample.direction.x();
+        t > 0.0 && t < sample.distance
+    }
+
+    #[test]
+    fn test_rectangle_samples_stay_in_their_cells() {
+        let light = square();
+        assert_eq!(16, light.sample_count());
+        assert_eq!(Point3D::new(0.0, 5.0, 0.0), light.center());
+        let points = light.sample_points(&mut Rng::new(3));
+        assert_eq!(16, points.len());
+        for (i, point) in points.iter().enumerate() {
+            let (u, v) = ((i % 4) as f32, (i / 4) as f32);
+            assert!(point.x() >= -1.0 + u * 0.5 && point.x() <= -0.5 + u * 0.5);
+            assert!(point.z() >= -1.0 + v * 0.5 && point.z() <= -0.5 + v * 0.5);
+            assert_eq!(5.0, point.y());
+        }
+    }
+
+    #[test]
+    fn test_sphere_samples_are_on_the_surface() {
+        let light = AreaLight::sphere(Point3D::new(1.0, 2.0, 3.0), 0.5, 3, 5, white());
+        let center = Point3D::new(1.0, 2.0, 3.0);
+        assert_eq!(center, light.center());
+        for point in light.sample_points(&mut Rng::new(9)) {
+            assert!(((point - center).magnitude() - 0.5).abs() < EPSILON * 10.0);
+        }
+    }
+
+    #[test]
+    fn test_rays_hit_area_lights() {
+        let light = square();
+        assert_eq!(4.0, light.area());
+        let down = Vector3D::new(0.0, -1.0, 0.0);
+        let up = Ray::new(Point3D::new(0.5, 0.0, 0.5), Vector3D::new(0.0, 1.0, 0.0));
+        assert_eq!(Some(5.0), light.intersect(&up));
+        assert_eq!(None, light.intersect(&Ray::new(up.origin, down)));
+        let past = Ray::new(Point3D::new(1.5, 0.0, 0.0), Vector3D::new(0.0, 1.0, 0.0));
+        assert_eq!(None, light.intersect(&past));
+        assert_eq!(1.0, light.normal_at(light.center()).dot(down).abs());
+
+        let mut ball = AreaLight::sphere(Point3D::new(0.0, 0.0, 5.0), 1.0, 1, 1, white());
+        ball.set_strength(4.0);
+        assert_eq!(Spectrum::gray(4.0), ball.radiance());
+        let ahead = Ray::new(Point3D::new(0.0, 0.0, 0.0), Vector3D::new(0.0, 0.0, 1.0));
+        assert_eq!(Some(4.0), ball.intersect(&ahead));
+        // From inside the light only the far side is in front
+        let inside = Ray::new(Point3D::new(0.0, 0.0, 5.0), Vector3D::new(0.0, 0.0, 1.0));
+        assert_eq!(Some(1.0), ball.intersect(&inside));
+        assert_eq!(
+            Vector3D::new(0.0, 0.0, 1.0),
+            ball.normal_at(Point3D::new(0.0, 0.0, 6.0))
+        );
+    }
+
+    #[test]
+    fn test_shadows_are_fractional() {
+        let light = square();
+        let mut rng = Rng::new(1);
+        let origin = Point3D::new(0.0, 0.0, 0.0);
+        assert_eq!(1.0, light.intensity_at(origin, &mut rng, &|_, _| false));
+        assert_eq!(0.0, light.intensity_at(origin, &mut rng, &|_, _| true));
+        // Half the light is on each side of the wall
+        let beside = Point3D::new(-0.5, 0.0, 0.0);
+        assert_eq!(0.5, light.intensity_at(beside, &mut rng, &wall));
+    }
+
+    #[test]
+    fn test_point_lights_cast_hard_shadows() {
+        let light = PointLight::new(Point3D::new(0.5, 5.0, 0.0), white());
+        let mut rng = Rng::new(1);
+        let left = Point3D::new(-0.5, 0.0, 0.0);
+        let right = Point3D::new(0.5, 0.0, 0.0);
+        assert_eq!(0.0, light.intensity_at(left, &mut rng, &wall));
+        assert_eq!(1.0, light.intensity_at(right, &mut rng, &wall));
+    }
+
+    #[test]
+    fn test_lighting_in_a_penumbra() {
+        let light = square();
+        let up = Vector3D::new(0.0, 1.0, 0.0);
+        let point = Point3D::new(0.0, 0.0, 0.0);
+        let beside = Point3D::new(-0.5, 0.0, 0.0);
+        let lit = light.lighting(point, up, &mut Rng::new(4), &|_, _| false);
+        let half = light.lighting(beside, up, &mut Rng::new(4), &wall);
+        let facing_away = light.lighting(point, -up, &mut Rng::new(4), &|_, _| false);
+        assert!(lit.r() > 0.9 && lit.r() < 1.0);
+        assert!(half.r() > 0.4 && half.r() < 0.55);
+        assert!(facing_away.is_black());
+
+        // Lights work as trait objects too
+        let boxed: Box<dyn Light> = Box::new(light);
+        assert_eq!(
+            1.0,
+            boxed.intensity_at(point, &mut Rng::new(4), &|_, _| false)
+        );
+    }
+
+    #[test]
+    fn test_intensities_are_not_clamped() {
+        let bright = PointLight::new(Point3D::new(0.0, 2.0, 0.0), Spectrum::gray(40.0));
+        assert_eq!(Spectrum::gray(40.0), bright.intensity());
+        let up = Vector3D::new(0.0, 1.0, 0.0);
+        let origin = Point3D::new(0.0, 0.0, 0.0);
+        let lit = bright.lighting(origin, up, &mut Rng::new(0), &|_, _| false);
+        assert_eq!(Spectrum::gray(40.0), lit);
+    }
+
+    #[test]
+    fn test_attenuation() {
+        assert_eq!(1.0, Attenuation::None.factor(100.0));
+        assert_eq!(0.25, Attenuation::InverseSquare.factor(2.0));
+        assert_eq!(1.0, Attenuation::InverseSquare.factor(0.5));
+        let custom = Attenuation::Custom {
+            constant: 1.0,
+            linear: 0.5,
+            quadratic: 0.0,
+        };
+        assert_eq!(0.5, custom.factor(2.0));
+
+        let mut light = PointLight::new(Point3D::new(0.0, 4.0, 0.0), white());
+        light.set_attenuation(Attenuation::InverseSquare);
+        let samples = light.samples(Point3D::new(0.0, 0.0, 0.0), &mut Rng::new(0));
+        assert_eq!(4.0, samples[0].distance);
+        assert_eq!(Vector3D::new(0.0, 1.0, 0.0), samples[0].direction);
+        assert_eq!(Spectrum::gray(0.0625), samples[0].intensity);
+    }
+
+    #[test]
+    fn test_directional_lights_never_end() {
+        let light = DirectionalLight::new(Vector3D::new(0.0, -2.0, 0.0), white());
+        let samples = light.samples(Point3D::new(5.0, -100.0, 3.0), &mut Rng::new(0));
+        assert_eq!(1, samples.len());
+        assert_eq!(Vector3D::new(0.0, 1.0, 0.0), samples[0].direction);
+        assert_eq!(Unit3D::INFINITY, samples[0].distance);
+        assert_eq!(Spectrum::gray(1.0), samples[0].intensity);
+
+        // A wall however far away still casts a shadow
+        let slanted = DirectionalLight::new(Vector3D::new(1.0, -1.0, 0.0), white());
+        let far = Point3D::new(1e6, 0.0, 0.0);
+        assert_eq!(0.0, slanted.intensity_at(far, &mut Rng::new(0), &wall));
+    }
+
+    #[test]
+    fn test_spot_light_cones() {
+        let light = SpotLight::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Vector3D::new(0.0, 0.0, 1.0),
+            PI / 8.0,
+            PI / 4.0,
+            white(),
+        );
+        assert_eq!(Attenuation::None, light.attenuation());
+        assert_eq!(1.0, light.cone(Point3D::new(0.0, 0.0, 10.0)));
+        assert_eq!(0.0, light.cone(Point3D::new(0.0, 10.0, 1.0)));
+        assert_eq!(0.0, light.cone(Point3D::new(0.0, 0.0, -10.0)));
+        let edge = light.cone(Point3D::new(0.0, (PI * 3.0 / 16.0).tan(), 1.0));
+        assert!(edge > 0.1 && edge < 0.9);
+
+        let facing = Vector3D::new(0.0, 0.0, -1.0);
+        let mut rng = Rng::new(0);
+        let inside = Point3D::new(0.0, 0.0, 2.0);
+        let outside = Point3D::new(5.0, 0.0, 2.0);
+        let lit = light.lighting(inside, facing, &mut rng, &|_, _| false);
+        let unlit = light.lighting(outside, facing, &mut rng, &|_, _| false);
+        assert_eq!(Spectrum::gray(1.0), lit);
+        assert!(unlit.is_black());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_spot_light_cones_must_nest() {
+        SpotLight::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Vector3D::new(0.0, 0.0, 1.0),
+            0.5,
+            0.25,
+            white(),
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_area_lights_need_samples() {
+        AreaLight::sphere(Point3D::new(0.0, 0.0, 0.0), 1.0, 0, 4, white());
+    }
+}
@@ -0,0 +1,1419 @@
+//! Building something to render out of a parsed `Scene`. `Stage::load`
+//! reads the camera, lights and objects of a scene into a `Stage`, a `World`
+//! the integrators can render as its camera sees it.
+//!
+//! Objects are `sphere`s, `cube`s and `plane`s, each a unit shape placed by
+//! its `transform`, `group`s of `children` moved together and `csg`s
+//! combining a `left` and a `right` operand, each of which may be any
+//! object. Objects are made of their `material`, or that of the group they
+//! are in.
+//!
+//! ```text
+//! - add: group
+//!   transform:
+//!     - [translate, 0, 1, 0]
+//!   material:
+//!     type: principled
+//!     color: [0.8, 0.2, 0.1]
+//!     roughness: 0.3
+//!   children:
+//!     - add: sphere
+//!     - add: csg
+//!       operation: difference
+//!       left:
+//!         add: cube
+//!       right:
+//!         add: sphere
+//!         transform:
+//!           - [scale, 1.3, 1.3, 1.3]
+//! ```
+//!
+//! Rather than one `color`, `matte` and `principled` materials can be
+//! painted in a `pattern` of two `colors`: `stripes`, `rings` or `checkers`,
+//! each one unit across unless the pattern's `transform` says otherwise.
+//!
+//! ```text
+//! - add: plane
+//!   material:
+//!     pattern:
+//!       type: checkers
+//!       colors: [[1, 1, 1], [0.1, 0.1, 0.1]]
+//!       transform:
+//!         - [scale, 2, 2, 2]
+//! ```
+//!
+//! The camera also picks the `integrator` rendering the scene, `whitted`
+//! unless it asks for `path` tracing.
+//!
+//! Lights are `point-light`s, `directional-light`s, `spot-light`s and
+//! `area-light`s. Unknown items and keys are errors reported where they were
+//! written, so a misspelt key doesn't silently do nothing.
+//!
+//! Scenes are loaded as they are at a moment, so they can be animated. Any
+//! item or material can list `keyframes`, each changing some of its
+//! numbers, points, vectors, colors or transforms `at` a time in seconds,
+//! eased into the next keyframe by its `easing`.
+//!
+//! ```text
+//! - add: point-light
+//!   position: [0, 10, 0]
+//!   intensity: [1, 1, 1]
+//!   keyframes:
+//!     - at: 2
+//!       intensity: [8, 6, 4]
+//!       easing: [bezier, 0.2, 0, 0.4, 1]
+//!     - at: 3
+//!       position: [5, 10, 0]
+//! ```
+//!
+//! A camera's `shutter` opens and closes at times after each frame. Objects
+//! whose `transform` changes meanwhile are blurred along the way they move,
+//! while everything else stays as it is at the frame.
+
+use crate::animation::{Animate, Easing, Key, Track};
+use crate::bsdf::{Conductor, Dielectric, Lambert, Principled};
+use crate::camera::Camera;
+use crate::color::{Color, Spectrum};
+use crate::csg::{Csg, Operation};
+use crate::group::{NodeId, SceneGraph};
+use crate::integrator::{Material, Method, Surface, World};
+use crate::light::{AreaLight, Attenuation, DirectionalLight, Light, PointLight, SpotLight};
+use crate::matrix::Matrix;
+use crate::motion::{Motion, Moving};
+use crate::pattern::{Kind, Pattern};
+use crate::ray::Ray;
+use crate::scene::{Position, Scene, SceneError, Value};
+use crate::shape::{Cube, Plane, Shape, Sphere};
+use crate::units::{Point3D, Tuple, Unit3D, Vector3D};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+const CAMERA_KEYS: &[&str] = &[
+    "width",
+    "height",
+    "field-of-view",
+    "from",
+    "to",
+    "up",
+    "aperture",
+    "focal-distance",
+    "shutter",
+    "background",
+    "background-top",
+    "integrator",
+];
+
+/// A scene ready to render: its camera and everything the camera sees
+pub struct Stage {
+    camera: Camera,
+    graph: SceneGraph,
+    /// What every object in `graph` is made of
+    materials: HashMap<NodeId, Finish>,
+    lights: Vec<Box<dyn Light + Send + Sync>>,
+    area_lights: Vec<AreaLight>,
+    /// The background at the horizon and straight up
+    background: (Spectrum, Spectrum),
+    method: Method,
+}
+
+impl Stage {
+    /// Read every item of `scene` as it is at `time`. It needs exactly one
+    /// camera.
+    pub fn load(scene: &Scene, time: Unit3D) -> Result<Self, SceneError> {
+        let mut cameras = scene.items_of("camera");
+        let camera = cameras
+            .next()
+            .ok_or_else(|| SceneError::new(Position::new(1, 1), "the scene has no camera"))?;
+        if let Some(extra) = cameras.next() {
+            return Err(SceneError::new(
+                extra.position(),
+                "the scene has more than one camera",
+            ));
+        }
+        let keys = Keys::new("camera", camera.properties(), CAMERA_KEYS, time)?;
+        let (camera, background) = load_camera(&keys)?;
+        let method = match keys.fixed("integrator") {
+            Some(value) => Method::from_name(value.as_text()?)
+                .ok_or_else(|| SceneError::new(value.position(), "expected `whitted` or `path`"))?,
+            None => Method::Whitted,
+        };
+
+        let exposure = Exposure {
+            time,
+            shutter: camera.shutter(),
+        };
+        let mut graph = SceneGraph::new();
+        let mut placed = Vec::new();
+        let mut lights: Vec<Box<dyn Light + Send + Sync>> = Vec::new();
+        let mut area_lights = Vec::new();
+        let default_material = Finish::Plain(Material::matte(Color::rgb(0.8, 0.8, 0.8)));
+        for item in scene.items() {
+            let properties = item.properties();
+            match item.kind() {
+                "camera" => {}
+                "point-light" => lights.push(Box::new(point_light(properties, time)?)),
+                "directional-light" => lights.push(Box::new(directional_light(properties, time)?)),
+                "spot-light" => lights.push(Box::new(spot_light(properties, time)?)),
+                "area-light" => area_lights.push(area_light(properties, time)?),
+                kind => {
+                    let root = graph.root();
+                    let material = Some(&default_material);
+                    let item = (kind, properties);
+                    place(
+                        &mut graph,
+                        root,
+                        item,
+                        material,
+                        &mut placed,
+                        &exposure,
+                        &[],
+                    )?;
+                }
+            }
+        }
+        Ok(Self {
+            camera,
+            graph,
+            materials: placed.into_iter().collect(),
+            lights,
+            area_lights,
+            background,
+            method,
+        })
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn graph(&self) -> &SceneGraph {
+        &self.graph
+    }
+
+    /// How the scene asks to be rendered
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    /// The material at `point` on the object `node`, hit by a ray cast at
+    /// `time`, `None` for groups
+    pub fn material(&self, node: NodeId, point: Point3D, time: Unit3D) -> Option<&Material> {
+        let finish = self.materials.get(&node)?;
+        Some(finish.at(&self.graph, node, point, time))
+    }
+}
+
+impl World for Stage {
+    fn intersect(&self, ray: &Ray) -> Option<Surface<'_>> {
+        let hit = self.graph.intersect(ray)?;
+        Some(Surface {
+            distance: hit.distance,
+            point: hit.point,
+            normal: hit.normal,
+            material: self.materials[&hit.node].at(&self.graph, hit.node, hit.point, ray.time),
+        })
+    }
+
+    fn lights(&self) -> &[Box<dyn Light + Send + Sync>] {
+        &self.lights
+    }
+
+    fn area_lights(&self) -> &[AreaLight] {
+        &self.area_lights
+    }
+
+    /// The `background` at the horizon and below, blended towards
+    /// `background-top` straight up
+    fn background(&self, direction: Vector3D) -> Spectrum {
+        let (horizon, top) = self.background;
+        let up = direction.normalize().y().max(0.0);
+        horizon * (1.0 - up) + top * up
+    }
+}
+
+// ==========================================================================
+// Keys
+
+/// The properties of an item at one moment, checked against the keys its
+/// kind takes. Numbers, points, vectors, colors and transforms can change
+/// over time, listed in `keyframes` each with the time it is reached `at`
+/// and the `easing` on to the next keyframe.
+struct Keys<'a> {
+    kind: &'a str,
+    properties: &'a Value,
+    /// The time, easing and properties of every keyframe
+    keyframes: Vec<(Unit3D, Easing, &'a Value)>,
+    time: Unit3D,
+}
+
+impl<'a> Keys<'a> {
+    /// Check that `properties` and its keyframes only have keys in `known`,
+    /// besides the `add` of nested items
+    fn new(
+        kind: &'a str,
+        properties: &'a Value,
+        known: &[&str],
+        time: Unit3D,
+    ) -> Result<Self, SceneError> {
+        let unknown = |key: &str, value: &Value| {
+            SceneError::new(
+                value.position(),
+                format!("`{}` doesn't take `{}`", kind, key),
+            )
+        };
+        for (key, value) in properties.as_map()? {
+            if key != "add" && key != "keyframes" && !known.contains(&key.as_str()) {
+                return Err(unknown(key, value));
+            }
+        }
+
+        let mut keyframes = Vec::new();
+        if let Some(list) = properties.get("keyframes") {
+            for keyframe in list.as_list()? {
+                for (key, value) in keyframe.as_map()? {
+                    if key != "at" && key != "easing" && !known.contains(&key.as_str()) {
+                        return Err(unknown(key, value));
+                    }
+                }
+                let at = keyframe.get("at").ok_or_else(|| {
+                    SceneError::new(keyframe.position(), "keyframes need an `at` time")
+                })?;
+                let easing = match keyframe.get("easing") {
+                    Some(easing) => load_easing(easing)?,
+                    None => Easing::Linear,
+                };
+                keyframes.push((at.as_number()?, easing, keyframe));
+            }
+        }
+        Ok(Self {
+            kind,
+            properties,
+            keyframes,
+            time,
+        })
+    }
+
+    /// The value of `key` at the time, read by `read` from the properties
+    /// and any keyframes, or `None` if none of them have it
+    fn get<T, F>(&self, key: &str, read: F) -> Result<Option<T>, SceneError>
+    where
+        T: Animate,
+        F: Fn(&Value) -> Result<T, SceneError>,
+    {
+        let mut track = match self.properties.get(key) {
+            Some(value) => Some(Track::new(read(value)?)),
+            None => None,
+        };
+        for &(at, easing, keyframe) in &self.keyframes {
+            if let Some(value) = keyframe.get(key) {
+                let value = read(value)?;
+                match &mut track {
+                    Some(track) => track.add(at, value, easing),
+                    None => track = Some(Track::from_keys(vec![Key::new(at, value, easing)])),
+                }
+            }
+        }
+        Ok(track.map(|track| track.at(self.time)))
+    }
+
+    /// The value of `key` at the time, or an error if the item doesn't
+    /// have it
+    fn require<T, F>(&self, key: &str, read: F) -> Result<T, SceneError>
+    where
+        T: Animate,
+        F: Fn(&Value) -> Result<T, SceneError>,
+    {
+        self.get(key, read)?.ok_or_else(|| self.missing(key))
+    }
+
+    /// The value of a key which can't change over time
+    fn fixed(&self, key: &str) -> Option<&'a Value> {
+        self.properties.get(key)
+    }
+
+    fn require_fixed(&self, key: &str) -> Result<&'a Value, SceneError> {
+        self.fixed(key).ok_or_else(|| self.missing(key))
+    }
+
+    /// Where `key` is written, in the properties or else in the first
+    /// keyframe changing it, to report what is wrong with it
+    fn position_of(&self, key: &str) -> Position {
+        let keyframes = self.keyframes.iter().map(|&(_, _, keyframe)| keyframe);
+        match std::iter::once(self.properties)
+            .chain(keyframes)
+            .find_map(|properties| properties.get(key))
+        {
+            Some(value) => value.position(),
+            None => self.properties.position(),
+        }
+    }
+
+    /// An error with `key`, saying when it goes wrong if it changes over
+    /// time
+    fn invalid(&self, key: &str, message: &str) -> SceneError {
+        if !self.is_animated(key) {
+            return SceneError::new(self.position_of(key), message);
+        }
+        // The last keyframe changing `key` by the time it goes wrong
+        let reached = self
+            .keyframes
+            .iter()
+            .filter(|&&(at, _, keyframe)| at <= self.time && keyframe.get(key).is_some())
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .and_then(|(_, _, keyframe)| keyframe.get(key));
+        let position = match reached {
+            Some(value) => value.position(),
+            None => self.position_of(key),
+        };
+        SceneError::new(position, format!("{} at {}s", message, self.time))
+    }
+
+    /// Whether any keyframe changes `key`
+    fn is_animated(&self, key: &str) -> bool {
+        self.keyframes
+            .iter()
+            .any(|(_, _, keyframe)| keyframe.get(key).is_some())
+    }
+
+    fn missing(&self, key: &str) -> SceneError {
+        SceneError::new(
+            self.properties.position(),
+            format!("`{}` needs a `{}`", self.kind, key),
+        )
+    }
+
+    /// The number `key`, or `default` if it isn't given. It must be at
+    /// least `min`.
+    fn number(&self, key: &str, default: Unit3D, min: Unit3D) -> Result<Unit3D, SceneError> {
+        let number = self.get(key, |value| {
+            let number = value.as_number()?;
+            if number < min {
+                return Err(SceneError::new(
+                    value.position(),
+                    format!("`{}` can't be less than {}", key, min),
+                ));
+            }
+            Ok(number)
+        })?;
+        Ok(number.unwrap_or(default))
+    }
+
+    /// The whole number `key`, or `default` if it isn't given
+    fn count(&self, key: &str, default: usize) -> Result<usize, SceneError> {
+        match self.fixed(key) {
+            Some(value) => match value.as_number()? {
+                n if n >= 1.0 && n.fract() == 0.0 => Ok(n as usize),
+                _ => Err(SceneError::new(
+                    value.position(),
+                    format!("`{}` must be a whole number above 0", key),
+                )),
+            },
+            None => Ok(default),
+        }
+    }
+
+    /// The `transform`, which must not flatten the object, as objects are
+    /// found by undoing it
+    fn transform(&self) -> Result<Matrix, SceneError> {
+        self.transform_at(self.time)
+    }
+
+    /// The `transform` at `time` rather than the time the item is read at
+    fn transform_at(&self, time: Unit3D) -> Result<Matrix, SceneError> {
+        let keys = Keys {
+            keyframes: self.keyframes.clone(),
+            time,
+            ..*self
+        };
+        let transform = keys.get("transform", Value::to_transform)?;
+        let transform = transform.unwrap_or_else(Matrix::identity);
+        if !transform.is_invertible() {
+            return Err(keys.invalid(
+                "transform",
+                "`transform` can't flatten an object to nothing",
+            ));
+        }
+        Ok(transform)
+    }
+
+    /// `none`, `inverse-square` or a list of the constant, linear and
+    /// quadratic terms, no attenuation if not given
+    fn attenuation(&self) -> Result<Attenuation, SceneError> {
+        let value = match self.fixed("attenuation") {
+            Some(value) => value,
+            None => return Ok(Attenuation::None),
+        };
+        if let Value::List(..) = value {
+            let terms = value.to_vector()?;
+            return Ok(Attenuation::Custom {
+                constant: terms.x(),
+                linear: terms.y(),
+                quadratic: terms.z(),
+            });
+        }
+        match value.as_text()? {
+            "none" => Ok(Attenuation::None),
+            "inverse-square" => Ok(Attenuation::InverseSquare),
+            name => Err(SceneError::new(
+                value.position(),
+                format!("unknown attenuation `{}`", name),
+            )),
+        }
+    }
+}
+
+/// An easing by name, or a curve written `[bezier, x1, y1, x2, y2]`
+fn load_easing(value: &Value) -> Result<Easing, SceneError> {
+    if let Value::List(items, _) = value {
+        return match &items[..] {
+            [name, x1, y1, x2, y2] if name.as_text()? == "bezier" => Ok(Easing::Bezier(
+                x1.as_number()?,
+                y1.as_number()?,
+                x2.as_number()?,
+                y2.as_number()?,
+            )),
+            _ => Err(SceneError::new(
+                value.position(),
+                "expected `[bezier, x1, y1, x2, y2]`",
+            )),
+        };
+    }
+    Easing::from_name(value.as_text()?)
+        .ok_or_else(|| SceneError::new(value.position(), "unknown easing"))
+}
+
+// ==========================================================================
+// Camera
+
+fn load_camera(keys: &Keys) -> Result<(Camera, (Spectrum, Spectrum)), SceneError> {
+    let size = |key| -> Result<usize, SceneError> {
+        keys.require_fixed(key)?;
+        keys.count(key, 1)
+    };
+    let mut camera = Camera::new(
+        size("width")?,
+        size("height")?,
+        keys.number("field-of-view", PI / 3.0, 0.0)?,
+    );
+    let from = keys.require("from", Value::to_point)?;
+    let to = keys.require("to", Value::to_point)?;
+    let up = keys.get("up", Value::to_vector)?;
+    let up = up.unwrap_or_else(|| Vector3D::new(0.0, 1.0, 0.0));
+    let forward = to - from;
+    if forward.magnitude() == 0.0 {
+        let key = if keys.is_animated("from") {
+            "from"
+        } else {
+            "to"
+        };
+        return Err(keys.invalid(key, "the camera can't look `to` where it is `from`"));
+    }
+    let view = Matrix::view_transform(from, to, up);
+    if forward.cross(up).magnitude() == 0.0 || !view.is_invertible() {
+        return Err(keys.invalid("up", "`up` can't point along the way the camera looks"));
+    }
+    camera.set_transform(view);
+    camera.set_aperture(keys.number("aperture", 0.0, 0.0)?);
+    let focal_distance = keys.get("focal-distance", |value| match value.as_number()? {
+        distance if distance > 0.0 => Ok(distance),
+        _ => Err(SceneError::new(
+            value.position(),
+            "`focal-distance` must be above 0",
+        )),
+    })?;
+    if let Some(distance) = focal_distance {
+        camera.set_focal_distance(distance);
+    }
+    if let Some(value) = keys.fixed("shutter") {
+        match value.as_list()? {
+            [open, close] if open.as_number()? <= close.as_number()? => {
+                camera.set_shutter(open.as_number()?, close.as_number()?)
+            }
+            _ => {
+                return Err(SceneError::new(
+                    value.position(),
+                    "`shutter` must be the times it opens and closes",
+                ))
+            }
+        }
+    }
+
+    let horizon = keys.get("background", Value::to_spectrum)?;
+    let horizon = horizon.unwrap_or_else(Spectrum::black);
+    let top = keys.get("background-top", Value::to_spectrum)?;
+    Ok((camera, (horizon, top.unwrap_or(horizon))))
+}
+
+// ==========================================================================
+// Lights
+
+/// The light given off, which may be brighter than 1
+fn intensity(keys: &Keys) -> Result<Spectrum, SceneError> {
+    keys.require("intensity", Value::to_spectrum)
+}
+
+fn point_light(properties: &Value, time: Unit3D) -> Result<PointLight, SceneError> {
+    let keys = Keys::new(
+        "point-light",
+        properties,
+        &["position", "intensity", "attenuation"],
+        time,
+    )?;
+    let position = keys.require("position", Value::to_point)?;
+    let mut light = PointLight::new(position, intensity(&keys)?);
+    light.set_attenuation(keys.attenuation()?);
+    Ok(light)
+}
+
+fn directional_light(properties: &Value, time: Unit3D) -> Result<DirectionalLight, SceneError> {
+    let keys = Keys::new(
+        "directional-light",
+        properties,
+        &["direction", "intensity"],
+        time,
+    )?;
+    Ok(DirectionalLight::new(
+        keys.require("direction", Value::to_vector)?,
+        intensity(&keys)?,
+    ))
+}
+
+fn spot_light(properties: &Value, time: Unit3D) -> Result<SpotLight, SceneError> {
+    let keys = Keys::new(
+        "spot-light",
+        properties,
+        &[
+            "position",
+            "direction",
+            "inner",
+            "outer",
+            "intensity",
+            "attenuation",
+        ],
+        time,
+    )?;
+    let inner = keys.require("inner", Value::as_number)?;
+    let outer = keys.require("outer", Value::as_number)?;
+    if !(0.0 <= inner && inner <= outer && outer <= PI) {
+        return Err(SceneError::new(
+            properties.position(),
+            "spot lights need 0 <= inner <= outer <= pi",
+        ));
+    }
+    let mut light = SpotLight::new(
+        keys.require("position", Value::to_point)?,
+        keys.require("direction", Value::to_vector)?,
+        inner,
+        outer,
+        intensity(&keys)?,
+    );
+    light.set_attenuation(keys.attenuation()?);
+    Ok(light)
+}
+
+/// A rectangle from a `corner` along the edges `u` and `v`, or a sphere
+/// with a `center` and `radius`
+fn area_light(properties: &Value, time: Unit3D) -> Result<AreaLight, SceneError> {
+    let keys = Keys::new(
+        "area-light",
+        properties,
+        &[
+            "corner",
+            "u",
+            "v",
+            "center",
+            "radius",
+            "usteps",
+            "vsteps",
+            "intensity",
+            "strength",
+            "attenuation",
+        ],
+        time,
+    )?;
+    let (usteps, vsteps) = (keys.count("usteps", 4)?, keys.count("vsteps", 4)?);
+    let mut light = match keys.get("center", Value::to_point)? {
+        Some(center) => AreaLight::sphere(
+            center,
+            keys.require("radius", Value::as_number)?,
+            usteps,
+            vsteps,
+            intensity(&keys)?,
+        ),
+        None => AreaLight::rectangle(
+            keys.require("corner", Value::to_point)?,
+            keys.require("u", Value::to_vector)?,
+            usteps,
+            keys.require("v", Value::to_vector)?,
+            vsteps,
+            intensity(&keys)?,
+        ),
+    };
+    light.set_strength(keys.number("strength", 1.0, 0.0)?);
+    light.set_attenuation(keys.attenuation()?);
+    Ok(light)
+}
+
+// ==========================================================================
+// Materials
+
+/// What an object is made of
+#[derive(Debug, Clone)]
+enum Finish {
+    /// One material all over
+    Plain(Material),
+    /// A pattern of two materials, along with how the object it covers
+    /// moves while the shutter is open, so the pattern moves with it
+    Patterned(Pattern<Material>, Option<Motion>),
+}
+
+impl Finish {
+    /// The material at `point` on the object `node` of `graph`, hit by a
+    /// ray cast at `time`
+    fn at(&self, graph: &SceneGraph, node: NodeId, point: Point3D, time: Unit3D) -> &Material {
+        match self {
+            Finish::Plain(material) => material,
+            Finish::Patterned(pattern, motion) => {
+                let point = graph.world_to_object(node, point);
+                match motion {
+                    Some(motion) => pattern.at(motion.inverse_at(time) * point),
+                    None => pattern.at(point),
+                }
+            }
+        }
+    }
+}
+
+/// A material given as its `type`, `matte` if not given, and the settings
+/// of that type. Any material can glow with an `emission`. `matte` and
+/// `principled` materials are painted all over in their `color`, or in a
+/// `pattern` of two colors instead.
+fn load_material(value: &Value, time: Unit3D) -> Result<Finish, SceneError> {
+    let kind = match value.get("type") {
+        Some(kind) => kind.as_text()?,
+        None => "matte",
+    };
+    let (name, known): (&str, &[&str]) = match kind {
+        "matte" => (
+            "matte material",
+            &["type", "color", "pattern", "diffuse", "emission"],
+        ),
+        "principled" => (
+            "principled material",
+            &[
+                "type",
+                "color",
+                "pattern",
+                "metallic",
+                "roughness",
+                "specular",
+                "emission",
+            ],
+        ),
+        "metal" => (
+            "metal material",
+            &["type", "metal", "roughness", "emission"],
+        ),
+        "glass" => ("glass material", &["type", "ior", "roughness", "emission"]),
+        _ => {
+            let position = value.get("type").unwrap().position();
+            return Err(SceneError::new(
+                position,
+                format!("unknown material type `{}`", kind),
+            ));
+        }
+    };
+    let keys = Keys::new(name, value, known, time)?;
+    let roughness = |default| keys.number("roughness", default, 0.0);
+    let paint = |color: Color| -> Result<Material, SceneError> {
+        let mut material = match kind {
+            "matte" => Material::new(Lambert::new(color * keys.number("diffuse", 1.0, 0.0)?)),
+            "principled" => {
+                let mut principled =
+                    Principled::new(color, keys.number("metallic", 0.0, 0.0)?, roughness(0.5)?);
+                principled.specular = keys.number("specular", 0.5, 0.0)?;
+                Material::new(principled)
+            }
+            "metal" => {
+                let metal = keys.require_fixed("metal")?;
+                let roughness = roughness(0.0)?;
+                Material::new(match metal.as_text()? {
+                    "gold" => Conductor::gold(roughness),
+                    "silver" => Conductor::silver(roughness),
+                    "copper" => Conductor::copper(roughness),
+                    "aluminium" => Conductor::aluminium(roughness),
+                    name => {
+                        return Err(SceneError::new(
+                            metal.position(),
+                            format!("unknown metal `{}`", name),
+                        ))
+                    }
+                })
+            }
+            _ => Material::new(Dielectric::new(
+                keys.number("ior", 1.5, 1.0)?,
+                roughness(0.0)?,
+            )),
+        };
+        if let Some(emission) = keys.get("emission", Value::to_spectrum)? {
+            material.emission = emission;
+        }
+        Ok(material)
+    };
+
+    let pattern = match keys.fixed("pattern") {
+        Some(pattern) => pattern,
+        None => {
+            let color = keys.get("color", Value::to_color)?;
+            let color = color.unwrap_or_else(|| Color::rgb(0.8, 0.8, 0.8));
+            return Ok(Finish::Plain(paint(color)?));
+        }
+    };
+    if keys.fixed("color").is_some() || keys.is_animated("color") {
+        return Err(SceneError::new(
+            keys.position_of("color"),
+            "a material can't have both a `color` and a `pattern`",
+        ));
+    }
+    Ok(Finish::Patterned(load_pattern(pattern, time, paint)?, None))
+}
+
+/// A `stripes`, `rings` or `checkers` pattern of its two `colors`, each
+/// painted by `paint`, placed on objects by its `transform`
+fn load_pattern<T, F>(value: &Value, time: Unit3D, paint: F) -> Result<Pattern<T>, SceneError>
+where
+    F: Fn(Color) -> Result<T, SceneError>,
+{
+    let keys = Keys::new("pattern", value, &["type", "colors", "transform"], time)?;
+    let name = keys.require_fixed("type")?;
+    let kind = match Kind::from_name(name.as_text()?) {
+        Some(kind) => kind,
+        None => {
+            return Err(SceneError::new(
+                name.position(),
+                format!("unknown pattern type `{}`", name.as_text()?),
+            ))
+        }
+    };
+    let colors = keys.require_fixed("colors")?;
+    let (first, second) = match colors.as_list()? {
+        [first, second] => (first.to_color()?, second.to_color()?),
+        _ => {
+            return Err(SceneError::new(
+                colors.position(),
+                "`colors` must be the two colors of the pattern",
+            ))
+        }
+    };
+    let mut pattern = Pattern::new(kind, paint(first)?, paint(second)?);
+    pattern.set_transform(keys.transform()?);
+    Ok(pattern)
+}
+
+// ==========================================================================
+// Objects
+
+/// The keys objects of `kind` take, `None` if it isn't an object
+fn object_keys(kind: &str) -> Option<&'static [&'static str]> {
+    match kind {
+        "sphere" | "cube" | "plane" => Some(&["transform", "material"]),
+        "group" => Some(&["transform", "material", "children"]),
+        "csg" => Some(&["transform", "material", "operation", "left", "right"]),
+        _ => None,
+    }
+}
+
+/// How many steps across the shutter objects moving while it is open are
+/// placed at, moving smoothly from each to the next
+const SHUTTER_STEPS: usize = 8;
+
+/// When objects are seen: at the time of the frame, and for as long as the
+/// camera's shutter is open, which opens and closes at times after it
+struct Exposure {
+    time: Unit3D,
+    shutter: (Unit3D, Unit3D),
+}
+
+impl Exposure {
+    /// The times after the frame that objects moving while the shutter is
+    /// open are placed at, none if it never opens
+    fn steps(&self) -> Vec<Unit3D> {
+        let (open, close) = self.shutter;
+        if close <= open {
+            return Vec::new();
+        }
+        (0..=SHUTTER_STEPS)
+            .map(|step| open + (close - open) * step as Unit3D / SHUTTER_STEPS as Unit3D)
+            .collect()
+    }
+}
+
+/// Add the object `kind` described by `properties` to `graph` under
+/// `parent` as it is at the time of `exposure`, made of `material` unless it
+/// gives its own. Each object added is listed in `placed` with its material.
+/// Without a material, as for the operands of a csg, objects can't be given
+/// one and nothing is listed.
+///
+/// Objects moving while the shutter is open are added as `Moving` shapes,
+/// placed where they are at each of its steps. Moving groups hand their
+/// transform at each step on to their children as `moving`, which is empty
+/// under groups staying still.
+fn place(
+    graph: &mut SceneGraph,
+    parent: NodeId,
+    (kind, properties): (&str, &Value),
+    material: Option<&Finish>,
+    placed: &mut Vec<(NodeId, Finish)>,
+    exposure: &Exposure,
+    moving: &[Matrix],
+) -> Result<(), SceneError> {
+    let time = exposure.time;
+    let known = object_keys(kind).ok_or_else(|| {
+        SceneError::new(properties.position(), format!("unknown item `{}`", kind))
+    })?;
+    let known: Vec<&str> = match material {
+        Some(_) => known.to_vec(),
+        None => known
+            .iter()
+            .copied()
+            .filter(|&key| key != "material")
+            .collect(),
+    };
+    let keys = Keys::new(kind, properties, &known, time)?;
+    let transform = keys.transform()?;
+    let mut steps = Vec::new();
+    if !moving.is_empty() || keys.is_animated("transform") {
+        for (step, after) in exposure.steps().into_iter().enumerate() {
+            let transform = keys.transform_at(time + after)?;
+            match moving.get(step) {
+                Some(&above) => steps.push((after, above * transform)),
+                None => steps.push((after, transform)),
+            }
+        }
+    }
+    let motion = Some(steps.clone())
+        .filter(|steps| !steps.is_empty())
+        .map(Motion::keyframed)
+        .filter(Motion::is_moving);
+    let material = match (keys.fixed("material"), material) {
+        (Some(value), _) => Some(load_material(value, time)?),
+        (None, material) => material.cloned(),
+    };
+
+    if kind == "group" {
+        // A moving group stays put, and its children move with it instead
+        let (group, moving) = match motion {
+            Some(_) => {
+                let moving: Vec<Matrix> = steps.iter().map(|&(_, step)| step).collect();
+                (graph.add_group(parent, Matrix::identity()), moving)
+            }
+            None => (graph.add_group(parent, transform), Vec::new()),
+        };
+        if let Some(children) = keys.fixed("children") {
+            for child in children.as_list()? {
+                let item = (kind_of(child)?, child);
+                let material = material.as_ref();
+                place(graph, group, item, material, placed, exposure, &moving)?;
+            }
+        }
+        return Ok(());
+    }
+    let shape: Arc<dyn Shape> = match kind {
+        "sphere" => Arc::new(Sphere),
+        "cube" => Arc::new(Cube),
+        "plane" => Arc::new(Plane),
+        _ => Arc::new(csg(&keys, exposure)?),
+    };
+    let node = match &motion {
+        Some(motion) => {
+            let shape = Arc::new(Moving::new(shape, motion.clone()));
+            graph.add_object(parent, Matrix::identity(), shape)
+        }
+        None => graph.add_object(parent, transform, shape),
+    };
+    match material {
+        Some(Finish::Patterned(pattern, _)) => {
+            placed.push((node, Finish::Patterned(pattern, motion)));
+        }
+        Some(material) => placed.push((node, material)),
+        None => {}
+    }
+    Ok(())
+}
+
+/// What a nested item is, from its `add` key
+fn kind_of(value: &Value) -> Result<&str, SceneError> {
+    match value.get("add") {
+        Some(add) => add.as_text(),
+        None => Err(SceneError::new(
+            value.position(),
+            "expected an `add` naming what this is",
+        )),
+    }
+}
+
+fn csg(keys: &Keys, exposure: &Exposure) -> Result<Csg, SceneError> {
+    let operation = keys.require_fixed("operation")?;
+    let operation = match operation.as_text()? {
+        "union" => Operation::Union,
+        "intersection" => Operation::Intersection,
+        "difference" => Operation::Difference,
+        name => {
+            return Err(SceneError::new(
+                operation.position(),
+                format!("unknown csg operation `{}`", name),
+            ))
+        }
+    };
+    let operand = |key| -> Result<Arc<dyn Shape>, SceneError> {
+        let value = keys.require_fixed(key)?;
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+        let item = (kind_of(value)?, value);
+        place(&mut graph, root, item, None, &mut Vec::new(), exposure, &[])?;
+        Ok(Arc::new(graph))
+    };
+    Ok(Csg::new(operation, operand("left")?, operand("right")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Rng;
+
+    const CAMERA: &str = "\
+- add: camera
+  width: 8
+  height: 4
+  from: [0, 0, -5]
+  to: [0, 0, 0]
+  background: [0.5, 0.5, 0.5]
+  background-top: [0, 0, 4]
+";
+
+    fn load(items: &str) -> Result<Stage, SceneError> {
+        Stage::load(&Scene::parse(&format!("{}{}", CAMERA, items)).unwrap(), 0.0)
+    }
+
+    fn ray(origin: (f32, f32, f32), direction: (f32, f32, f32)) -> Ray {
+        Ray::new(
+            Point3D::new(origin.0, origin.1, origin.2),
+            Vector3D::new(direction.0, direction.1, direction.2),
+        )
+    }
+
+    #[test]
+    fn test_loads_the_camera() {
+        let stage = load("").unwrap();
+        assert_eq!(8, stage.camera().hsize());
+        assert_eq!(4, stage.camera().vsize());
+        let center = stage.camera().ray_for_pixel(3, 1).unwrap();
+        assert!(center.direction.z() > 0.9);
+        assert_eq!(
+            Spectrum::gray(0.5),
+            stage.background(Vector3D::new(0.0, -1.0, 0.0))
+        );
+        assert_eq!(
+            Spectrum::new(0.0, 0.0, 4.0),
+            stage.background(Vector3D::new(0.0, 1.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_loads_objects_with_their_materials() {
+        let stage = load(
+            "\
+- define: red
+  value:
+    color: [1, 0, 0]
+    diffuse: 0.5
+- add: group
+  transform:
+    - [translate, 0, 0, 10]
+  material: red
+  children:
+    - add: sphere
+    - add: cube
+      transform:
+        - [translate, 5, 0, 0]
+      material:
+        type: glass
+        ior: 1.33
+- add: plane
+  transform:
+    - [translate, 0, -1, 0]
+",
+        )
+        .unwrap();
+        assert_eq!(3, stage.graph().objects().len());
+
+        let ball = stage
+            .intersect(&ray((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)))
+            .unwrap();
+        assert_eq!(9.0, ball.distance);
+        let bsdf = format!("{:?}", ball.material.bsdf);
+        assert!(bsdf.contains("Lambert"));
+        let cube = stage
+            .intersect(&ray((5.0, 0.0, 0.0), (0.0, 0.0, 1.0)))
+            .unwrap();
+        assert!(format!("{:?}", cube.material.bsdf).contains("Dielectric"));
+        let floor = stage
+            .intersect(&ray((0.0, 0.0, 0.0), (0.0, -1.0, 0.0)))
+            .unwrap();
+        assert_eq!(1.0, floor.distance);
+        assert_eq!(Vector3D::new(0.0, 1.0, 0.0), floor.normal);
+    }
+
+    #[test]
+    fn test_loads_csg_operands() {
+        let stage = load(
+            "\
+- add: csg
+  operation: difference
+  left:
+    add: cube
+  right:
+    add: group
+    transform:
+      - [translate, 0, 0, -1]
+    children:
+      - add: sphere
+        transform:
+          - [scale, 0.5, 0.5, 0.5]
+",
+        )
+        .unwrap();
+        // The ball scoops a hollow out of the front of the cube
+        let hit = stage
+            .intersect(&ray((0.0, 0.0, -5.0), (0.0, 0.0, 1.0)))
+            .unwrap();
+        assert_eq!(4.5, hit.distance);
+        assert_eq!(Vector3D::new(0.0, 0.0, -1.0), hit.normal);
+        let beside = stage
+            .intersect(&ray((0.8, 0.0, -5.0), (0.0, 0.0, 1.0)))
+            .unwrap();
+        assert_eq!(4.0, beside.distance);
+    }
+
+    #[test]
+    fn test_loads_lights() {
+        let stage = load(
+            "\
+- add: point-light
+  position: [0, 10, 0]
+  intensity: [20, 20, 20]
+  attenuation: inverse-square
+- add: spot-light
+  position: [0, 10, 0]
+  direction: [0, -1, 0]
+  inner: 0.2
+  outer: 0.4
+  intensity: [1, 1, 1]
+- add: directional-light
+  direction: [0, -1, 0]
+  intensity: [2, 2, 2]
+- add: area-light
+  center: [0, 5, 0]
+  radius: 1
+  usteps: 2
+  vsteps: 3
+  intensity: [1, 1, 1]
+  strength: 8
+",
+        )
+        .unwrap();
+        assert_eq!(3, stage.lights().len());
+        let point = Point3D::new(0.0, 0.0, 0.0);
+        let samples = stage.lights()[0].samples(point, &mut Rng::new(0));
+        assert!((samples[0].intensity.r() - 0.2).abs() < 1e-6);
+        let samples = stage.lights()[2].samples(point, &mut Rng::new(0));
+        assert_eq!(Spectrum::gray(2.0), samples[0].intensity);
+        let area = &stage.area_lights()[0];
+        assert_eq!(6, area.sample_count());
+        assert_eq!(Spectrum::gray(8.0), area.radiance());
+    }
+
+    #[test]
+    fn test_reports_unknown_items_and_keys_where_they_are() {
+        let error = load("- add: teapot\n  size: 3\n").err().unwrap();
+        assert_eq!(Position::new(8, 3), error.position);
+        assert_eq!("unknown item `teapot`", error.message);
+
+        let error = load("- add: sphere\n  radius: 3\n").err().unwrap();
+        assert_eq!(Position::new(9, 11), error.position);
+        assert_eq!("`sphere` doesn't take `radius`", error.message);
+
+        let error = load(
+            "- add: group\n  children:\n    - add: cube\n      material:\n        type: matte\n        ior: 1.5\n",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(Position::new(13, 14), error.position);
+        assert_eq!("`matte material` doesn't take `ior`", error.message);
+
+        let error = load("- add: csg\n  operation: union\n  left:\n    add: cube\n    material:\n      color: [1, 0, 0]\n  right:\n    add: cone\n").err().unwrap();
+        assert_eq!("`cube` doesn't take `material`", error.message);
+        let error = load("- add: point-light\n  position: [0, 1, 0]\n")
+            .err()
+            .unwrap();
+        assert_eq!("`point-light` needs a `intensity`", error.message);
+        assert!(load("- add: spot-light\n  position: [0, 0, 0]\n  direction: [0, 0, 1]\n  inner: 1\n  outer: 0.5\n  intensity: [1, 1, 1]\n").is_err());
+    }
+
+    #[test]
+    fn test_paints_materials_in_patterns() {
+        let stage = load(
+            "\
+- add: plane
+  transform:
+    - [translate, 0, -1, 0]
+  material:
+    pattern:
+      type: checkers
+      colors: [[1, 1, 1], [0, 0, 0]]
+      transform:
+        - [scale, 2, 2, 2]
+",
+        )
+        .unwrap();
+        let color = |x, z| {
+            let hit = stage.intersect(&ray((x, 0.0, z), (0.0, -1.0, 0.0)));
+            format!("{:?}", hit.unwrap().material.bsdf)
+        };
+        assert!(color(1.0, 1.0).contains("Color(1.0, 1.0, 1.0"));
+        assert!(color(3.0, 1.0).contains("Color(0.0, 0.0, 0.0"));
+        assert!(color(1.0, -1.0).contains("Color(0.0, 0.0, 0.0"));
+        assert!(color(-1.0, -1.0).contains("Color(1.0, 1.0, 1.0"));
+    }
+
+    #[test]
+    fn test_patterns_move_with_their_objects() {
+        let scene = format!(
+            "{}  shutter: [0, 1]\n- add: sphere\n  material:\n    pattern:\n      type: stripes\n      colors: [[1, 1, 1], [0, 0, 0]]\n  keyframes:\n    - at: 0\n      transform:\n        - [translate, 0, 0, 10]\n    - at: 2\n      transform:\n        - [translate, 4, 0, 10]\n",
+            CAMERA
+        );
+        let stage = Stage::load(&Scene::parse(&scene).unwrap(), 0.0).unwrap();
+        // Half way through the shutter the ball is 1 along, and so are its
+        // stripes
+        let color = |x| {
+            let ray = ray((x, 0.0, 0.0), (0.0, 0.0, 1.0)).with_time(0.5);
+            format!("{:?}", stage.intersect(&ray).unwrap().material.bsdf)
+        };
+        assert!(color(1.5).contains("Color(1.0, 1.0, 1.0"));
+        assert!(color(0.5).contains("Color(0.0, 0.0, 0.0"));
+    }
+
+    #[test]
+    fn test_reports_bad_patterns_where_they_are() {
+        let pattern = |properties: &str| {
+            let sphere = format!("- add: sphere\n  material:\n{}", properties);
+            load(&sphere).err().unwrap()
+        };
+        let error =
+            pattern("    pattern:\n      type: plaid\n      colors: [[1, 1, 1], [0, 0, 0]]\n");
+        assert_eq!("unknown pattern type `plaid`", error.message);
+        assert_eq!(Position::new(11, 13), error.position);
+
+        let error = pattern("    color: [1, 0, 0]\n    pattern:\n      type: rings\n      colors: [[1, 1, 1], [0, 0, 0]]\n");
+        assert_eq!(
+            "a material can't have both a `color` and a `pattern`",
+            error.message
+        );
+        assert_eq!(Position::new(10, 12), error.position);
+
+        let error = pattern("    pattern:\n      type: stripes\n      colors: [[1, 1, 1]]\n");
+        assert_eq!(
+            "`colors` must be the two colors of the pattern",
+            error.message
+        );
+        assert_eq!(Position::new(12, 15), error.position);
+
+        let error = pattern("    pattern:\n      colors: [[1, 1, 1], [0, 0, 0]]\n");
+        assert_eq!("`pattern` needs a `type`", error.message);
+        let error = pattern("    type: glass\n    pattern:\n      type: stripes\n");
+        assert_eq!("`glass material` doesn't take `pattern`", error.message);
+    }
+
+    #[test]
+    fn test_picks_the_integrator() {
+        assert_eq!(Method::Whitted, load("").unwrap().method());
+        let scene = CAMERA.replace("camera\n", "camera\n  integrator: path\n");
+        let stage = Stage::load(&Scene::parse(&scene).unwrap(), 0.0).unwrap();
+        assert_eq!(Method::PathTracer, stage.method());
+    }
+
+    #[test]
+    fn test_rejects_flat_transforms_and_cameras_looking_up() {
+        let error = load("- add: sphere\n  transform:\n    - [scale, 0, 1, 1]\n")
+            .err()
+            .unwrap();
+        assert_eq!(
+            "`transform` can't flatten an object to nothing",
+            error.message
+        );
+        assert_eq!(Position::new(10, 5), error.position);
+
+        let looking_up = |up: &str| {
+            let scene = CAMERA.replace("camera\n", &format!("camera\n  up: {}\n", up));
+            Stage::load(&Scene::parse(&scene).unwrap(), 0.0)
+        };
+        let error = looking_up("[0, 0, 1]").err().unwrap();
+        assert_eq!(
+            "`up` can't point along the way the camera looks",
+            error.message
+        );
+        assert_eq!(Position::new(2, 7), error.position);
+        assert!(looking_up("[0, 0, 0]").is_err());
+        assert!(looking_up("[1, 0, 0]").is_ok());
+
+        let scene = CAMERA.replace("to: [0, 0, 0]", "to: [0, 0, -5]");
+        assert!(Stage::load(&Scene::parse(&scene).unwrap(), 0.0).is_err());
+    }
+
+    #[test]
+    fn test_reports_when_animations_flatten_objects() {
+        let scene = format!(
+            "{}- add: sphere\n  keyframes:\n    - at: 0\n      transform:\n        - [scale, 1, 1, 1]\n    - at: 1\n      transform:\n        - [scale, 0, 0, 0]\n",
+            CAMERA
+        );
+        let scene = Scene::parse(&scene).unwrap();
+        assert!(Stage::load(&scene, 0.5).is_ok());
+        let error = Stage::load(&scene, 1.0).err().unwrap();
+        assert_eq!(
+            "`transform` can't flatten an object to nothing at 1s",
+            error.message
+        );
+        assert_eq!(Position::new(15, 9), error.position);
+    }
+
+    #[test]
+    fn test_needs_one_camera() {
+        let error = Stage::load(&Scene::parse("- add: sphere\n").unwrap(), 0.0)
+            .err()
+            .unwrap();
+        assert_eq!("the scene has no camera", error.message);
+        let twice = format!("{}{}", CAMERA, CAMERA);
+        assert!(Stage::load(&Scene::parse(&twice).unwrap(), 0.0).is_err());
+    }
+
+    #[test]
+    fn test_keyframes_cameras_objects_materials_and_lights() {
+        let scene = Scene::parse(
+            "\
+- add: camera
+  width: 8
+  height: 4
+  from: [1, 0, -5]
+  to: [0, 0, 0]
+  keyframes:
+    - at: 2
+      from: [1, 0, 5]
+- add: sphere
+  transform:
+    - [translate, 0, 0, 10]
+  material:
+    color: [1, 0, 0]
+    keyframes:
+      - at: 1
+        color: [0, 0, 1]
+        easing: step
+  keyframes:
+    - at: 2
+      transform:
+        - [translate, 4, 0, 10]
+      easing: [bezier, 0, 0, 1, 1]
+- add: point-light
+  position: [0, 10, 0]
+  intensity: [1, 1, 1]
+  keyframes:
+    - at: 4
+      intensity: [9, 9, 9]
+",
+        )
+        .unwrap();
+        let at = |time| Stage::load(&scene, time).unwrap();
+        let intensity = |stage: &Stage| {
+            let point = Point3D::new(0.0, 0.0, 0.0);
+            stage.lights()[0].samples(point, &mut Rng::new(0))[0].intensity
+        };
+        let (start, middle, end) = (at(0.0), at(1.0), at(2.0));
+
+        let ahead = ray((0.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+        assert!(start.intersect(&ahead).is_some());
+        let moved = ray((2.0, 0.0, 0.0), (0.0, 0.0, 1.0));
+        assert_eq!(9.0, middle.intersect(&moved).unwrap().distance);
+        assert!(end.intersect(&ahead).is_none());
+
+        // Stepping to blue at 1 second
+        let color = |stage: &Stage| {
+            let material = stage.materials.values().next().unwrap();
+            format!("{:?}", material)
+        };
+        assert!(color(&start).contains("Color(1.0, 0.0, 0.0"));
+        assert!(color(&middle).contains("Color(0.0, 0.0, 1.0"));
+
+        assert_eq!(Spectrum::gray(1.0), intensity(&start));
+        assert_eq!(Spectrum::gray(5.0), intensity(&at(2.0)));
+        assert_eq!(Spectrum::gray(9.0), intensity(&at(10.0)));
+
+        // The camera swings round to look back from the other side
+        let looking = |stage: &Stage| stage.camera().ray_for_pixel(3, 1).unwrap().direction.z();
+        assert!(looking(&start) > 0.9);
+        assert!(looking(&end) < -0.9);
+    }
+
+    #[test]
+    fn test_objects_move_while_the_shutter_is_open() {
+        let sliding = "  keyframes:
+    - at: 0
+      transform:
+        - [translate, 0, 0, 10]
+    - at: 2
+      transform:
+        - [translate, 4, 0, 10]
+";
+        let scene = format!(
+            "{}  shutter: [0, 1]\n- add: sphere\n{}- add: group\n{}  children:\n    - add: sphere\n      transform:\n        - [translate, 0, 5, 0]\n- add: cube\n  transform:\n    - [translate, 0, -5, 10]\n",
+            CAMERA, sliding, sliding
+        );
+        let stage = Stage::load(&Scene::parse(&scene).unwrap(), 1.0).unwrap();
+        let hits = |x, y, time| {
+            let ray = ray((x, y, 0.0), (0.0, 0.0, 1.0)).with_time(time);
+            stage.intersect(&ray).is_some()
+        };
+        // Where the frame has them as the shutter opens, and a second on
+        // as it closes
+        for &y in &[0.0, 5.0] {
+            assert!(hits(2.0, y, 0.0));
+            assert!(!hits(4.0, y, 0.0));
+            assert!(hits(3.0, y, 0.5));
+            assert!(!hits(2.0, y, 1.0));
+            assert!(hits(4.0, y, 1.0));
+        }
+        // Which leaves things keeping still where they are
+        assert!(hits(0.0, -5.0, 0.0));
+        assert!(hits(0.0, -5.0, 1.0));
+        assert!(!hits(2.5, -5.0, 1.0));
+    }
+
+    #[test]
+    fn test_reports_bad_keyframes() {
+        let error = load("- add: sphere\n  keyframes:\n    - at: 1\n      radius: 2\n")
+            .err()
+            .unwrap();
+        assert_eq!("`sphere` doesn't take `radius`", error.message);
+        let error = load("- add: sphere\n  keyframes:\n    - transform: []\n")
+            .err()
+            .unwrap();
+        assert_eq!("keyframes need an `at` time", error.message);
+        let error = load("- add: sphere\n  keyframes:\n    - at: 1\n      easing: wobble\n")
+            .err()
+            .unwrap();
+        assert_eq!("unknown easing", error.message);
+    }
+}
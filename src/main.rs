@@ -1,3 +1,552 @@
+//! The `raytray` command line renderer. Reads a scene file, renders it and
+//! writes the image, reporting progress and timing on stderr so it can be
+//! run from scripts.
+//!
+//! Scenes are loaded by `loader::Stage`, and the light reaching the camera
+//! is kept unclamped until it is tone mapped or written as HDR. Items may
+//! list `keyframes`, changing them over time. `--frames` renders a range of
+//! frames of the animation to numbered images, skipping any already written
+//! so an interrupted sequence can be picked up again.
+
+use raytray::adaptive::AdaptiveSettings;
+use raytray::animation::Timeline;
+use raytray::canvas::{Canvas, Origin};
+use raytray::encode::{self, Format};
+use raytray::integrator::Method;
+use raytray::loader::Stage;
+use raytray::render::{Renderer, Rng};
+use raytray::sampling::{Pattern, PixelFilter, Sampler};
+use raytray::scene::Scene;
+use raytray::tonemap::ToneMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+const USAGE: &str = "\
+Usage: raytray [OPTIONS] <SCENE>
+
+Options:
+  -o, --output <PATH>     Where to write the image [default: the scene name]
+  -f, --format <FORMAT>   p3, p6, png or hdr [default: from the output path, or p3]
+  -s, --size <WxH>        Override the camera resolution
+  -n, --samples <N>       Samples per pixel [default: 1]
+  -p, --pattern <NAME>    grid, jittered, halton, sobol or blue-noise [default: jittered]
+      --filter <NAME>     box, tent, gaussian or mitchell [default: box]
+      --adaptive <NOISE>  Keep sampling pixels noisier than this, up to --samples
+      --sample-map <PATH> Write a heatmap of samples per pixel (with --adaptive)
+      --frames <A-B>      Render frames A to B of the animation to numbered images
+      --fps <N>           Frames per second of the animation [default: 24]
+  -j, --threads <N>       Render threads [default: one per CPU]
+      --seed <N>          Random seed [default: 0]
+  -t, --tone-map <CURVE>  clamp, reinhard or aces [default: clamp]
+  -i, --integrator <NAME> whitted or path [default: from the scene, or whitted]
+  -q, --quiet             Don't report progress
+  -h, --help              Print this message";
+
+#[derive(PartialEq, Debug, Clone)]
+struct Options {
+    scene: PathBuf,
+    output: Option<PathBuf>,
+    format: Option<Format>,
+    size: Option<(usize, usize)>,
+    samples: usize,
+    pattern: Pattern,
+    filter: PixelFilter,
+    adaptive: Option<f32>,
+    sample_map: Option<PathBuf>,
+    frames: Option<(usize, usize)>,
+    fps: Option<f32>,
+    threads: Option<usize>,
+    seed: u64,
+    tone_map: ToneMap,
+    integrator: Option<Method>,
+    quiet: bool,
+}
+
+impl Options {
+    /// Parse the command line arguments, not including the program name.
+    /// Returns `None` if help was asked for.
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Self>, String> {
+        let mut scene = None;
+        let mut options = Options {
+            scene: PathBuf::new(),
+            output: None,
+            format: None,
+            size: None,
+            samples: 1,
+            pattern: Pattern::Jittered,
+            filter: PixelFilter::Box,
+            adaptive: None,
+            sample_map: None,
+            frames: None,
+            fps: None,
+            threads: None,
+            seed: 0,
+            tone_map: ToneMap::Clamp,
+            integrator: None,
+            quiet: false,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // Accept both `--name value` and `--name=value`
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value)),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || match inline {
+                Some(value) => Ok(value.to_string()),
+                None => args.next().ok_or_else(|| format!("{} needs a value", name)),
+            };
+
+            match name.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-q" | "--quiet" => options.quiet = true,
+                "-o" | "--output" => options.output = Some(PathBuf::from(value()?)),
+                "-f" | "--format" => {
+                    let format = value()?;
+                    options.format = Some(
+                        Format::from_name(&format)
+                            .ok_or_else(|| format!("unknown format `{}`", format))?,
+                    );
+                }
+                "-s" | "--size" => options.size = Some(parse_size(&value()?)?),
+                "-n" | "--samples" => options.samples = parse_count(&name, &value()?)?,
+                "-p" | "--pattern" => {
+                    let pattern = value()?;
+                    options.pattern = Pattern::from_name(&pattern)
+                        .ok_or_else(|| format!("unknown sample pattern `{}`", pattern))?;
+                }
+                "--filter" => {
+                    let filter = value()?;
+                    options.filter = PixelFilter::from_name(&filter)
+                        .ok_or_else(|| format!("unknown filter `{}`", filter))?;
+                }
+                "--adaptive" => {
+                    let noise = value()?;
+                    match noise.parse::<f32>() {
+                        Ok(noise) if noise > 0.0 => options.adaptive = Some(noise),
+                        _ => return Err(format!("--adaptive must be above 0, not `{}`", noise)),
+                    }
+                }
+                "--sample-map" => options.sample_map = Some(PathBuf::from(value()?)),
+                "--frames" => options.frames = Some(parse_frames(&value()?)?),
+                "--fps" => {
+                    let fps = value()?;
+                    match fps.parse::<f32>() {
+                        Ok(fps) if fps > 0.0 => options.fps = Some(fps),
+                        _ => return Err(format!("--fps must be above 0, not `{}`", fps)),
+                    }
+                }
+                "-j" | "--threads" => options.threads = Some(parse_count(&name, &value()?)?),
+                "--seed" => {
+                    let seed = value()?;
+                    options.seed = seed
+                        .parse()
+                        .map_err(|_| format!("--seed must be a number, not `{}`", seed))?;
+                }
+                "-t" | "--tone-map" => {
+                    let curve = value()?;
+                    options.tone_map = ToneMap::from_name(&curve)
+                        .ok_or_else(|| format!("unknown tone map `{}`", curve))?;
+                }
+                "-i" | "--integrator" => {
+                    let integrator = value()?;
+                    options.integrator = Some(
+                        Method::from_name(&integrator)
+                            .ok_or_else(|| format!("unknown integrator `{}`", integrator))?,
+                    );
+                }
+                _ if name.starts_with('-') && name.len() > 1 => {
+                    return Err(format!("unknown option `{}`", name))
+                }
+                _ if scene.is_none() => scene = Some(PathBuf::from(arg)),
+                _ => return Err(format!("unexpected argument `{}`", arg)),
+            }
+        }
+
+        options.scene = scene.ok_or("no scene file given")?;
+        if options.adaptive.is_some() && options.samples < 2 {
+            return Err("--adaptive needs at least 2 --samples".to_string());
+        }
+        if options.sample_map.is_some() && options.adaptive.is_none() {
+            return Err("--sample-map only works with --adaptive".to_string());
+        }
+        if options.fps.is_some() && options.frames.is_none() {
+            return Err("--fps only works with --frames".to_string());
+        }
+        Ok(Some(options))
+    }
+
+    /// The format to write, from `--format` or the output path
+    fn format(&self) -> Format {
+        self.format
+            .or_else(|| self.output.as_ref().and_then(Format::from_path))
+            .unwrap_or(Format::PpmPlain)
+    }
+
+    /// Where to write the image, next to the scene if no output was given
+    fn output(&self) -> PathBuf {
+        match &self.output {
+            Some(output) => output.clone(),
+            None => self.scene.with_extension(self.format().extension()),
+        }
+    }
+
+    /// The frames to render and when they are shown, if rendering an
+    /// animation
+    fn timeline(&self) -> Option<Timeline> {
+        let (first, last) = self.frames?;
+        Some(Timeline::new(self.fps.unwrap_or(24.0), first, last))
+    }
+}
+
+/// Parse a frame range written as `FIRST-LAST`, or a single frame
+fn parse_frames(frames: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("--frames must look like 1-48, not `{}`", frames);
+    let (first, last) = frames.split_once('-').unwrap_or((frames, frames));
+    match (first.parse(), last.parse()) {
+        (Ok(first), Ok(last)) if first <= last => Ok((first, last)),
+        _ => Err(invalid()),
+    }
+}
+
+/// `path` numbered for `frame`, so `out/frame.png` becomes
+/// `out/frame_0001.png` for frame 1
+fn frame_path(path: &Path, frame: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}_{:04}.{}", stem, frame, extension.to_string_lossy()),
+        None => format!("{}_{:04}", stem, frame),
+    };
+    path.with_file_name(name)
+}
+
+/// Parse a size written as `WIDTHxHEIGHT`
+fn parse_size(size: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("--size must look like 640x480, not `{}`", size);
+    let (width, height) = size.split_once(['x', 'X']).ok_or_else(invalid)?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_count(name: &str, count: &str) -> Result<usize, String> {
+    match count.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("{} must be at least 1, not `{}`", name, count)),
+    }
+}
+
 fn main() {
-    println!("Hello, world!");
+    match Options::parse(env::args().skip(1)) {
+        Ok(Some(options)) => {
+            if let Err(message) = run(&options) {
+                eprintln!("raytray: {}", message);
+                process::exit(1);
+            }
+        }
+        Ok(None) => println!("{}", USAGE),
+        Err(message) => {
+            eprintln!("raytray: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let scene_name = options.scene.display();
+    let source = fs::read_to_string(&options.scene)
+        .map_err(|err| format!("can't read {}: {}", scene_name, err))?;
+    let scene = Scene::parse(&source).map_err(|err| format!("{}: {}", scene_name, err))?;
+    let load = |frame: Option<usize>, time| {
+        Stage::load(&scene, time).map_err(|err| match frame {
+            Some(frame) => format!("{}: frame {}: {}", scene_name, frame, err),
+            None => format!("{}: {}", scene_name, err),
+        })
+    };
+
+    let mut renderer = Renderer::new(options.seed);
+    if let Some(threads) = options.threads {
+        renderer.set_threads(threads);
+    }
+
+    let output = options.output();
+    let timeline = match options.timeline() {
+        Some(timeline) => timeline,
+        None => {
+            let stage = load(None, 0.0)?;
+            return render(options, &renderer, stage, &output, &options.sample_map);
+        }
+    };
+    // Check every frame loads before rendering any, so a scene which goes
+    // wrong part way through doesn't leave half an animation
+    for frame in timeline.frames() {
+        load(Some(frame), timeline.time(frame))?;
+    }
+    for frame in timeline.frames() {
+        let path = frame_path(&output, frame);
+        if path.exists() {
+            if !options.quiet {
+                eprintln!(
+                    "skipping frame {}, {} already exists",
+                    frame,
+                    path.display()
+                );
+            }
+            continue;
+        }
+        let sample_map = options
+            .sample_map
+            .as_ref()
+            .map(|map| frame_path(map, frame));
+        let stage = load(Some(frame), timeline.time(frame))?;
+        render(options, &renderer, stage, &path, &sample_map)?;
+    }
+    Ok(())
+}
+
+/// Render `stage` and write it to `output`
+fn render(
+    options: &Options,
+    renderer: &Renderer,
+    mut stage: Stage,
+    output: &Path,
+    sample_map_path: &Option<PathBuf>,
+) -> Result<(), String> {
+    if let Some((width, height)) = options.size {
+        stage.camera_mut().set_size(width, height);
+    }
+    let camera = stage.camera();
+    let (width, height) = (camera.hsize(), camera.vsize());
+    // Cameras count rows from the top
+    let mut canvas = Canvas::with_origin(width, height, Origin::TopLeft);
+    let sampler = Sampler::new(options.pattern, options.filter, options.samples);
+    let integrator = options.integrator.unwrap_or(stage.method()).integrator();
+
+    let start = Instant::now();
+    let reported = AtomicUsize::new(0);
+    let mut sample_map = None;
+    let mut total_samples = width * height * options.samples;
+    let film = match options.adaptive {
+        Some(threshold) => {
+            let min_samples = options.samples.min(4);
+            let settings = AdaptiveSettings::new(min_samples, options.samples, threshold);
+            let shade = |x, y, rng: &mut Rng| match camera.ray_for_sample(x, y, rng) {
+                Some(ray) => integrator.radiance(&stage, &ray, rng),
+                None => Default::default(),
+            };
+            let accumulator =
+                renderer.render_adaptive_radiance(&canvas, &settings, shade, |pass, active| {
+                    if !options.quiet {
+                        eprint!(
+                            "\rrendering: pass {}, {} pixels still noisy   ",
+                            pass, active
+                        );
+                        let _ = io::stderr().flush();
+                    }
+                });
+            total_samples = accumulator.total_samples();
+            sample_map = Some(accumulator.sample_heatmap());
+            accumulator.film()
+        }
+        None => renderer.render_world(
+            &canvas,
+            camera,
+            &stage,
+            integrator.as_ref(),
+            &sampler,
+            |done, total| {
+                let percent = done * 100 / total;
+                if !options.quiet && reported.fetch_max(percent, Ordering::Relaxed) < percent {
+                    eprint!("\rrendering: {:3}% ({}/{} tiles)", percent, done, total);
+                    let _ = io::stderr().flush();
+                }
+            },
+        ),
+    };
+    let elapsed = start.elapsed();
+
+    let written = match options.format() {
+        Format::Hdr => encode::save_film(&film, output),
+        format => {
+            film.resolve_tone_mapped(&mut canvas, options.tone_map);
+            encode::save(&canvas, output, format)
+        }
+    };
+    written.map_err(|err| format!("can't write {}: {}", output.display(), err))?;
+    if let (Some(path), Some(map)) = (sample_map_path, &sample_map) {
+        let format = Format::from_path(path).unwrap_or(Format::PpmPlain);
+        encode::save(map, path, format)
+            .map_err(|err| format!("can't write {}: {}", path.display(), err))?;
+    }
+
+    if !options.quiet {
+        eprintln!(
+            "\rrendered {}x{} in {:.2}s ({} threads, {:.1} samples per pixel)",
+            width,
+            height,
+            elapsed.as_secs_f64(),
+            renderer.threads(),
+            total_samples as f64 / (width * height) as f64
+        );
+        eprintln!("wrote {}", output.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raytray::color::Spectrum;
+    use raytray::integrator::World;
+    use raytray::units::Vector3D;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parses_options() {
+        let options = parse(&[
+            "-o",
+            "out.png",
+            "--size=64x32",
+            "-n",
+            "4",
+            "--threads",
+            "2",
+            "--seed",
+            "9",
+            "-t",
+            "aces",
+            "--pattern=sobol",
+            "--filter",
+            "mitchell",
+            "--integrator=path",
+            "scene.yml",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(PathBuf::from("scene.yml"), options.scene);
+        assert_eq!(Some((64, 32)), options.size);
+        assert_eq!(4, options.samples);
+        assert_eq!(Some(2), options.threads);
+        assert_eq!(9, options.seed);
+        assert_eq!(ToneMap::Aces, options.tone_map);
+        assert_eq!(Pattern::Sobol, options.pattern);
+        assert_eq!(PixelFilter::Mitchell, options.filter);
+        assert_eq!(Some(Method::PathTracer), options.integrator);
+        assert_eq!(Format::Png, options.format());
+    }
+
+    #[test]
+    fn test_output_defaults_to_the_scene_name() {
+        let options = parse(&["scenes/ball.yml", "-f", "p6"]).unwrap().unwrap();
+        assert_eq!(PathBuf::from("scenes/ball.ppm"), options.output());
+        assert_eq!(Format::PpmRaw, options.format());
+        let options = parse(&["ball.yml"]).unwrap().unwrap();
+        assert_eq!(Format::PpmPlain, options.format());
+    }
+
+    #[test]
+    fn test_help() {
+        assert_eq!(Ok(None), parse(&["scene.yml", "--help"]));
+    }
+
+    #[test]
+    fn test_rejects_bad_arguments() {
+        assert_eq!(Err("no scene file given".to_string()), parse(&[]));
+        assert_eq!(
+            Err("unknown option `--fast`".to_string()),
+            parse(&["--fast", "a.yml"])
+        );
+        assert_eq!(Err("-o needs a value".to_string()), parse(&["a.yml", "-o"]));
+        assert_eq!(
+            Err("--samples must be at least 1, not `0`".to_string()),
+            parse(&["a.yml", "--samples", "0"])
+        );
+        assert!(parse(&["a.yml", "--size", "64"]).is_err());
+        assert!(parse(&["a.yml", "b.yml"]).is_err());
+        assert!(parse(&["a.yml", "-f", "gif"]).is_err());
+        assert!(parse(&["a.yml", "-i", "photon"]).is_err());
+        assert!(parse(&["a.yml", "--adaptive", "0.01"]).is_err());
+        assert!(parse(&["a.yml", "--sample-map", "map.png"]).is_err());
+        assert!(parse(&["a.yml", "-n", "16", "--adaptive", "0.01"]).is_ok());
+        assert!(parse(&["a.yml", "--frames", "9-3"]).is_err());
+        assert!(parse(&["a.yml", "--fps", "30"]).is_err());
+    }
+
+    #[test]
+    fn test_numbers_frames() {
+        let options = parse(&["a.yml", "--frames=3-5", "--fps", "30"])
+            .unwrap()
+            .unwrap();
+        let timeline = options.timeline().unwrap();
+        assert_eq!(3..=5, timeline.frames());
+        assert_eq!(0.1, timeline.time(3));
+        let options = parse(&["a.yml", "--frames", "7"]).unwrap().unwrap();
+        assert_eq!(7..=7, options.timeline().unwrap().frames());
+        assert_eq!(None, parse(&["a.yml"]).unwrap().unwrap().timeline());
+
+        assert_eq!(
+            PathBuf::from("out/frame_0001.png"),
+            frame_path(Path::new("out/frame.png"), 1)
+        );
+        assert_eq!(
+            PathBuf::from("frame_12345"),
+            frame_path(Path::new("frame"), 12345)
+        );
+    }
+
+    #[test]
+    fn test_keyframes_the_camera_background() {
+        let scene = Scene::parse(
+            "- add: camera\n  width: 4\n  height: 2\n  from: [0, 0, -5]\n  to: [0, 0, 0]\n  background: [1, 0, 0]\n  keyframes:\n    - at: 2\n      background: [0, 0, 1]\n      easing: step\n    - at: 4\n      background: [0, 1, 0]\n",
+        )
+        .unwrap();
+        let background = |time| {
+            let stage = Stage::load(&scene, time).unwrap();
+            stage.background(Vector3D::new(0.0, 0.0, 1.0))
+        };
+        assert_eq!(Spectrum::new(0.5, 0.0, 0.5), background(1.0));
+        assert_eq!(Spectrum::new(0.0, 0.0, 1.0), background(3.0));
+
+        let scene = Scene::parse(
+            "- add: camera\n  width: 4\n  height: 2\n  from: [0, 0, -5]\n  to: [0, 0, 0]\n  keyframes:\n    - background: [0, 0, 1]\n",
+        )
+        .unwrap();
+        assert!(Stage::load(&scene, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_renders_scenes_unclamped() {
+        let dir = env::temp_dir().join(format!("raytray-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let scene = dir.join("glow.yml");
+        fs::write(
+            &scene,
+            "- add: camera\n  width: 2\n  height: 2\n  from: [0, 0, -5]\n  to: [0, 0, 0]\n  background: [4, 2, 1]\n",
+        )
+        .unwrap();
+        let output = dir.join("glow.hdr");
+        let options = parse(&[
+            scene.to_str().unwrap(),
+            "-q",
+            "-o",
+            output.to_str().unwrap(),
+        ])
+        .unwrap()
+        .unwrap();
+        run(&options).unwrap();
+        let hdr = fs::read(&output).unwrap();
+        // Light brighter than white keeps its exponent
+        assert_eq!([128, 64, 32, 131], hdr[hdr.len() - 4..]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
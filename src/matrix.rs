@@ -1,4 +1,4 @@
-use crate::units::{Point3D, Tuple, TupleMut, Unit3D, EPSILON};
+use crate::units::{Point3D, Quaternion, Tuple, TupleMut, Unit3D, Vector3D, EPSILON};
 use std::ops::{Index, IndexMut, Mul, Rem};
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -26,6 +26,150 @@ impl Matrix {
         translation
     }
 
+    /// The identity Matrix, which leaves anything it multiplies unchanged
+    pub fn identity() -> Self {
+        IDENTITY
+    }
+
+    /// Create a scaling matrix which scales each axis by the matching
+    /// component of the tuple
+    pub fn scaling<T: Tuple>(factors: T) -> Self {
+        let mut scaling = IDENTITY;
+        scaling[0][0] = factors.x();
+        scaling[1][1] = factors.y();
+        scaling[2][2] = factors.z();
+        scaling
+    }
+
+    /// Create a matrix rotating `radians` around the x axis, clockwise when
+    /// looking back along the axis towards the origin
+    pub fn rotation_x(radians: Unit3D) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        let mut rotation = IDENTITY;
+        rotation[1][1] = cos;
+        rotation[1][2] = -sin;
+        rotation[2][1] = sin;
+        rotation[2][2] = cos;
+        rotation
+    }
+
+    /// Create a matrix rotating `radians` around the y axis
+    pub fn rotation_y(radians: Unit3D) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        let mut rotation = IDENTITY;
+        rotation[0][0] = cos;
+        rotation[0][2] = sin;
+        rotation[2][0] = -sin;
+        rotation[2][2] = cos;
+        rotation
+    }
+
+    /// Create a matrix rotating `radians` around the z axis
+    pub fn rotation_z(radians: Unit3D) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        let mut rotation = IDENTITY;
+        rotation[0][0] = cos;
+        rotation[0][1] = -sin;
+        rotation[1][0] = sin;
+        rotation[1][1] = cos;
+        rotation
+    }
+
+    /// Create a matrix which moves the world so that an eye at `from`
+    /// looks towards `to`, with `up` roughly upwards
+    pub fn view_transform(from: Point3D, to: Point3D, up: Vector3D) -> Self {
+        let forward = (to - from).normalize();
+        let left = forward.cross(up.normalize());
+        let true_up = left.cross(forward);
+        let orientation = Matrix([
+            [left.x(), left.y(), left.z(), 0.0],
+            [true_up.x(), true_up.y(), true_up.z(), 0.0],
+            [-forward.x(), -forward.y(), -forward.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        orientation * Matrix::translation(Point3D::new(-from.x(), -from.y(), -from.z()))
+    }
+
+    /// Create a matrix rotating by the unit quaternion `rotation`
+    pub fn from_quaternion(rotation: Quaternion) -> Self {
+        let (w, x, y, z) = (rotation.w(), rotation.x(), rotation.y(), rotation.z());
+        Matrix([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Create a matrix which scales, then rotates, then translates
+    pub fn from_trs(translation: Vector3D, rotation: Quaternion, scale: Vector3D) -> Self {
+        Matrix::translation(translation)
+            * Matrix::from_quaternion(rotation)
+            * Matrix::scaling(scale)
+    }
+
+    /// Split the matrix into the translation, rotation, shear and scale
+    /// which make it up, or `None` if it flattens space or has a
+    /// perspective part which can't be split that way
+    pub fn decompose(&self) -> Option<Decomposition> {
+        let m = self.0;
+        if m[3] != [0.0, 0.0, 0.0, 1.0] {
+            return None;
+        }
+        let column = |col: usize| Vector3D::new(m[0][col], m[1][col], m[2][col]);
+        let (a0, a1, a2) = (column(0), column(1), column(2));
+
+        // Gram-Schmidt the columns into a rotation, keeping track of how
+        // far each leaned on the ones before
+        let sx = a0.magnitude();
+        if sx < EPSILON {
+            return None;
+        }
+        let r0 = a0 / sx;
+        let leaned = a1 - r0 * r0.dot(a1);
+        let sy = leaned.magnitude();
+        if sy < EPSILON {
+            return None;
+        }
+        let r1 = leaned / sy;
+        let shear_xy = r0.dot(a1) / sy;
+        let leaned = a2 - r0 * r0.dot(a2) - r1 * r1.dot(a2);
+        let mut sz = leaned.magnitude();
+        if sz < EPSILON {
+            return None;
+        }
+        let mut r2 = leaned / sz;
+        // A mirror image shows up as a negative scale rather than a
+        // rotation which isn't one
+        if r0.cross(r1).dot(r2) < 0.0 {
+            r2 = -r2;
+            sz = -sz;
+        }
+        let (shear_xz, shear_yz) = (r0.dot(a2) / sz, r1.dot(a2) / sz);
+
+        Some(Decomposition {
+            translation: Vector3D::new(m[0][3], m[1][3], m[2][3]),
+            rotation: quaternion_from_columns(r0, r1, r2),
+            scale: Vector3D::new(sx, sy, sz),
+            shear: Vector3D::new(shear_xy, shear_xz, shear_yz),
+        })
+    }
+
     /// Transpose the rows and columns of the Matrix such that the element
     /// as `[2,3]` is at `[3,2]` in the resulting Matrix. The original Matrix
     /// is consumed and a new one returned in its place.
@@ -197,6 +341,67 @@ impl<T: TupleMut + Clone> Translate<T> for Point3D {
     }
 }
 
+/// A transform split into parts which can each be blended on their own
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Decomposition {
+    pub translation: Vector3D,
+    pub rotation: Quaternion,
+    pub scale: Vector3D,
+    /// How far y leans towards x, z towards x and z towards y
+    pub shear: Vector3D,
+}
+
+impl Decomposition {
+    /// Put the parts back together, scaling first, then shearing, rotating
+    /// and translating
+    pub fn to_matrix(&self) -> Matrix {
+        let mut shear = IDENTITY;
+        shear[0][1] = self.shear.x();
+        shear[0][2] = self.shear.y();
+        shear[1][2] = self.shear.z();
+        Matrix::translation(self.translation)
+            * Matrix::from_quaternion(self.rotation)
+            * shear
+            * Matrix::scaling(self.scale)
+    }
+
+    /// The transform `t` of the way from this one to `other`. Rotations
+    /// turn steadily instead of shrinking through the middle like blended
+    /// matrix elements do.
+    pub fn interpolate(&self, other: &Self, t: Unit3D) -> Self {
+        let lerp = |a: Vector3D, b: Vector3D| a + (b - a) * t;
+        Self {
+            translation: lerp(self.translation, other.translation),
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: lerp(self.scale, other.scale),
+            shear: lerp(self.shear, other.shear),
+        }
+    }
+}
+
+/// The unit quaternion for the rotation matrix with columns `r0`, `r1` and
+/// `r2`
+fn quaternion_from_columns(r0: Vector3D, r1: Vector3D, r2: Vector3D) -> Quaternion {
+    let (m00, m10, m20) = (r0.x(), r0.y(), r0.z());
+    let (m01, m11, m21) = (r1.x(), r1.y(), r1.z());
+    let (m02, m12, m22) = (r2.x(), r2.y(), r2.z());
+    let trace = m00 + m11 + m22;
+    let q = if trace > 0.0 {
+        let s = (trace + 1.0).sqrt() * 2.0;
+        Quaternion::new(0.25 * s, (m21 - m12) / s, (m02 - m20) / s, (m10 - m01) / s)
+    } else if m00 > m11 && m00 > m22 {
+        let s = (1.0 + m00 - m11 - m22).sqrt() * 2.0;
+        Quaternion::new((m21 - m12) / s, 0.25 * s, (m01 + m10) / s, (m02 + m20) / s)
+    } else if m11 > m22 {
+        let s = (1.0 + m11 - m00 - m22).sqrt() * 2.0;
+        Quaternion::new((m02 - m20) / s, (m01 + m10) / s, 0.25 * s, (m12 + m21) / s)
+    } else {
+        let s = (1.0 + m22 - m00 - m11).sqrt() * 2.0;
+        Quaternion::new((m10 - m01) / s, (m02 + m20) / s, (m12 + m21) / s, 0.25 * s)
+    };
+    q.normalize()
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 struct SubMatrix([[Unit3D; 3]; 3]);
 
@@ -204,7 +409,7 @@ impl SubMatrix {
     // FIXME: Leaving this commented out in case needed but I suspect
     // it isn't.
 
-    /// Calculate and return the determinate of the SubMatrix
+    // Calculate and return the determinate of the SubMatrix
     // pub fn determinate(&self) -> Unit3D {
     //     let mut determinate = 0.0;
     //     for (col, sv) in self[0].iter().enumerate() {
@@ -270,7 +475,6 @@ impl IndexMut<usize> for SubMatrix {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::units::Vector3D;
 
     // Approximate equality, good enough for floating point primative comparisons
     // in tests.
@@ -510,4 +714,131 @@ mod tests {
         let translated = Point3D::new(5.0, -3.0, 2.0).translate(Vector3D::new(-3.0, 4.0, 5.0));
         assert_eq!(Vector3D::new(-3.0, 4.0, 5.0), translated);
     }
+
+    #[test]
+    fn test_scales_a_point() {
+        let scaling = Matrix::scaling(Point3D::new(2.0, 3.0, 4.0));
+        assert_eq!(
+            Point3D::new(-8.0, 18.0, 32.0),
+            scaling * Point3D::new(-4.0, 6.0, 8.0)
+        );
+    }
+
+    #[test]
+    fn test_rotates_a_point_around_each_axis() {
+        let quarter = std::f32::consts::FRAC_PI_2;
+        let close = |a: Point3D, b: Point3D| {
+            (a.x() - b.x()).abs() < EPSILON
+                && (a.y() - b.y()).abs() < EPSILON
+                && (a.z() - b.z()).abs() < EPSILON
+        };
+        assert!(close(
+            Point3D::new(0.0, 0.0, 1.0),
+            Matrix::rotation_x(quarter) * Point3D::new(0.0, 1.0, 0.0)
+        ));
+        assert!(close(
+            Point3D::new(1.0, 0.0, 0.0),
+            Matrix::rotation_y(quarter) * Point3D::new(0.0, 0.0, 1.0)
+        ));
+        assert!(close(
+            Point3D::new(-1.0, 0.0, 0.0),
+            Matrix::rotation_z(quarter) * Point3D::new(0.0, 1.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn test_identity_leaves_a_matrix_unchanged() {
+        let m = Matrix::rotation_z(0.3) * Matrix::translation(Point3D::new(1.0, 2.0, 3.0));
+        assert_eq!(m, m * Matrix::identity());
+    }
+
+    #[test]
+    fn test_view_transforms() {
+        let origin = Point3D::new(0.0, 0.0, 0.0);
+        let up = Vector3D::new(0.0, 1.0, 0.0);
+        assert_eq!(
+            Matrix::identity(),
+            Matrix::view_transform(origin, Point3D::new(0.0, 0.0, -1.0), up)
+        );
+        assert_eq!(
+            Matrix::scaling(Point3D::new(-1.0, 1.0, -1.0)),
+            Matrix::view_transform(origin, Point3D::new(0.0, 0.0, 1.0), up)
+        );
+        assert_eq!(
+            Matrix::translation(Point3D::new(0.0, 0.0, -8.0)),
+            Matrix::view_transform(Point3D::new(0.0, 0.0, 8.0), origin, up)
+        );
+        let arbitrary = Matrix::view_transform(
+            Point3D::new(1.0, 3.0, 2.0),
+            Point3D::new(4.0, -2.0, 8.0),
+            Vector3D::new(1.0, 1.0, 0.0),
+        );
+        let expected = Matrix([
+            [-0.50709, 0.50709, 0.67612, -2.36643],
+            [0.76772, 0.60609, 0.12122, -2.82843],
+            [-0.35857, 0.59761, -0.71714, 0.00000],
+            [0.00000, 0.00000, 0.00000, 1.00000],
+        ]);
+        for row in 0..4 {
+            for col in 0..4 {
+                assert!((expected[row][col] - arbitrary[row][col]).abs() < 0.0001);
+            }
+        }
+    }
+
+    fn matrices_close(a: &Matrix, b: &Matrix) -> bool {
+        (0..4).all(|row| (0..4).all(|col| (a[row][col] - b[row][col]).abs() < 0.0001))
+    }
+
+    #[test]
+    fn test_quaternion_matrices_match_axis_rotations() {
+        let y = Vector3D::new(0.0, 1.0, 0.0);
+        let z = Vector3D::new(0.0, 0.0, 1.0);
+        let turn_y = Matrix::from_quaternion(Quaternion::from_axis_angle(y, 0.7));
+        let turn_z = Matrix::from_quaternion(Quaternion::from_axis_angle(z, -1.2));
+        assert!(matrices_close(&Matrix::rotation_y(0.7), &turn_y));
+        assert!(matrices_close(&Matrix::rotation_z(-1.2), &turn_z));
+    }
+
+    #[test]
+    fn test_decomposes_a_trs_matrix() {
+        let translation = Vector3D::new(1.0, -2.0, 3.0);
+        let rotation = Quaternion::from_axis_angle(Vector3D::new(1.0, 1.0, 0.0), 0.8);
+        let scale = Vector3D::new(2.0, 0.5, 3.0);
+        let m = Matrix::from_trs(translation, rotation, scale);
+        let parts = m.decompose().unwrap();
+        assert!((parts.translation - translation).magnitude() < 0.0001);
+        assert!((parts.scale - scale).magnitude() < 0.0001);
+        assert!(parts.shear.magnitude() < 0.0001);
+        assert!((parts.rotation.dot(rotation).abs() - 1.0).abs() < 0.0001);
+        assert!(matrices_close(&m, &parts.to_matrix()));
+    }
+
+    #[test]
+    fn test_decomposes_shears_and_mirrors() {
+        let mut shear = Matrix::identity();
+        shear[0][1] = 0.5;
+        shear[1][2] = -0.25;
+        let m = Matrix::rotation_x(0.3) * shear * Matrix::scaling(Point3D::new(1.0, 2.0, -1.5));
+        let parts = m.decompose().unwrap();
+        assert!(parts.scale.z() < 0.0);
+        assert!(matrices_close(&m, &parts.to_matrix()));
+        assert_eq!(
+            None,
+            Matrix::scaling(Point3D::new(1.0, 0.0, 1.0)).decompose()
+        );
+    }
+
+    #[test]
+    fn test_interpolated_rotations_keep_their_size() {
+        let start = Matrix::rotation_z(0.0).decompose().unwrap();
+        let end = Matrix::rotation_z(std::f32::consts::PI * 0.9)
+            .decompose()
+            .unwrap();
+        let middle = start.interpolate(&end, 0.5).to_matrix();
+        assert!(matrices_close(
+            &Matrix::rotation_z(std::f32::consts::PI * 0.45),
+            &middle
+        ));
+    }
 }
@@ -0,0 +1,348 @@
+//! Transforms which change over time, for motion blur. A `Motion` is a list
+//! of keyframes, each a transform at a moment, and the transform between
+//! them is interpolated by decomposing the keyframes, so things turn rather
+//! than shrink and shear between them. Rays carry the time they were cast,
+//! so each ray sees a moving object where it was at that moment and
+//! averaging many rays over the shutter interval smears it along its path.
+//!
+//! A `Moving` shape puts any `Shape` on a `Motion`, so it can be placed in a
+//! `SceneGraph` like any other.
+
+use crate::bounds::BoundingBox;
+use crate::bvh::Bounded;
+use crate::matrix::{Decomposition, Matrix};
+use crate::ray::Ray;
+use crate::shape::{Crossing, Shape};
+use crate::units::{Tuple, Unit3D, Vector3D};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// Steps taken between keyframes when working out the bounds of something
+/// moving, since an interpolated path can bulge past the keyframes
+const BOUNDS_STEPS: usize = 4;
+
+/// The most a keyframe's rotation may turn in one of those steps, more
+/// steps are taken for larger turns
+const BOUNDS_STEP_ANGLE: Unit3D = PI / 8.0;
+
+/// A transform which may move over time
+#[derive(PartialEq, Debug, Clone)]
+pub struct Motion {
+    keyframes: Vec<Keyframe>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+struct Keyframe {
+    time: Unit3D,
+    transform: Matrix,
+    inverse: Matrix,
+    /// `None` for transforms which can't be decomposed, which are blended
+    /// element by element instead
+    parts: Option<Decomposition>,
+}
+
+impl Motion {
+    /// A transform which never moves
+    pub fn fixed(transform: Matrix) -> Self {
+        Self::keyframed(vec![(0.0, transform)])
+    }
+
+    /// A transform moving from `start` at time 0 to `end` at time 1
+    pub fn linear(start: Matrix, end: Matrix) -> Self {
+        Self::keyframed(vec![(0.0, start), (1.0, end)])
+    }
+
+    /// A transform passing through each of `keyframes`, given as times and
+    /// transforms in any order. Before the first and after the last it
+    /// stays still.
+    pub fn keyframed(mut keyframes: Vec<(Unit3D, Matrix)>) -> Self {
+        if keyframes.is_empty() {
+            panic!("motion needs at least one keyframe!");
+        }
+        keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            keyframes: keyframes
+                .into_iter()
+                .map(|(time, transform)| Keyframe {
+                    time,
+                    transform,
+                    inverse: transform.inverse(),
+                    parts: transform.decompose(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether the transform changes at all
+    pub fn is_moving(&self) -> bool {
+        // Compared element by element, since matrix equality is only
+        // approximate
+        self.keyframes.windows(2).any(|pair| {
+            let (a, b) = (pair[0].transform, pair[1].transform);
+            (0..4).any(|row| (0..4).any(|col| a[row][col] != b[row][col]))
+        })
+    }
+
+    /// The times of the keyframes
+    pub fn times(&self) -> Vec<Unit3D> {
+        self.keyframes
+            .iter()
+            .map(|keyframe| keyframe.time)
+            .collect()
+    }
+
+    /// The transform at `time`, interpolated between the keyframes either
+    /// side of it
+    pub fn at(&self, time: Unit3D) -> Matrix {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        if time <= first.time {
+            return first.transform;
+        }
+        if time >= last.time {
+            return last.transform;
+        }
+        let next = self.keyframes.iter().position(|k| k.time > time).unwrap();
+        let (start, end) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - start.time) / (end.time - start.time);
+        if let (Some(from), Some(to)) = (&start.parts, &end.parts) {
+            return from.interpolate(to, t).to_matrix();
+        }
+        let mut transform = start.transform;
+        for row in 0..4 {
+            for col in 0..4 {
+                transform[row][col] += (end.transform[row][col] - start.transform[row][col]) * t;
+            }
+        }
+        transform
+    }
+
+    /// The inverse of the transform at `time`
+    pub fn inverse_at(&self, time: Unit3D) -> Matrix {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        if time <= first.time {
+            first.inverse
+        } else if time >= last.time {
+            last.inverse
+        } else {
+            self.at(time).inverse()
+        }
+    }
+
+    /// `ray` moved into the space of the object at the time it was cast,
+    /// ready to be intersected
+    pub fn ray_to_object(&self, ray: &Ray) -> Ray {
+        ray.transform(&self.inverse_at(ray.time))
+    }
+
+    /// Bounds around `bounds` wherever the transform takes it. Between
+    /// keyframes it is sampled in steps, and where those turn, the boxes
+    /// are padded by how far a corner can swing off the straight line
+    /// between two steps, which is at most its reach times the angle
+    /// turned.
+    pub fn bounds(&self, bounds: &BoundingBox) -> BoundingBox {
+        let mut swept = bounds.transform(&self.keyframes[0].transform);
+        if bounds.is_empty() || !bounds.is_finite() {
+            return swept;
+        }
+        for pair in self.keyframes.windows(2) {
+            let (start, end) = (&pair[0], &pair[1]);
+            let angle = match (&start.parts, &end.parts) {
+                (Some(from), Some(to)) => {
+                    2.0 * from.rotation.dot(to.rotation).abs().min(1.0).acos()
+                }
+                // Blended element by element, every point moves in a
+                // straight line so the keyframes are enough
+                _ => 0.0,
+            };
+            let steps = BOUNDS_STEPS.max((angle / BOUNDS_STEP_ANGLE).ceil() as usize);
+            let pad = reach(bounds, &start.transform).max(reach(bounds, &end.transform)) * angle
+                / steps as Unit3D;
+            for step in 0..=steps {
+                let t = step as Unit3D / steps as Unit3D;
+                let time = start.time + (end.time - start.time) * t;
+                let moved = bounds.transform(&self.at(time));
+                swept = swept.merge(&padded(&moved, pad));
+            }
+        }
+        swept
+    }
+}
+
+/// How far the corners of `bounds` are from its origin once `transform` has
+/// scaled and turned them
+fn reach(bounds: &BoundingBox, transform: &Matrix) -> Unit3D {
+    let (min, max) = (bounds.min(), bounds.max());
+    (0..8)
+        .map(|corner| {
+            let pick = |bit, a: Unit3D, b: Unit3D| if corner & bit == 0 { a } else { b };
+            let offset = Vector3D::new(
+                pick(1, min.x(), max.x()),
+                pick(2, min.y(), max.y()),
+                pick(4, min.z(), max.z()),
+            );
+            (*transform * offset).magnitude()
+        })
+        .fold(0.0, Unit3D::max)
+}
+
+fn padded(bounds: &BoundingBox, pad: Unit3D) -> BoundingBox {
+    let pad = Vector3D::new(pad, pad, pad);
+    BoundingBox::new(bounds.min() - pad, bounds.max() + pad)
+}
+
+impl From<Matrix> for Motion {
+    fn from(transform: Matrix) -> Self {
+        Self::fixed(transform)
+    }
+}
+
+// ==========================================================================
+// Moving
+
+/// A shape following a `Motion`, seen by each ray where it was at the time
+/// the ray was cast
+#[derive(Debug, Clone)]
+pub struct Moving {
+    shape: Arc<dyn Shape>,
+    motion: Motion,
+}
+
+impl Moving {
+    pub fn new<M: Into<Motion>>(shape: Arc<dyn Shape>, motion: M) -> Self {
+        Self {
+            shape,
+            motion: motion.into(),
+        }
+    }
+
+    pub fn motion(&self) -> &Motion {
+        &self.motion
+    }
+}
+
+impl Shape for Moving {
+    fn intersect(&self, ray: &Ray) -> Vec<Crossing> {
+        let normals = self.motion.inverse_at(ray.time).transpose();
+        self.shape
+            .intersect(&self.motion.ray_to_object(ray))
+            .into_iter()
+            .map(|crossing| Crossing::new(crossing.t, normals * crossing.normal))
+            .collect()
+    }
+}
+
+impl Bounded for Moving {
+    fn bounds(&self) -> BoundingBox {
+        self.motion.bounds(&self.shape.bounds())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::Sphere;
+    use crate::units::Point3D;
+
+    fn shift(x: Unit3D) -> Matrix {
+        Matrix::translation(Point3D::new(x, 0.0, 0.0))
+    }
+
+    #[test]
+    fn test_interpolates_between_keyframes() {
+        let motion = Motion::linear(shift(0.0), shift(4.0));
+        assert!(motion.is_moving());
+        assert_eq!(shift(0.0), motion.at(-1.0));
+        assert_eq!(shift(1.0), motion.at(0.25));
+        assert_eq!(shift(4.0), motion.at(2.0));
+        assert_eq!(shift(-3.0), motion.inverse_at(0.75));
+    }
+
+    #[test]
+    fn test_keyframes_in_any_order() {
+        let motion = Motion::keyframed(vec![
+            (2.0, shift(0.0)),
+            (0.0, shift(0.0)),
+            (1.0, shift(2.0)),
+        ]);
+        assert_eq!(vec![0.0, 1.0, 2.0], motion.times());
+        assert_eq!(shift(1.0), motion.at(0.5));
+        assert_eq!(shift(1.0), motion.at(1.5));
+        assert!(!Motion::fixed(shift(3.0)).is_moving());
+    }
+
+    #[test]
+    fn test_rays_see_objects_where_they_were() {
+        let motion = Motion::linear(shift(0.0), shift(10.0));
+        let ray = Ray::new(Point3D::new(5.0, 0.0, -5.0), Vector3D::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            Point3D::new(5.0, 0.0, -5.0),
+            motion.ray_to_object(&ray).origin
+        );
+        let later = motion.ray_to_object(&ray.with_time(0.5));
+        assert_eq!(Point3D::new(0.0, 0.0, -5.0), later.origin);
+        assert_eq!(0.5, later.time);
+    }
+
+    #[test]
+    fn test_bounds_cover_the_whole_path() {
+        let motion = Motion::keyframed(vec![
+            (0.0, shift(0.0)),
+            (0.5, shift(5.0)),
+            (1.0, shift(-2.0)),
+        ]);
+        let unit = BoundingBox::new(Point3D::new(-1.0, -1.0, -1.0), Point3D::new(1.0, 1.0, 1.0));
+        let swept = motion.bounds(&unit);
+        assert_eq!(Point3D::new(-3.0, -1.0, -1.0), swept.min());
+        assert_eq!(Point3D::new(6.0, 1.0, 1.0), swept.max());
+    }
+
+    #[test]
+    fn test_bounds_cover_turns_between_steps() {
+        // A long bar swinging half a turn about y
+        let motion = Motion::linear(Matrix::rotation_y(0.0), Matrix::rotation_y(PI - 0.01));
+        let bar = BoundingBox::new(Point3D::new(-0.1, -0.1, 0.0), Point3D::new(0.1, 0.1, 10.0));
+        let swept = motion.bounds(&bar);
+        for step in 0..=100 {
+            let moved = bar.transform(&motion.at(step as Unit3D / 100.0));
+            assert!(swept.contains_box(&moved));
+        }
+    }
+
+    #[test]
+    fn test_turns_without_shrinking() {
+        let motion = Motion::linear(Matrix::rotation_y(0.0), Matrix::rotation_y(3.0));
+        let point = motion.at(0.5) * Point3D::new(0.0, 0.0, 2.0);
+        let expected = Matrix::rotation_y(1.5) * Point3D::new(0.0, 0.0, 2.0);
+        assert!((point - expected).magnitude() < 0.0001);
+    }
+
+    #[test]
+    fn test_rays_hit_moving_shapes_where_they_were() {
+        let ball = Moving::new(Arc::new(Sphere), Motion::linear(shift(0.0), shift(10.0)));
+        let ray = Ray::new(Point3D::new(5.0, 0.0, -5.0), Vector3D::new(0.0, 0.0, 1.0));
+        assert!(ball.intersect(&ray).is_empty());
+        let crossings = ball.intersect(&ray.with_time(0.5));
+        assert_eq!(4.0, crossings[0].t);
+        assert_eq!(Vector3D::new(0.0, 0.0, -1.0), crossings[0].normal);
+        assert_eq!(Point3D::new(11.0, 1.0, 1.0), ball.bounds().max());
+
+        // Normals follow the shape as it turns
+        let turning = Moving::new(
+            Arc::new(Sphere),
+            Motion::linear(Matrix::rotation_y(0.0), Matrix::rotation_y(PI / 2.0)),
+        );
+        let crossings = turning.intersect(&ray.with_time(1.0));
+        assert!(crossings.is_empty());
+        let ahead = Ray::new(Point3D::new(0.0, 0.0, -5.0), Vector3D::new(0.0, 0.0, 1.0));
+        let normal = turning.intersect(&ahead.with_time(1.0))[0].normal;
+        assert!((normal - Vector3D::new(0.0, 0.0, -1.0)).magnitude() < 0.0001);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_motion_needs_a_keyframe() {
+        Motion::keyframed(Vec::new());
+    }
+}
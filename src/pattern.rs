@@ -0,0 +1,154 @@
+//! Patterns alternating between two things, like the materials a surface
+//! is painted with, through space. A pattern fills its own space, where each
+//! stripe, ring or checker is one unit across, and is placed on an object by
+//! a transform from the object's space, so it moves along with the object.
+
+use crate::matrix::Matrix;
+use crate::units::{Point3D, Tuple, Unit3D, EPSILON};
+
+/// How a pattern alternates between its two things
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Kind {
+    /// Stripes across x, which stretch along y and z
+    Stripes,
+    /// Rings around the y axis
+    Rings,
+    /// Cubes alternating along every axis, like a checkerboard
+    Checkers,
+}
+
+impl Kind {
+    /// The kind called `name`, as given in a scene
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "stripes" => Some(Kind::Stripes),
+            "rings" => Some(Kind::Rings),
+            "checkers" => Some(Kind::Checkers),
+            _ => None,
+        }
+    }
+}
+
+/// `first` and `second` laid out by a `Kind` of pattern, starting with
+/// `first` at the origin
+#[derive(Debug, Clone)]
+pub struct Pattern<T> {
+    kind: Kind,
+    first: T,
+    second: T,
+    transform: Matrix,
+    inverse: Matrix,
+}
+
+impl<T> Pattern<T> {
+    pub fn new(kind: Kind, first: T, second: T) -> Self {
+        Self {
+            kind,
+            first,
+            second,
+            transform: Matrix::identity(),
+            inverse: Matrix::identity(),
+        }
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    pub fn transform(&self) -> Matrix {
+        self.transform
+    }
+
+    /// Place the pattern by `transform` from the space of the object it
+    /// covers
+    pub fn set_transform(&mut self, transform: Matrix) {
+        if !transform.is_invertible() {
+            panic!("a pattern can't be flattened to nothing!");
+        }
+        self.transform = transform;
+        self.inverse = transform.inverse();
+    }
+
+    /// Which of the two things is at `point`, in the space of the object
+    /// the pattern covers
+    pub fn at(&self, point: Point3D) -> &T {
+        let point = self.inverse * point;
+        let cells = match self.kind {
+            Kind::Stripes => cell(point.x()),
+            Kind::Rings => cell((point.x() * point.x() + point.z() * point.z()).sqrt()),
+            Kind::Checkers => cell(point.x()) + cell(point.y()) + cell(point.z()),
+        };
+        if cells % 2 == 0 {
+            &self.first
+        } else {
+            &self.second
+        }
+    }
+}
+
+/// The unit cell `coordinate` is in. Points found on a surface are a
+/// little off, so those on a cell's edge, like the hits on a plane through
+/// the origin, are nudged into the cell above rather than flickering
+/// between the two.
+fn cell(coordinate: Unit3D) -> i64 {
+    (coordinate + EPSILON).floor() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(pattern: &Pattern<char>, x: Unit3D, y: Unit3D, z: Unit3D) -> char {
+        *pattern.at(Point3D::new(x, y, z))
+    }
+
+    #[test]
+    fn test_stripes_alternate_along_x() {
+        let stripes = Pattern::new(Kind::Stripes, 'a', 'b');
+        assert_eq!('a', at(&stripes, 0.0, 0.0, 0.0));
+        assert_eq!('a', at(&stripes, 0.9, 5.0, -3.0));
+        assert_eq!('b', at(&stripes, 1.0, 0.0, 0.0));
+        assert_eq!('b', at(&stripes, -0.1, 0.0, 0.0));
+        assert_eq!('a', at(&stripes, -1.1, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_rings_alternate_out_from_the_y_axis() {
+        let rings = Pattern::new(Kind::Rings, 'a', 'b');
+        assert_eq!('a', at(&rings, 0.0, 0.0, 0.0));
+        assert_eq!('b', at(&rings, 1.0, 0.0, 0.0));
+        assert_eq!('b', at(&rings, 0.0, 7.0, 1.0));
+        assert_eq!('b', at(&rings, 0.708, 0.0, 0.708));
+        assert_eq!('a', at(&rings, 0.0, 0.0, -2.5));
+    }
+
+    #[test]
+    fn test_checkers_alternate_along_every_axis() {
+        let checkers = Pattern::new(Kind::Checkers, 'a', 'b');
+        assert_eq!('a', at(&checkers, 0.5, 0.5, 0.5));
+        assert_eq!('b', at(&checkers, 1.5, 0.5, 0.5));
+        assert_eq!('b', at(&checkers, 0.5, 1.5, 0.5));
+        assert_eq!('b', at(&checkers, 0.5, 0.5, 1.5));
+        assert_eq!('a', at(&checkers, 1.5, 1.5, 0.5));
+        // Just below a plane through the origin is still on it
+        assert_eq!('a', at(&checkers, 0.5, -0.000001, 0.5));
+    }
+
+    #[test]
+    fn test_patterns_are_placed_by_their_transform() {
+        let mut stripes = Pattern::new(Kind::Stripes, 'a', 'b');
+        stripes.set_transform(Matrix::scaling(Point3D::new(0.25, 1.0, 1.0)));
+        assert_eq!('a', at(&stripes, 0.2, 0.0, 0.0));
+        assert_eq!('b', at(&stripes, 0.3, 0.0, 0.0));
+        assert_eq!(Kind::Stripes, stripes.kind());
+        assert_eq!(Some(Kind::Checkers), Kind::from_name("checkers"));
+        assert_eq!(None, Kind::from_name("plaid"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_patterns_cant_be_flattened() {
+        let mut stripes = Pattern::new(Kind::Stripes, 'a', 'b');
+        stripes.set_transform(Matrix::scaling(Point3D::new(0.0, 1.0, 1.0)));
+    }
+}
@@ -0,0 +1,241 @@
+//! Camera projections, which decide the ray leaving the camera for each
+//! point of the canvas. The camera hands a projection the position on the
+//! canvas as `u` across and `v` up, both running from -1 at one edge to 1
+//! at the other, along with the width of the canvas over its height. Rays
+//! are in the camera's own space, looking down -z with +y up and +x to the
+//! left, and the camera moves them into the world.
+
+use crate::ray::Ray;
+use crate::units::{Point3D, Unit3D, Vector3D};
+use std::f32::consts::PI;
+use std::fmt::Debug;
+
+/// A way of mapping the canvas onto rays
+pub trait Projection: Debug + Send + Sync {
+    /// The ray through canvas position `u`, `v`, or `None` if that part of
+    /// the canvas sees nothing, like the corners of a fisheye image
+    fn ray(&self, u: Unit3D, v: Unit3D, aspect: Unit3D) -> Option<Ray>;
+}
+
+fn origin() -> Point3D {
+    Point3D::new(0.0, 0.0, 0.0)
+}
+
+/// The half width and half height of a view `field_of_view` radians across
+/// its wider side, one unit in front of the camera
+fn half_extent(field_of_view: Unit3D, aspect: Unit3D) -> (Unit3D, Unit3D) {
+    let half_view = (field_of_view / 2.0).tan();
+    if aspect >= 1.0 {
+        (half_view, half_view / aspect)
+    } else {
+        (half_view * aspect, half_view)
+    }
+}
+
+/// The usual pinhole projection, where straight lines stay straight
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Perspective {
+    /// The angle in radians seen across the wider side of the canvas
+    pub field_of_view: Unit3D,
+}
+
+impl Perspective {
+    pub fn new(field_of_view: Unit3D) -> Self {
+        Self { field_of_view }
+    }
+
+    /// The width of a pixel one unit in front of the camera
+    pub fn pixel_size(&self, hsize: usize, vsize: usize) -> Unit3D {
+        let (half_width, _) = half_extent(self.field_of_view, hsize as Unit3D / vsize as Unit3D);
+        half_width * 2.0 / hsize as Unit3D
+    }
+}
+
+impl Projection for Perspective {
+    fn ray(&self, u: Unit3D, v: Unit3D, aspect: Unit3D) -> Option<Ray> {
+        let (half_width, half_height) = half_extent(self.field_of_view, aspect);
+        let direction = Vector3D::new(-u * half_width, v * half_height, -1.0);
+        Some(Ray::new(origin(), direction.normalize()))
+    }
+}
+
+/// Parallel rays with no perspective, so things keep their size however
+/// far away they are
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Orthographic {
+    /// The width of the area seen
+    pub width: Unit3D,
+}
+
+impl Orthographic {
+    pub fn new(width: Unit3D) -> Self {
+        Self { width }
+    }
+}
+
+impl Projection for Orthographic {
+    fn ray(&self, u: Unit3D, v: Unit3D, aspect: Unit3D) -> Option<Ray> {
+        let half_width = self.width / 2.0;
+        let half_height = half_width / aspect;
+        Some(Ray::new(
+            Point3D::new(-u * half_width, v * half_height, 0.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+        ))
+    }
+}
+
+/// An equidistant fisheye, where the angle from the center of view grows
+/// evenly with the distance from the center of the image. The image is a
+/// circle touching the shorter sides of the canvas.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Fisheye {
+    /// The angle in radians seen across the circle, which may be more than
+    /// a half turn
+    pub field_of_view: Unit3D,
+}
+
+impl Fisheye {
+    pub fn new(field_of_view: Unit3D) -> Self {
+        Self { field_of_view }
+    }
+}
+
+impl Projection for Fisheye {
+    fn ray(&self, u: Unit3D, v: Unit3D, aspect: Unit3D) -> Option<Ray> {
+        let x = u * aspect.max(1.0);
+        let y = v * (1.0 / aspect).max(1.0);
+        let radius = (x * x + y * y).sqrt();
+        if radius > 1.0 {
+            return None;
+        }
+        let (sin, cos) = (radius * self.field_of_view / 2.0).sin_cos();
+        let direction = if radius == 0.0 {
+            Vector3D::new(0.0, 0.0, -1.0)
+        } else {
+            Vector3D::new(-x / radius * sin, y / radius * sin, -cos)
+        };
+        Some(Ray::new(origin(), direction))
+    }
+}
+
+/// A spherical panorama, with longitude across the canvas and latitude up
+/// it. Seeing all the way around and from pole to pole makes an
+/// equirectangular image for VR viewers.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Panorama {
+    /// The angle in radians seen across the canvas
+    pub horizontal: Unit3D,
+    /// The angle in radians seen up the canvas
+    pub vertical: Unit3D,
+}
+
+impl Panorama {
+    pub fn new(horizontal: Unit3D, vertical: Unit3D) -> Self {
+        Self {
+            horizontal,
+            vertical,
+        }
+    }
+
+    /// The full sphere, best on a canvas twice as wide as it is high
+    pub fn equirectangular() -> Self {
+        Self::new(2.0 * PI, PI)
+    }
+}
+
+impl Projection for Panorama {
+    fn ray(&self, u: Unit3D, v: Unit3D, _: Unit3D) -> Option<Ray> {
+        let (sin_long, cos_long) = (u * self.horizontal / 2.0).sin_cos();
+        let (sin_lat, cos_lat) = (v * self.vertical / 2.0).sin_cos();
+        let direction = Vector3D::new(-sin_long * cos_lat, sin_lat, -cos_long * cos_lat);
+        Some(Ray::new(origin(), direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Tuple;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn close(a: Unit3D, b: Unit3D) -> bool {
+        (a - b).abs() < 0.0001
+    }
+
+    fn vector_close(a: Vector3D, b: Vector3D) -> bool {
+        close(a.x(), b.x()) && close(a.y(), b.y()) && close(a.z(), b.z())
+    }
+
+    fn direction(projection: &dyn Projection, u: Unit3D, v: Unit3D) -> Vector3D {
+        projection.ray(u, v, 2.0).unwrap().direction
+    }
+
+    #[test]
+    fn test_pixel_size() {
+        assert!(close(
+            0.01,
+            Perspective::new(FRAC_PI_2).pixel_size(200, 125)
+        ));
+        assert!(close(
+            0.01,
+            Perspective::new(FRAC_PI_2).pixel_size(125, 200)
+        ));
+    }
+
+    #[test]
+    fn test_every_projection_looks_ahead_in_the_middle() {
+        let projections: Vec<Box<dyn Projection>> = vec![
+            Box::new(Perspective::new(1.0)),
+            Box::new(Orthographic::new(4.0)),
+            Box::new(Fisheye::new(PI)),
+            Box::new(Panorama::equirectangular()),
+        ];
+        for projection in &projections {
+            let ray = projection.ray(0.0, 0.0, 2.0).unwrap();
+            assert_eq!(origin(), ray.origin);
+            assert!(vector_close(Vector3D::new(0.0, 0.0, -1.0), ray.direction));
+        }
+    }
+
+    #[test]
+    fn test_orthographic_rays_are_parallel() {
+        let projection = Orthographic::new(4.0);
+        let corner = projection.ray(1.0, 1.0, 2.0).unwrap();
+        assert_eq!(Point3D::new(-2.0, 1.0, 0.0), corner.origin);
+        assert_eq!(Vector3D::new(0.0, 0.0, -1.0), corner.direction);
+    }
+
+    #[test]
+    fn test_fisheye_angles_grow_evenly() {
+        let projection = Fisheye::new(PI);
+        // Half way out is 45 degrees, the edge looks sideways
+        let half = 2.0f32.sqrt() / 2.0;
+        assert!(vector_close(
+            Vector3D::new(0.0, half, -half),
+            direction(&projection, 0.0, 0.5)
+        ));
+        assert!(vector_close(
+            Vector3D::new(1.0, 0.0, 0.0),
+            direction(&projection, -0.5, 0.0)
+        ));
+        // Outside the circle
+        assert_eq!(None, projection.ray(0.9, 0.0, 2.0));
+    }
+
+    #[test]
+    fn test_equirectangular_sees_all_around() {
+        let projection = Panorama::equirectangular();
+        assert!(vector_close(
+            Vector3D::new(0.0, 0.0, 1.0),
+            direction(&projection, 1.0, 0.0)
+        ));
+        assert!(vector_close(
+            Vector3D::new(-1.0, 0.0, 0.0),
+            direction(&projection, 0.5, 0.0)
+        ));
+        assert!(vector_close(
+            Vector3D::new(0.0, 1.0, 0.0),
+            direction(&projection, 0.3, 1.0)
+        ));
+    }
+}
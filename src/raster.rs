@@ -0,0 +1,723 @@
+//! 2D drawing primitives for `Canvas`. Everything is drawn in canvas
+//! coordinates, relative to the canvas origin, and composited over the
+//! existing pixels using the alpha channel of the drawing color. Anything
+//! falling outside the canvas is clipped.
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::units::{Point3D, Tuple};
+use std::collections::BTreeMap;
+
+impl Canvas {
+    /// Draw a one pixel wide line between `from` and `to` using Bresenham's
+    /// algorithm.
+    pub fn draw_line(&mut self, from: Point3D, to: Point3D, color: Color) {
+        let points = self.clipped_line_points(self.to_signed(from), self.to_signed(to));
+        self.plot_all(points, color);
+    }
+
+    /// Draw an anti-aliased line between `from` and `to` using Xiaolin Wu's
+    /// algorithm. Pixels straddled by the line are blended in proportion to
+    /// how much of the line covers them.
+    pub fn draw_line_aa(&mut self, from: Point3D, to: Point3D, color: Color) {
+        if self.is_empty() {
+            return;
+        }
+        let (offset_x, offset_y) = self.origin_offset();
+        let (mut x0, mut y0) = (from.x() + offset_x as f32, from.y() + offset_y as f32);
+        let (mut x1, mut y1) = (to.x() + offset_x as f32, to.y() + offset_y as f32);
+
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+
+        // Coverage is accumulated per pixel so pixels touched more than once,
+        // like the ends of a very short line, are only blended once.
+        let mut coverage: BTreeMap<(i64, i64), f32> = BTreeMap::new();
+        let mut plot = |x: f32, y: f32, amount: f32| {
+            let (x, y) = if steep { (y, x) } else { (x, y) };
+            *coverage.entry((x as i64, y as i64)).or_insert(0.0) += amount;
+        };
+
+        let x_start = x0.round();
+        let y_start = y0 + gradient * (x_start - x0);
+        let gap = 1.0 - fract(x0 + 0.5);
+        plot(x_start, y_start.floor(), (1.0 - fract(y_start)) * gap);
+        plot(x_start, y_start.floor() + 1.0, fract(y_start) * gap);
+
+        let x_end = x1.round();
+        let y_end = y1 + gradient * (x_end - x1);
+        let gap = fract(x1 + 0.5);
+        plot(x_end, y_end.floor(), (1.0 - fract(y_end)) * gap);
+        plot(x_end, y_end.floor() + 1.0, fract(y_end) * gap);
+
+        // Only the part of the line across the canvas is stepped along, so
+        // lines reaching far outside it cost no more than ones inside
+        let extent = if steep { self.height() } else { self.width() } as f32;
+        let mut y = y_start + gradient;
+        let mut x = x_start + 1.0;
+        if x < -1.0 {
+            y += gradient * (-1.0 - x);
+            x = -1.0;
+        }
+        let x_last = x_end.min(extent + 1.0);
+        while x < x_last {
+            plot(x, y.floor(), 1.0 - fract(y));
+            plot(x, y.floor() + 1.0, fract(y));
+            y += gradient;
+            x += 1.0;
+        }
+
+        for ((x, y), amount) in coverage {
+            if amount > 0.0 {
+                self.plot(x, y, color.with_alpha(color.a() * amount.min(1.0)));
+            }
+        }
+    }
+
+    /// Draw the outline of the rectangle with opposite corners `a` and `b`.
+    pub fn draw_rect(&mut self, a: Point3D, b: Point3D, color: Color) {
+        let corners = [
+            a,
+            Point3D::new(b.x(), a.y(), 0.0),
+            b,
+            Point3D::new(a.x(), b.y(), 0.0),
+        ];
+        self.draw_polygon(&corners, color);
+    }
+
+    /// Fill the rectangle with opposite corners `a` and `b`, edges included.
+    pub fn fill_rect(&mut self, a: Point3D, b: Point3D, color: Color) {
+        let (ax, ay) = self.to_signed(a);
+        let (bx, by) = self.to_signed(b);
+        // Only the rows on the canvas are visited, however tall the
+        // rectangle is
+        let first = ay.min(by).max(0);
+        let last = ay.max(by).min(self.height() as i64 - 1);
+        for y in first..=last {
+            self.plot_span(ax.min(bx), ax.max(bx), y, color);
+        }
+    }
+
+    /// Draw the outline of a circle of `radius` pixels around `center`.
+    pub fn draw_circle(&mut self, center: Point3D, radius: f32, color: Color) {
+        self.draw_ellipse(center, radius, radius, color);
+    }
+
+    /// Fill a circle of `radius` pixels around `center`.
+    pub fn fill_circle(&mut self, center: Point3D, radius: f32, color: Color) {
+        self.fill_ellipse(center, radius, radius, color);
+    }
+
+    /// Draw the outline of an axis aligned ellipse around `center` using the
+    /// midpoint ellipse algorithm. `radius_x` and `radius_y` are rounded to
+    /// whole pixels.
+    pub fn draw_ellipse(&mut self, center: Point3D, radius_x: f32, radius_y: f32, color: Color) {
+        let (cx, cy) = self.to_signed(center);
+        let rx = radius_x.abs().round() as i64;
+        let ry = radius_y.abs().round() as i64;
+        if self.is_empty() || self.misses(cx, cy, rx, ry) {
+            return;
+        }
+        if rx == 0 || ry == 0 {
+            let points = self.clipped_line_points(
+                (cx.saturating_sub(rx), cy.saturating_sub(ry)),
+                (cx.saturating_add(rx), cy.saturating_add(ry)),
+            );
+            self.plot_all(points, color);
+            return;
+        }
+
+        // Only points on the canvas are kept, so large ellipses crossing it
+        // don't fill memory with points which are never plotted
+        let (width, height) = (self.width() as i64, self.height() as i64);
+        let mut points = Vec::new();
+        let mut quadrants = |x: i64, y: i64| {
+            for &px in &[cx.saturating_add(x), cx.saturating_sub(x)] {
+                for &py in &[cy.saturating_add(y), cy.saturating_sub(y)] {
+                    if (0..width).contains(&px) && (0..height).contains(&py) {
+                        points.push((px, py));
+                    }
+                }
+            }
+        };
+
+        let (rx2, ry2) = ((rx as f64).powi(2), (ry as f64).powi(2));
+        let (mut x, mut y) = (0_i64, ry);
+        let mut dx = 0.0;
+        let mut dy = 2.0 * rx2 * y as f64;
+
+        // Region 1, where the slope of the curve is shallower than -1
+        let mut p = ry2 - rx2 * ry as f64 + 0.25 * rx2;
+        while dx < dy {
+            quadrants(x, y);
+            x += 1;
+            dx += 2.0 * ry2;
+            if p < 0.0 {
+                p += ry2 + dx;
+            } else {
+                y -= 1;
+                dy -= 2.0 * rx2;
+                p += ry2 + dx - dy;
+            }
+        }
+
+        // Region 2, where the slope is steeper
+        let (xf, yf) = (x as f64 + 0.5, (y - 1) as f64);
+        let mut p = ry2 * xf * xf + rx2 * yf * yf - rx2 * ry2;
+        while y >= 0 {
+            quadrants(x, y);
+            y -= 1;
+            dy -= 2.0 * rx2;
+            if p > 0.0 {
+                p += rx2 - dy;
+            } else {
+                x += 1;
+                dx += 2.0 * ry2;
+                p += rx2 - dy + dx;
+            }
+        }
+
+        self.plot_all(points, color);
+    }
+
+    /// Fill an axis aligned ellipse around `center`. A pixel is filled when
+    /// its center falls inside the ellipse.
+    pub fn fill_ellipse(&mut self, center: Point3D, radius_x: f32, radius_y: f32, color: Color) {
+        let (cx, cy) = self.to_signed(center);
+        let (rx, ry) = (radius_x.abs(), radius_y.abs());
+        let rows = ry.floor() as i64;
+        if self.is_empty() || self.misses(cx, cy, rx.floor() as i64, rows) {
+            return;
+        }
+        // Only the rows on the canvas are visited, however large the
+        // ellipse is
+        let first = (-rows).max(cy.saturating_neg());
+        let last = rows.min((self.height() as i64 - 1).saturating_sub(cy));
+        for dy in first..=last {
+            let scale = 1.0 - (dy as f32 / ry).powi(2);
+            let half_width = if ry == 0.0 {
+                rx
+            } else {
+                rx * scale.max(0.0).sqrt()
+            };
+            let half_width = half_width.floor() as i64;
+            self.plot_span(
+                cx.saturating_sub(half_width),
+                cx.saturating_add(half_width),
+                cy + dy,
+                color,
+            );
+        }
+    }
+
+    /// Draw the closed outline of the polygon through `vertices`.
+    pub fn draw_polygon(&mut self, vertices: &[Point3D], color: Color) {
+        let mut points = Vec::new();
+        for (index, vertex) in vertices.iter().enumerate() {
+            let next = vertices[(index + 1) % vertices.len()];
+            points.extend(self.clipped_line_points(self.to_signed(*vertex), self.to_signed(next)));
+        }
+        self.plot_all(points, color);
+    }
+
+    /// Fill the polygon through `vertices` with a scanline fill using the
+    /// even-odd rule. A pixel is filled when its center is inside the
+    /// polygon, so polygons sharing an edge never fill the same pixel.
+    /// Polygons with a vertex which isn't a finite number fill nothing.
+    pub fn fill_polygon(&mut self, vertices: &[Point3D], color: Color) {
+        if vertices.len() < 3
+            || vertices
+                .iter()
+                .any(|v| !v.x().is_finite() || !v.y().is_finite())
+        {
+            return;
+        }
+
+        let (offset_x, offset_y) = self.origin_offset();
+        let shifted: Vec<(f32, f32)> = vertices
+            .iter()
+            .map(|v| (v.x() + offset_x as f32, v.y() + offset_y as f32))
+            .collect();
+
+        let min_y = shifted.iter().map(|v| v.1).fold(f32::INFINITY, f32::min);
+        let max_y = shifted
+            .iter()
+            .map(|v| v.1)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let first_row = min_y.ceil().max(0.0) as i64;
+        let last_row = max_y.ceil().min(self.height() as f32) as i64;
+
+        let mut crossings: Vec<f32> = Vec::new();
+        for y in first_row..last_row {
+            let scan = y as f32;
+            crossings.clear();
+            for (index, &(x0, y0)) in shifted.iter().enumerate() {
+                let (x1, y1) = shifted[(index + 1) % shifted.len()];
+                // Half-open so a vertex on the scanline is only counted once
+                if (y0 <= scan && scan < y1) || (y1 <= scan && scan < y0) {
+                    crossings.push(x0 + (scan - y0) * (x1 - x0) / (y1 - y0));
+                }
+            }
+            crossings.sort_by(|a, b| a.total_cmp(b));
+
+            for pair in crossings.chunks(2) {
+                if let [start, end] = pair {
+                    let first = start.ceil() as i64;
+                    let last = end.ceil() as i64 - 1;
+                    self.plot_span(first, last, y, color);
+                }
+            }
+        }
+    }
+
+    /// Round a point in canvas coordinates to signed pixel coordinates.
+    /// Coordinates too large for an `i64` are held at its limits.
+    pub(crate) fn to_signed(&self, point: Point3D) -> (i64, i64) {
+        let (offset_x, offset_y) = self.origin_offset();
+        (
+            (point.x().round() as i64).saturating_add(offset_x),
+            (point.y().round() as i64).saturating_add(offset_y),
+        )
+    }
+
+    /// The pixels of the Bresenham line between two signed pixel positions
+    /// which fall on the canvas. The line is clipped to the canvas first,
+    /// so lines reaching far beyond it take no more time or memory than
+    /// ones inside it.
+    fn clipped_line_points(&self, from: (i64, i64), to: (i64, i64)) -> Vec<(i64, i64)> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        let max = ((self.width() - 1) as f64, (self.height() - 1) as f64);
+        let from = (from.0 as f64, from.1 as f64);
+        let to = (to.0 as f64, to.1 as f64);
+        match clip_segment(from, to, max) {
+            Some((from, to)) => line_points(
+                (from.0.round() as i64, from.1.round() as i64),
+                (to.0.round() as i64, to.1.round() as i64),
+            ),
+            None => Vec::new(),
+        }
+    }
+
+    /// Whether the canvas has no pixels to draw on
+    fn is_empty(&self) -> bool {
+        self.width() == 0 || self.height() == 0
+    }
+
+    /// Whether the box `rx` by `ry` pixels either side of `cx`, `cy` is
+    /// entirely off the canvas
+    fn misses(&self, cx: i64, cy: i64, rx: i64, ry: i64) -> bool {
+        cx.saturating_add(rx) < 0
+            || cy.saturating_add(ry) < 0
+            || cx.saturating_sub(rx) >= self.width() as i64
+            || cy.saturating_sub(ry) >= self.height() as i64
+    }
+
+    /// Blend `color` into the pixel at signed pixel coordinates, if any
+    pub(crate) fn plot(&mut self, x: i64, y: i64, color: Color) {
+        if let Some(pixel) = self.signed_pixel(x, y) {
+            self.blend_pixel(pixel, color);
+        }
+    }
+
+    /// Blend `color` into every pixel in `points` exactly once, so
+    /// translucent outlines don't darken where their segments meet.
+    fn plot_all(&mut self, mut points: Vec<(i64, i64)>, color: Color) {
+        points.sort_unstable();
+        points.dedup();
+        for (x, y) in points {
+            self.plot(x, y, color);
+        }
+    }
+
+    /// Blend `color` into the pixels from `first` to `last` inclusive on
+    /// row `y`, clipped to the canvas.
+    pub(crate) fn plot_span(&mut self, first: i64, last: i64, y: i64, color: Color) {
+        let first = first.max(0);
+        let last = last.min(self.width() as i64 - 1);
+        for x in first..=last {
+            self.plot(x, y, color);
+        }
+    }
+}
+
+/// The pixels on a Bresenham line between two signed pixel positions,
+/// both ends included.
+fn line_points(from: (i64, i64), to: (i64, i64)) -> Vec<(i64, i64)> {
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let step_x = if x < to.0 { 1 } else { -1 };
+    let step_y = if y < to.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    let mut points = Vec::with_capacity((dx - dy + 1) as usize);
+    loop {
+        points.push((x, y));
+        if (x, y) == to {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+    points
+}
+
+/// The part of the segment between `from` and `to` inside the rectangle
+/// from the origin to `max`, using the Liang-Barsky algorithm, or `None` if
+/// it misses the rectangle
+fn clip_segment(
+    from: (f64, f64),
+    to: (f64, f64),
+    max: (f64, f64),
+) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let (mut enter, mut leave) = (0.0_f64, 1.0_f64);
+    // Each edge as the rate the segment moves towards the outside of it
+    // and how far inside it the segment starts
+    for &(towards, inside) in &[
+        (-dx, from.0),
+        (dx, max.0 - from.0),
+        (-dy, from.1),
+        (dy, max.1 - from.1),
+    ] {
+        if towards == 0.0 {
+            if inside < 0.0 {
+                return None;
+            }
+        } else {
+            let t = inside / towards;
+            if towards < 0.0 {
+                enter = enter.max(t);
+            } else {
+                leave = leave.min(t);
+            }
+        }
+    }
+    if enter > leave {
+        return None;
+    }
+    Some((
+        (from.0 + dx * enter, from.1 + dy * enter),
+        (from.0 + dx * leave, from.1 + dy * leave),
+    ))
+}
+
+fn fract(value: f32) -> f32 {
+    value - value.floor()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Origin, Pixel};
+
+    fn white() -> Color {
+        Color::rgb(1.0, 1.0, 1.0)
+    }
+
+    fn is_lit(canvas: &Canvas, x: usize, y: usize) -> bool {
+        canvas[Pixel::new(x, y)].as_rgb_bytes() != [0, 0, 0]
+    }
+
+    fn lit_count(canvas: &Canvas) -> usize {
+        let mut count = 0;
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                if is_lit(canvas, x, y) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn test_bresenham_line_includes_both_ends() {
+        let points = line_points((0, 0), (4, 2));
+        assert_eq!(Some(&(0, 0)), points.first());
+        assert_eq!(Some(&(4, 2)), points.last());
+        assert_eq!(5, points.len());
+    }
+
+    #[test]
+    fn test_draws_a_diagonal_line() {
+        let mut canvas = Canvas::new(5, 5);
+        canvas.draw_line(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(4.0, 4.0, 0.0),
+            white(),
+        );
+        for step in 0..5 {
+            assert!(is_lit(&canvas, step, step));
+        }
+        assert_eq!(5, lit_count(&canvas));
+    }
+
+    #[test]
+    fn test_lines_are_clipped_to_the_canvas() {
+        let mut canvas = Canvas::new(5, 5);
+        canvas.draw_line(
+            Point3D::new(-10.0, 2.0, 0.0),
+            Point3D::new(10.0, 2.0, 0.0),
+            white(),
+        );
+        assert_eq!(5, lit_count(&canvas));
+    }
+
+    #[test]
+    fn test_lines_reaching_far_off_the_canvas() {
+        let mut canvas = Canvas::new(10, 10);
+        canvas.draw_line(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(3e9, 1.0, 0.0),
+            white(),
+        );
+        assert_eq!(10, lit_count(&canvas));
+        canvas.draw_line(
+            Point3D::new(-1e6, -1e6, 0.0),
+            Point3D::new(1e6, 1e6, 0.0),
+            white(),
+        );
+        assert!(is_lit(&canvas, 5, 5));
+        // Beyond what fits in a pixel position lines still end
+        canvas.draw_line(
+            Point3D::new(-1e30, 50.0, 0.0),
+            Point3D::new(1e30, -1e30, 0.0),
+            white(),
+        );
+        canvas.draw_line_aa(
+            Point3D::new(0.0, 8.0, 0.0),
+            Point3D::new(3e9, 8.0, 0.0),
+            white(),
+        );
+        assert!(is_lit(&canvas, 9, 8));
+        assert_eq!(
+            ((0.0, 2.0), (4.0, 2.0)),
+            clip_segment((-4.0, 2.0), (8.0, 2.0), (4.0, 4.0)).unwrap()
+        );
+        assert_eq!(None, clip_segment((-4.0, 5.0), (8.0, 5.0), (4.0, 4.0)));
+    }
+
+    #[test]
+    fn test_lines_follow_the_canvas_origin() {
+        let mut canvas = Canvas::with_origin(5, 5, Origin::Center);
+        canvas.draw_line(
+            Point3D::new(0.0, -2.0, 0.0),
+            Point3D::new(0.0, 2.0, 0.0),
+            white(),
+        );
+        for y in 0..5 {
+            assert!(is_lit(&canvas, 2, y));
+        }
+        assert_eq!(5, lit_count(&canvas));
+    }
+
+    #[test]
+    fn test_translucent_outlines_blend_each_pixel_once() {
+        let mut canvas = Canvas::new(5, 5);
+        let square = [
+            Point3D::new(1.0, 1.0, 0.0),
+            Point3D::new(3.0, 1.0, 0.0),
+            Point3D::new(3.0, 3.0, 0.0),
+            Point3D::new(1.0, 3.0, 0.0),
+        ];
+        canvas.draw_polygon(&square, Color::rgba(1.0, 1.0, 1.0, 0.5));
+        assert_eq!([128, 128, 128], canvas[Pixel::new(1, 1)].as_rgb_bytes());
+        assert_eq!([128, 128, 128], canvas[Pixel::new(2, 1)].as_rgb_bytes());
+        assert_eq!(8, lit_count(&canvas));
+    }
+
+    #[test]
+    fn test_anti_aliased_horizontal_line_is_solid_between_its_ends() {
+        let mut canvas = Canvas::new(5, 3);
+        canvas.draw_line_aa(
+            Point3D::new(0.0, 1.0, 0.0),
+            Point3D::new(4.0, 1.0, 0.0),
+            white(),
+        );
+        for x in 1..4 {
+            assert_eq!([255, 255, 255], canvas[Pixel::new(x, 1)].as_rgb_bytes());
+        }
+        // The line ends at the pixel centers so only covers half of each
+        assert_eq!([128, 128, 128], canvas[Pixel::new(0, 1)].as_rgb_bytes());
+        assert_eq!([128, 128, 128], canvas[Pixel::new(4, 1)].as_rgb_bytes());
+        assert_eq!(5, lit_count(&canvas));
+    }
+
+    #[test]
+    fn test_anti_aliased_line_between_pixels_shares_coverage() {
+        let mut canvas = Canvas::new(5, 3);
+        canvas.draw_line_aa(
+            Point3D::new(0.0, 0.5, 0.0),
+            Point3D::new(4.0, 0.5, 0.0),
+            white(),
+        );
+        assert_eq!([128, 128, 128], canvas[Pixel::new(2, 0)].as_rgb_bytes());
+        assert_eq!([128, 128, 128], canvas[Pixel::new(2, 1)].as_rgb_bytes());
+        assert!(!is_lit(&canvas, 2, 2));
+    }
+
+    #[test]
+    fn test_draws_a_circle_outline() {
+        let mut canvas = Canvas::new(11, 11);
+        canvas.draw_circle(Point3D::new(5.0, 5.0, 0.0), 3.0, white());
+        assert!(is_lit(&canvas, 8, 5));
+        assert!(is_lit(&canvas, 2, 5));
+        assert!(is_lit(&canvas, 5, 8));
+        assert!(is_lit(&canvas, 5, 2));
+        assert!(!is_lit(&canvas, 5, 5));
+    }
+
+    #[test]
+    fn test_fills_a_circle() {
+        let mut canvas = Canvas::new(11, 11);
+        canvas.fill_circle(Point3D::new(5.0, 5.0, 0.0), 1.0, white());
+        assert!(is_lit(&canvas, 5, 5));
+        assert_eq!(5, lit_count(&canvas));
+    }
+
+    #[test]
+    fn test_draws_an_ellipse_outline() {
+        let mut canvas = Canvas::new(11, 11);
+        canvas.draw_ellipse(Point3D::new(5.0, 5.0, 0.0), 4.0, 2.0, white());
+        assert!(is_lit(&canvas, 9, 5));
+        assert!(is_lit(&canvas, 1, 5));
+        assert!(is_lit(&canvas, 5, 7));
+        assert!(is_lit(&canvas, 5, 3));
+        assert!(!is_lit(&canvas, 5, 8));
+    }
+
+    #[test]
+    fn test_fills_a_rectangle_including_its_edges() {
+        let mut canvas = Canvas::new(10, 10);
+        canvas.fill_rect(
+            Point3D::new(6.0, 4.0, 0.0),
+            Point3D::new(2.0, 1.0, 0.0),
+            white(),
+        );
+        assert_eq!(20, lit_count(&canvas));
+        assert!(is_lit(&canvas, 2, 1));
+        assert!(is_lit(&canvas, 6, 4));
+    }
+
+    #[test]
+    fn test_draws_a_rectangle_outline() {
+        let mut canvas = Canvas::new(10, 10);
+        canvas.draw_rect(
+            Point3D::new(2.0, 1.0, 0.0),
+            Point3D::new(6.0, 4.0, 0.0),
+            white(),
+        );
+        assert_eq!(14, lit_count(&canvas));
+        assert!(!is_lit(&canvas, 4, 2));
+    }
+
+    #[test]
+    fn test_fills_a_triangle() {
+        let mut canvas = Canvas::new(10, 10);
+        let triangle = [
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(4.0, 0.0, 0.0),
+            Point3D::new(0.0, 4.0, 0.0),
+        ];
+        canvas.fill_polygon(&triangle, white());
+        assert!(is_lit(&canvas, 0, 0));
+        assert!(is_lit(&canvas, 1, 2));
+        assert!(!is_lit(&canvas, 3, 3));
+        assert!(!is_lit(&canvas, 4, 0));
+
+        let mut canvas = Canvas::new(10, 10);
+        let broken = [
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(f32::NAN, 0.0, 0.0),
+            Point3D::new(0.0, 4.0, 0.0),
+        ];
+        canvas.fill_polygon(&broken, white());
+        assert_eq!(0, lit_count(&canvas));
+    }
+
+    #[test]
+    fn test_polygons_sharing_an_edge_do_not_overlap() {
+        let mut canvas = Canvas::new(10, 10);
+        let translucent = Color::rgba(1.0, 1.0, 1.0, 0.5);
+        let left = [
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(5.0, 0.0, 0.0),
+            Point3D::new(5.0, 5.0, 0.0),
+            Point3D::new(0.0, 5.0, 0.0),
+        ];
+        let right = [
+            Point3D::new(5.0, 0.0, 0.0),
+            Point3D::new(10.0, 0.0, 0.0),
+            Point3D::new(10.0, 5.0, 0.0),
+            Point3D::new(5.0, 5.0, 0.0),
+        ];
+        canvas.fill_polygon(&left, translucent);
+        canvas.fill_polygon(&right, translucent);
+        for x in 0..10 {
+            assert_eq!([128, 128, 128], canvas[Pixel::new(x, 2)].as_rgb_bytes());
+        }
+    }
+
+    #[test]
+    fn test_drawing_on_an_empty_canvas_does_nothing() {
+        let mut canvas = Canvas::new(0, 0);
+        let (a, b) = (Point3D::new(-3.0, -3.0, 0.0), Point3D::new(3.0, 3.0, 0.0));
+        canvas.draw_line(a, b, white());
+        canvas.draw_line_aa(a, b, white());
+        canvas.draw_rect(a, b, white());
+        canvas.fill_rect(a, b, white());
+        canvas.draw_circle(a, 2.0, white());
+        canvas.fill_circle(a, 2.0, white());
+        canvas.draw_ellipse(a, 0.0, 2.0, white());
+        canvas.draw_polygon(&[a, b, Point3D::new(3.0, -3.0, 0.0)], white());
+        canvas.fill_polygon(&[a, b, Point3D::new(3.0, -3.0, 0.0)], white());
+    }
+
+    #[test]
+    fn test_far_off_shapes_are_clipped_without_overflowing() {
+        let mut canvas = Canvas::new(8, 8);
+        for &far in &[1e30, -1e30] {
+            let center = Point3D::new(far, 0.0, 0.0);
+            canvas.draw_circle(center, 3.0, white());
+            canvas.fill_circle(center, 3.0, white());
+            canvas.draw_ellipse(center, 0.0, 3.0, white());
+            canvas.fill_rect(center, Point3D::new(far, far, 0.0), white());
+        }
+        assert_eq!(0, lit_count(&canvas));
+    }
+
+    #[test]
+    fn test_huge_shapes_only_visit_the_canvas() {
+        let mut canvas = Canvas::new(8, 8);
+        canvas.fill_circle(Point3D::new(4.0, 4.0, 0.0), 1e9, white());
+        assert_eq!(64, lit_count(&canvas));
+
+        let mut canvas = Canvas::new(8, 8);
+        let (a, b) = (
+            Point3D::new(-1e12, -1e12, 0.0),
+            Point3D::new(1e12, 1e12, 0.0),
+        );
+        canvas.fill_rect(a, b, white());
+        assert_eq!(64, lit_count(&canvas));
+    }
+}
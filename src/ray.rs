@@ -0,0 +1,72 @@
+//! Rays, the half lines cast from the camera and from surfaces towards
+//! lights.
+
+use crate::matrix::Matrix;
+use crate::units::{Point3D, Unit3D, Vector3D};
+
+/// A ray starting at `origin` and heading along `direction`, cast at `time`
+/// while the shutter is open
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Point3D,
+    pub direction: Vector3D,
+    pub time: Unit3D,
+}
+
+impl Ray {
+    /// A ray cast at time 0
+    pub fn new(origin: Point3D, direction: Vector3D) -> Self {
+        Self {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    /// The same ray cast at `time`
+    pub fn with_time(self, time: Unit3D) -> Self {
+        Self { time, ..self }
+    }
+
+    /// The point at distance `t` along the ray
+    pub fn position(&self, t: Unit3D) -> Point3D {
+        self.origin + self.direction * t
+    }
+
+    /// The ray moved by `transform`
+    pub fn transform(&self, transform: &Matrix) -> Self {
+        Self {
+            origin: *transform * self.origin,
+            direction: *transform * self.direction,
+            time: self.time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_computes_points_along_a_ray() {
+        let ray = Ray::new(Point3D::new(2.0, 3.0, 4.0), Vector3D::new(1.0, 0.0, 0.0));
+        assert_eq!(Point3D::new(2.0, 3.0, 4.0), ray.position(0.0));
+        assert_eq!(Point3D::new(3.0, 3.0, 4.0), ray.position(1.0));
+        assert_eq!(Point3D::new(4.5, 3.0, 4.0), ray.position(2.5));
+    }
+
+    #[test]
+    fn test_transforms_a_ray() {
+        let ray = Ray::new(Point3D::new(1.0, 2.0, 3.0), Vector3D::new(0.0, 1.0, 0.0));
+        let moved = ray
+            .with_time(0.5)
+            .transform(&Matrix::translation(Point3D::new(3.0, 4.0, 5.0)));
+        assert_eq!(Point3D::new(4.0, 6.0, 8.0), moved.origin);
+        assert_eq!(Vector3D::new(0.0, 1.0, 0.0), moved.direction);
+        assert_eq!(0.5, moved.time);
+
+        let scaled = ray.transform(&Matrix::scaling(Point3D::new(2.0, 3.0, 4.0)));
+        assert_eq!(Point3D::new(2.0, 6.0, 12.0), scaled.origin);
+        assert_eq!(Vector3D::new(0.0, 3.0, 0.0), scaled.direction);
+    }
+}
@@ -0,0 +1,276 @@
+//! Rendering a `Canvas` one pixel at a time, either serially or spread over
+//! several threads. The canvas is split into tiles which the threads take
+//! from a shared counter as they finish, so a thread stuck on an expensive
+//! tile doesn't hold up the rest. Every pixel gets its own random number
+//! generator seeded from its position, which makes the result the same
+//! whichever thread renders it and in whatever order.
+
+use crate::canvas::{Canvas, Pixel};
+use crate::color::Color;
+use crate::view::CanvasViewMut;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// A small, fast pseudo random number generator (SplitMix64). Not suitable
+/// for anything but sampling.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// The generator for `pixel` in a render seeded with `seed`
+    pub fn for_pixel(seed: u64, pixel: Pixel) -> Self {
+        let mut rng = Self(seed ^ (pixel.x() as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        rng.0 ^= rng.next_u64() ^ (pixel.y() as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+        rng
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in the range [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Renders a canvas by calling a shading function for every pixel
+#[derive(PartialEq, Debug, Clone)]
+pub struct Renderer {
+    threads: usize,
+    tile_size: usize,
+    seed: u64,
+}
+
+impl Renderer {
+    /// A renderer using one thread per available CPU and 16x16 tiles
+    pub fn new(seed: u64) -> Self {
+        let threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        Self {
+            threads,
+            tile_size: 16,
+            seed,
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        if threads == 0 {
+            panic!("a renderer needs at least one thread!");
+        }
+        self.threads = threads;
+    }
+
+    pub fn tile_size(&self) -> usize {
+        self.tile_size
+    }
+
+    pub fn set_tile_size(&mut self, tile_size: usize) {
+        if tile_size == 0 {
+            panic!("tiles must be at least 1x1!");
+        }
+        self.tile_size = tile_size;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Set every pixel of `canvas` to the color returned by `shade` on the
+    /// calling thread.
+    pub fn render_serial<F>(&self, canvas: &mut Canvas, shade: F)
+    where
+        F: Fn(Pixel, &mut Rng) -> Color,
+    {
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                let pixel = Pixel::new(x, y);
+                canvas[pixel] = shade(pixel, &mut Rng::for_pixel(self.seed, pixel));
+            }
+        }
+    }
+
+    /// Set every pixel of `canvas` to the color returned by `shade`, sharing
+    /// the tiles between the renderer's threads. The result is identical to
+    /// `render_serial` with the same seed.
+    pub fn render<F>(&self, canvas: &mut Canvas, shade: F)
+    where
+        F: Fn(Pixel, &mut Rng) -> Color + Sync,
+    {
+        self.render_with_progress(canvas, shade, |_, _| {});
+    }
+
+    /// Render like `render`, calling `progress` with the number of tiles
+    /// finished so far and the total each time a tile is finished.
+    pub fn render_with_progress<F, P>(&self, canvas: &mut Canvas, shade: F, progress: P)
+    where
+        F: Fn(Pixel, &mut Rng) -> Color + Sync,
+        P: Fn(usize, usize) + Sync,
+    {
+        let tiles: Vec<Mutex<Option<CanvasViewMut>>> = canvas
+            .tiles_mut(self.tile_size, self.tile_size)
+            .into_iter()
+            .map(|view| Mutex::new(Some(view)))
+            .collect();
+        self.share_work(tiles.len(), &progress, |index| {
+            if let Some(mut view) = tiles[index].lock().unwrap().take() {
+                self.render_tile(&mut view, &shade);
+            }
+        });
+    }
+
+    /// Call `work` once for every index below `count`, spread over the
+    /// renderer's threads. Each thread takes the next index from a shared
+    /// counter as soon as it's free.
+    pub(crate) fn share_work<W, P>(&self, count: usize, progress: &P, work: W)
+    where
+        W: Fn(usize) + Sync,
+        P: Fn(usize, usize) + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let finished = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(count) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= count {
+                        break;
+                    }
+                    work(index);
+                    let done = finished.fetch_add(1, Ordering::Relaxed) + 1;
+                    progress(done, count);
+                });
+            }
+        });
+    }
+
+    fn render_tile<F>(&self, view: &mut CanvasViewMut, shade: &F)
+    where
+        F: Fn(Pixel, &mut Rng) -> Color,
+    {
+        for y in 0..view.height() {
+            for x in 0..view.width() {
+                let local = Pixel::new(x, y);
+                let pixel = view.to_canvas(local);
+                view[local] = shade(pixel, &mut Rng::for_pixel(self.seed, pixel));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(pixel: Pixel, rng: &mut Rng) -> Color {
+        let shade = (pixel.x() + pixel.y()) as f32 / 100.0;
+        Color::rgb(shade, rng.next_f32(), rng.next_f32())
+    }
+
+    fn pixels(canvas: &Canvas) -> Vec<[u8; 3]> {
+        let mut pixels = Vec::new();
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                pixels.push(canvas[Pixel::new(x, y)].as_rgb_bytes());
+            }
+        }
+        pixels
+    }
+
+    /// The exact bits of every channel of every pixel, so even differences
+    /// too small to change a byte are caught
+    fn exact_pixels(canvas: &Canvas) -> Vec<[u32; 4]> {
+        let mut pixels = Vec::new();
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                let color = canvas[Pixel::new(x, y)];
+                pixels.push([
+                    color.r().to_bits(),
+                    color.g().to_bits(),
+                    color.b().to_bits(),
+                    color.a().to_bits(),
+                ]);
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn test_random_numbers_are_in_the_unit_range() {
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            let n = rng.next_f32();
+            assert!((0.0..1.0).contains(&n));
+        }
+    }
+
+    #[test]
+    fn test_pixels_get_different_generators() {
+        let a = Rng::for_pixel(1, Pixel::new(0, 1)).next_u64();
+        let b = Rng::for_pixel(1, Pixel::new(1, 0)).next_u64();
+        let c = Rng::for_pixel(2, Pixel::new(0, 1)).next_u64();
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a, Rng::for_pixel(1, Pixel::new(0, 1)).next_u64());
+    }
+
+    #[test]
+    fn test_parallel_render_matches_serial_render() {
+        let mut renderer = Renderer::new(42);
+        renderer.set_tile_size(7);
+        let mut serial = Canvas::new(37, 23);
+        renderer.render_serial(&mut serial, noise);
+
+        for &threads in &[1, 2, 5] {
+            renderer.set_threads(threads);
+            let mut parallel = Canvas::new(37, 23);
+            renderer.render(&mut parallel, noise);
+            assert_eq!(exact_pixels(&serial), exact_pixels(&parallel));
+        }
+    }
+
+    #[test]
+    fn test_more_threads_than_tiles() {
+        let mut renderer = Renderer::new(0);
+        renderer.set_threads(16);
+        let mut canvas = Canvas::new(3, 3);
+        renderer.render(&mut canvas, |_, _| Color::rgb(1.0, 1.0, 1.0));
+        assert!(pixels(&canvas).iter().all(|&p| p == [255, 255, 255]));
+    }
+
+    #[test]
+    fn test_reports_progress_for_every_tile() {
+        let mut renderer = Renderer::new(0);
+        renderer.set_threads(3);
+        renderer.set_tile_size(4);
+        let calls = Mutex::new(Vec::new());
+        let mut canvas = Canvas::new(10, 10);
+        renderer.render_with_progress(&mut canvas, noise, |done, total| {
+            calls.lock().unwrap().push((done, total));
+        });
+        let mut calls = calls.into_inner().unwrap();
+        calls.sort();
+        let expected: Vec<(usize, usize)> = (1..=9).map(|done| (done, 9)).collect();
+        assert_eq!(expected, calls);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_renderers_need_a_thread() {
+        Renderer::new(0).set_threads(0);
+    }
+}
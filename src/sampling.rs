@@ -0,0 +1,675 @@
+//! Supersampling. Each pixel is shaded at several points spread over its
+//! area by a sample `Pattern`, and the results are blended back into pixels
+//! by a reconstruction `PixelFilter` on a `Film`. Filters wider than a pixel
+//! let each sample contribute to its neighbours too, which smooths edges
+//! without blurring them as much as a wide box would.
+
+use crate::canvas::{Canvas, Pixel};
+use crate::color::{Color, Spectrum};
+use crate::render::{Renderer, Rng};
+use crate::tonemap::ToneMap;
+use crate::view::Rect;
+use std::sync::Mutex;
+
+/// How the sample points within a pixel are chosen
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Pattern {
+    /// The centers of a regular grid of cells
+    Grid,
+    /// A random point within each cell of a regular grid
+    Jittered,
+    /// The Halton sequence in bases 2 and 3, randomly shifted per pixel
+    Halton,
+    /// The Sobol sequence, randomly scrambled per pixel
+    Sobol,
+    /// Points chosen to be as far from each other as possible (Mitchell's
+    /// best candidate algorithm), so there are no clumps or gaps
+    BlueNoise,
+}
+
+impl Pattern {
+    /// The pattern called `name`, as given on a command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "grid" => Some(Pattern::Grid),
+            "jittered" | "stratified" => Some(Pattern::Jittered),
+            "halton" => Some(Pattern::Halton),
+            "sobol" => Some(Pattern::Sobol),
+            "blue-noise" => Some(Pattern::BlueNoise),
+            _ => None,
+        }
+    }
+
+    /// `count` sample points in the unit square. Grid patterns use the most
+    /// square grid with exactly `count` cells, so every part of the pixel
+    /// is sampled equally, though counts without a factor near their square
+    /// root give long thin cells.
+    pub fn points(&self, count: usize, rng: &mut Rng) -> Vec<(f32, f32)> {
+        match self {
+            Pattern::Grid | Pattern::Jittered => {
+                let rows = (1..=count)
+                    .take_while(|rows| rows * rows <= count)
+                    .filter(|&rows| count.is_multiple_of(rows))
+                    .last()
+                    .unwrap_or(1);
+                let columns = count / rows;
+                (0..count)
+                    .map(|i| {
+                        let (jx, jy) = match self {
+                            Pattern::Grid => (0.5, 0.5),
+                            _ => (rng.next_f32(), rng.next_f32()),
+                        };
+                        (
+                            ((i % columns) as f32 + jx) / columns as f32,
+                            ((i / columns) as f32 + jy) / rows as f32,
+                        )
+                    })
+                    .collect()
+            }
+            Pattern::Halton => {
+                let (sx, sy) = (rng.next_f32(), rng.next_f32());
+                (1..=count)
+                    .map(|i| {
+                        (
+                            wrap(radical_inverse(i, 2) + sx),
+                            wrap(radical_inverse(i, 3) + sy),
+                        )
+                    })
+                    .collect()
+            }
+            Pattern::Sobol => {
+                let (sx, sy) = (rng.next_u64() as u32, rng.next_u64() as u32);
+                (0..count as u32)
+                    .map(|i| (unit(i.reverse_bits() ^ sx), unit(sobol_second(i) ^ sy)))
+                    .collect()
+            }
+            Pattern::BlueNoise => best_candidates(count, rng),
+        }
+    }
+}
+
+/// The digits of `index` in `base` mirrored around the decimal point
+fn radical_inverse(mut index: usize, base: usize) -> f32 {
+    let mut result = 0.0;
+    let mut scale = 1.0 / base as f32;
+    while index > 0 {
+        result += (index % base) as f32 * scale;
+        index /= base;
+        scale /= base as f32;
+    }
+    result
+}
+
+/// The second dimension of the Sobol sequence as 32 bit fractions
+fn sobol_second(mut index: u32) -> u32 {
+    let mut direction = 1 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+fn unit(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1u32 << 24) as f32
+}
+
+fn wrap(value: f32) -> f32 {
+    value - value.floor()
+}
+
+/// Add points one at a time, each the candidate furthest from the points
+/// already chosen out of several random ones. Distances wrap around the
+/// edges so neighbouring pixels tile without seams.
+fn best_candidates(count: usize, rng: &mut Rng) -> Vec<(f32, f32)> {
+    const CANDIDATES_PER_POINT: usize = 8;
+    let mut points: Vec<(f32, f32)> = Vec::with_capacity(count);
+    while points.len() < count {
+        let candidates = CANDIDATES_PER_POINT * points.len() + 1;
+        let best = (0..candidates)
+            .map(|_| (rng.next_f32(), rng.next_f32()))
+            .map(|candidate| {
+                let nearest = points
+                    .iter()
+                    .map(|&point| toroidal_distance(candidate, point))
+                    .fold(f32::INFINITY, f32::min);
+                (candidate, nearest)
+            })
+            .fold(((0.0, 0.0), -1.0), |best, next| {
+                if next.1 > best.1 {
+                    next
+                } else {
+                    best
+                }
+            });
+        points.push(best.0);
+    }
+    points
+}
+
+fn toroidal_distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    let dx = (a.0 - b.0).abs();
+    let dy = (a.1 - b.1).abs();
+    let dx = dx.min(1.0 - dx);
+    let dy = dy.min(1.0 - dy);
+    dx * dx + dy * dy
+}
+
+/// How much a sample counts towards a pixel by its distance from the pixel
+/// center
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PixelFilter {
+    /// Equal weight for every sample inside the pixel
+    Box,
+    /// Weight falling linearly to nothing one pixel away
+    Tent,
+    /// A Gaussian bell, cut off 1.5 pixels away
+    Gaussian,
+    /// The Mitchell-Netravali cubic with B = C = 1/3. Slightly negative
+    /// lobes keep edges sharp.
+    Mitchell,
+}
+
+impl PixelFilter {
+    /// The filter called `name`, as given on a command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "box" => Some(PixelFilter::Box),
+            "tent" | "triangle" => Some(PixelFilter::Tent),
+            "gaussian" => Some(PixelFilter::Gaussian),
+            "mitchell" => Some(PixelFilter::Mitchell),
+            _ => None,
+        }
+    }
+
+    /// How far from a pixel center, in pixels, samples still count
+    pub fn radius(&self) -> f32 {
+        match self {
+            PixelFilter::Box => 0.5,
+            PixelFilter::Tent => 1.0,
+            PixelFilter::Gaussian => 1.5,
+            PixelFilter::Mitchell => 2.0,
+        }
+    }
+
+    /// The weight of a sample `dx`, `dy` pixels from a pixel center
+    pub fn weight(&self, dx: f32, dy: f32) -> f32 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, d: f32) -> f32 {
+        let d = d.abs();
+        if d > self.radius() {
+            return 0.0;
+        }
+        match self {
+            PixelFilter::Box => 1.0,
+            PixelFilter::Tent => 1.0 - d,
+            PixelFilter::Gaussian => {
+                let falloff = |x: f32| (-2.0 * x * x).exp();
+                (falloff(d) - falloff(self.radius())).max(0.0)
+            }
+            PixelFilter::Mitchell => {
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+                let weight = if d < 1.0 {
+                    (12.0 - 9.0 * b - 6.0 * c) * d * d * d
+                        + (-18.0 + 12.0 * b + 6.0 * c) * d * d
+                        + (6.0 - 2.0 * b)
+                } else {
+                    (-b - 6.0 * c) * d * d * d
+                        + (6.0 * b + 30.0 * c) * d * d
+                        + (-12.0 * b - 48.0 * c) * d
+                        + (8.0 * b + 24.0 * c)
+                };
+                weight / 6.0
+            }
+        }
+    }
+}
+
+/// A buffer collecting filtered samples for each pixel. Colors are kept as
+/// unclamped sums until the film is resolved into a canvas.
+#[derive(Debug, Clone)]
+pub struct Film {
+    /// The pixels of the image the film covers, all of them except for the
+    /// films of single tiles
+    area: Rect,
+    filter: PixelFilter,
+    /// Weighted red, green and blue sums and the total weight per pixel
+    sums: Vec<[f32; 4]>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: PixelFilter) -> Self {
+        Self::covering(Rect::new(0, 0, width, height), filter)
+    }
+
+    /// A film for only the pixels in `area` of a larger image. Samples are
+    /// still added at their position in the whole image.
+    fn covering(area: Rect, filter: PixelFilter) -> Self {
+        Self {
+            area,
+            filter,
+            sums: vec![[0.0; 4]; area.area()],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.area.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.area.height()
+    }
+
+    /// Add a sample at `x`, `y` in pixel units, where pixel (0, 0) covers
+    /// the square from (0, 0) to (1, 1), to every pixel the filter reaches.
+    pub fn add_sample(&mut self, x: f32, y: f32, color: Color) {
+        self.add_spectrum(x, y, Spectrum::from(color));
+    }
+
+    /// Add a sample of unclamped light, like `add_sample`
+    pub fn add_spectrum(&mut self, x: f32, y: f32, spectrum: Spectrum) {
+        let radius = self.filter.radius();
+        let (left, top) = (self.area.x() as f32, self.area.y() as f32);
+        let first_x = (x - 0.5 - radius).ceil().max(left) as usize;
+        let first_y = (y - 0.5 - radius).ceil().max(top) as usize;
+        let last_x = (x - 0.5 + radius)
+            .floor()
+            .min(left + self.area.width() as f32 - 1.0);
+        let last_y = (y - 0.5 + radius)
+            .floor()
+            .min(top + self.area.height() as f32 - 1.0);
+        if last_x < left || last_y < top {
+            return;
+        }
+
+        for py in first_y..=last_y as usize {
+            for px in first_x..=last_x as usize {
+                let weight = self
+                    .filter
+                    .weight(x - (px as f32 + 0.5), y - (py as f32 + 0.5));
+                if weight == 0.0 {
+                    continue;
+                }
+                let index = self.index(px, py);
+                let sum = &mut self.sums[index];
+                sum[0] += spectrum.r() * weight;
+                sum[1] += spectrum.g() * weight;
+                sum[2] += spectrum.b() * weight;
+                sum[3] += weight;
+            }
+        }
+    }
+
+    /// Replace everything added to a pixel with a single sample of
+    /// `spectrum`
+    pub(crate) fn set_spectrum(&mut self, position: Pixel, spectrum: Spectrum) {
+        let index = self.index(position.x(), position.y());
+        self.sums[index] = [spectrum.r(), spectrum.g(), spectrum.b(), 1.0];
+    }
+
+    /// Add the samples collected by `other`, which must cover part of this
+    /// film's pixels with the same filter
+    fn merge(&mut self, other: &Film) {
+        let area = other.area.intersect(&self.area);
+        for y in area.y()..area.y() + area.height() {
+            for x in area.x()..area.x() + area.width() {
+                let from = other.sums[other.index(x, y)];
+                let index = self.index(x, y);
+                let sum = &mut self.sums[index];
+                for (total, value) in sum.iter_mut().zip(from.iter()) {
+                    *total += value;
+                }
+            }
+        }
+    }
+
+    /// Where the sums of the pixel at `x`, `y` in the whole image are kept
+    fn index(&self, x: usize, y: usize) -> usize {
+        (y - self.area.y()) * self.area.width() + x - self.area.x()
+    }
+
+    /// The filtered, unclamped light of a pixel, black if no samples
+    /// reached it
+    pub fn spectrum(&self, position: Pixel) -> Spectrum {
+        let sum = self.sums[self.index(position.x(), position.y())];
+        if sum[3] <= 0.0 {
+            return Spectrum::black();
+        }
+        Spectrum::new(
+            (sum[0] / sum[3]).max(0.0),
+            (sum[1] / sum[3]).max(0.0),
+            (sum[2] / sum[3]).max(0.0),
+        )
+    }
+
+    /// The filtered color of a pixel, black if no samples reached it
+    pub fn color(&self, position: Pixel) -> Color {
+        self.spectrum(position).to_color()
+    }
+
+    /// Write the filtered color of every pixel into `canvas`, which must be
+    /// the same size as the film.
+    pub fn resolve(&self, canvas: &mut Canvas) {
+        self.resolve_with(canvas, |spectrum| spectrum.to_color());
+    }
+
+    /// Write every pixel into `canvas` like `resolve`, squeezing the
+    /// unclamped light into colors with `tone_map`
+    pub fn resolve_tone_mapped(&self, canvas: &mut Canvas, tone_map: ToneMap) {
+        self.resolve_with(canvas, |spectrum| tone_map.apply_spectrum(spectrum));
+    }
+
+    fn resolve_with<F: Fn(Spectrum) -> Color>(&self, canvas: &mut Canvas, to_color: F) {
+        if canvas.width() != self.width() || canvas.height() != self.height() {
+            panic!(
+                "can't resolve a {}x{} film into a {}x{} canvas!",
+                self.width(),
+                self.height(),
+                canvas.width(),
+                canvas.height()
+            );
+        }
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let pixel = Pixel::new(x, y);
+                canvas[pixel] = to_color(self.spectrum(pixel));
+            }
+        }
+    }
+}
+
+/// The settings for taking several samples per pixel
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Sampler {
+    pub pattern: Pattern,
+    pub filter: PixelFilter,
+    pub samples: usize,
+}
+
+impl Sampler {
+    pub fn new(pattern: Pattern, filter: PixelFilter, samples: usize) -> Self {
+        if samples == 0 {
+            panic!("a sampler needs at least one sample per pixel!");
+        }
+        Self {
+            pattern,
+            filter,
+            samples,
+        }
+    }
+}
+
+impl Renderer {
+    /// Render `canvas` by calling `shade` with the position of every sample
+    /// in pixel units and the pixel's random number generator. Each tile's
+    /// samples are shaded in parallel into a film of their own, and the
+    /// tiles' films are added together in a fixed order, so the result
+    /// doesn't depend on the number of threads.
+    pub fn render_supersampled<F, P>(
+        &self,
+        canvas: &mut Canvas,
+        sampler: &Sampler,
+        shade: F,
+        progress: P,
+    ) where
+        F: Fn(f32, f32, &mut Rng) -> Color + Sync,
+        P: Fn(usize, usize) + Sync,
+    {
+        let shade = |x, y, rng: &mut Rng| Spectrum::from(shade(x, y, rng));
+        self.render_radiance(canvas, sampler, shade, progress)
+            .resolve(canvas);
+    }
+
+    /// Render the light reaching every sample into a film the size of
+    /// `canvas`, like `render_supersampled` but without clamping, so it can
+    /// be tone mapped afterwards
+    pub fn render_radiance<F, P>(
+        &self,
+        canvas: &Canvas,
+        sampler: &Sampler,
+        shade: F,
+        progress: P,
+    ) -> Film
+    where
+        F: Fn(f32, f32, &mut Rng) -> Spectrum + Sync,
+        P: Fn(usize, usize) + Sync,
+    {
+        let tiles: Vec<_> = canvas.tiles(self.tile_size(), self.tile_size()).collect();
+        let films: Vec<Mutex<Option<Film>>> = tiles.iter().map(|_| Mutex::new(None)).collect();
+        let whole = Rect::new(0, 0, canvas.width(), canvas.height());
+        // Samples reach the pixels up to the filter's radius outside their
+        // tile
+        let reach = sampler.filter.radius().ceil() as usize;
+
+        self.share_work(tiles.len(), &progress, |index| {
+            let tile = tiles[index];
+            let area = Rect::new(
+                tile.x().saturating_sub(reach),
+                tile.y().saturating_sub(reach),
+                tile.width() + 2 * reach,
+                tile.height() + 2 * reach,
+            );
+            let mut film = Film::covering(area.intersect(&whole), sampler.filter);
+            for y in tile.y()..tile.y() + tile.height() {
+                for x in tile.x()..tile.x() + tile.width() {
+                    let mut rng = Rng::for_pixel(self.seed(), Pixel::new(x, y));
+                    for (dx, dy) in sampler.pattern.points(sampler.samples, &mut rng) {
+                        let (sx, sy) = (x as f32 + dx, y as f32 + dy);
+                        film.add_spectrum(sx, sy, shade(sx, sy, &mut rng));
+                    }
+                }
+            }
+            *films[index].lock().unwrap() = Some(film);
+        });
+
+        let mut film = Film::new(canvas.width(), canvas.height(), sampler.filter);
+        for tile_film in films {
+            if let Some(tile_film) = tile_film.into_inner().unwrap() {
+                film.merge(&tile_film);
+            }
+        }
+        film
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATTERNS: [Pattern; 5] = [
+        Pattern::Grid,
+        Pattern::Jittered,
+        Pattern::Halton,
+        Pattern::Sobol,
+        Pattern::BlueNoise,
+    ];
+
+    #[test]
+    fn test_patterns_stay_inside_the_pixel() {
+        for pattern in PATTERNS.iter() {
+            let points = pattern.points(16, &mut Rng::new(3));
+            assert_eq!(16, points.len(), "{:?}", pattern);
+            for &(x, y) in points.iter() {
+                assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_grid_points_are_cell_centers() {
+        let points = Pattern::Grid.points(4, &mut Rng::new(0));
+        assert_eq!(
+            vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)],
+            points
+        );
+    }
+
+    #[test]
+    fn test_stratified_patterns_put_one_point_in_each_cell() {
+        for pattern in [Pattern::Jittered, Pattern::Sobol].iter() {
+            let mut cells = [0; 4];
+            for (x, y) in pattern.points(4, &mut Rng::new(11)) {
+                cells[(x * 2.0) as usize + 2 * (y * 2.0) as usize] += 1;
+            }
+            assert_eq!([1, 1, 1, 1], cells, "{:?}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_grid_patterns_sample_the_whole_pixel_evenly() {
+        // Counts which aren't square still get a cell each, so on average
+        // the points sit in the middle of the pixel
+        for count in 1..=12 {
+            let points = Pattern::Grid.points(count, &mut Rng::new(0));
+            let (x, y) = points
+                .iter()
+                .fold((0.0, 0.0), |(x, y), point| (x + point.0, y + point.1));
+            assert!((x / count as f32 - 0.5).abs() < 1e-6, "{}", count);
+            assert!((y / count as f32 - 0.5).abs() < 1e-6, "{}", count);
+        }
+        let mut cells = [0; 8];
+        for (x, y) in Pattern::Jittered.points(8, &mut Rng::new(4)) {
+            cells[(x * 4.0) as usize + 4 * (y * 2.0) as usize] += 1;
+        }
+        assert_eq!([1; 8], cells);
+    }
+
+    #[test]
+    fn test_low_discrepancy_sequences() {
+        assert_eq!(0.5, radical_inverse(1, 2));
+        assert_eq!(0.25, radical_inverse(2, 2));
+        assert!((radical_inverse(1, 3) - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(
+            vec![0, 1 << 31, 3 << 30, 1 << 30],
+            (0..4).map(sobol_second).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_blue_noise_avoids_clumps() {
+        let points = Pattern::BlueNoise.points(16, &mut Rng::new(5));
+        for (i, &a) in points.iter().enumerate() {
+            for &b in points[i + 1..].iter() {
+                assert!(toroidal_distance(a, b).sqrt() > 0.1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_filters_peak_at_the_pixel_center() {
+        for filter in [
+            PixelFilter::Box,
+            PixelFilter::Tent,
+            PixelFilter::Gaussian,
+            PixelFilter::Mitchell,
+        ]
+        .iter()
+        {
+            assert!(filter.weight(0.0, 0.0) > 0.0);
+            assert!(filter.weight(0.0, 0.0) >= filter.weight(0.3, 0.2));
+            assert_eq!(0.0, filter.weight(filter.radius() + 0.1, 0.0));
+        }
+        assert!(PixelFilter::Mitchell.weight(1.5, 0.0) < 0.0);
+    }
+
+    #[test]
+    fn test_box_filtered_film_averages_samples_in_a_pixel() {
+        let mut film = Film::new(2, 1, PixelFilter::Box);
+        film.add_sample(0.25, 0.5, Color::rgb(1.0, 0.0, 0.0));
+        film.add_sample(0.75, 0.5, Color::rgb(0.0, 0.0, 1.0));
+        assert_eq!([128, 0, 128], film.color(Pixel::new(0, 0)).as_rgb_bytes());
+        assert_eq!([0, 0, 0], film.color(Pixel::new(1, 0)).as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_films_keep_bright_light_until_tone_mapped() {
+        let mut film = Film::new(1, 1, PixelFilter::Box);
+        film.add_spectrum(0.25, 0.5, Spectrum::new(6.0, 1.0, 0.0));
+        film.add_spectrum(0.75, 0.5, Spectrum::new(2.0, 0.0, 0.0));
+        assert_eq!(
+            Spectrum::new(4.0, 0.5, 0.0),
+            film.spectrum(Pixel::new(0, 0))
+        );
+        let mut canvas = Canvas::new(1, 1);
+        film.resolve_tone_mapped(&mut canvas, ToneMap::Reinhard);
+        assert_eq!([204, 85, 0], canvas[Pixel::new(0, 0)].as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_wide_filters_reach_neighbouring_pixels() {
+        let mut film = Film::new(3, 1, PixelFilter::Tent);
+        film.add_sample(1.5, 0.5, Color::rgb(1.0, 1.0, 1.0));
+        film.add_sample(0.5, 0.5, Color::rgb(0.0, 0.0, 0.0));
+        // Pixel 1 only sees the white sample at its center and the black
+        // one exactly a pixel away, where the tent reaches zero.
+        assert_eq!([255, 255, 255], film.color(Pixel::new(1, 0)).as_rgb_bytes());
+        assert_eq!([0, 0, 0], film.color(Pixel::new(0, 0)).as_rgb_bytes());
+        film.add_sample(0.75, 0.5, Color::rgb(1.0, 1.0, 1.0));
+        assert_ne!([0, 0, 0], film.color(Pixel::new(0, 0)).as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_tile_films_add_up_to_the_whole() {
+        let mut whole = Film::new(6, 4, PixelFilter::Gaussian);
+        let mut left = Film::covering(Rect::new(0, 0, 4, 4), PixelFilter::Gaussian);
+        let mut right = Film::covering(Rect::new(2, 0, 4, 4), PixelFilter::Gaussian);
+        let mut merged = Film::new(6, 4, PixelFilter::Gaussian);
+        whole.add_spectrum(1.2, 1.7, Spectrum::gray(2.0));
+        whole.add_spectrum(4.5, 2.5, Spectrum::gray(2.0));
+        left.add_spectrum(1.2, 1.7, Spectrum::gray(2.0));
+        right.add_spectrum(4.5, 2.5, Spectrum::gray(2.0));
+        merged.merge(&left);
+        merged.merge(&right);
+        for y in 0..4 {
+            for x in 0..6 {
+                let pixel = Pixel::new(x, y);
+                assert_eq!(whole.spectrum(pixel), merged.spectrum(pixel));
+            }
+        }
+    }
+
+    #[test]
+    fn test_supersampling_smooths_an_edge() {
+        // A diagonal edge through the middle of a 4x4 canvas
+        let shade = |x: f32, y: f32, _: &mut Rng| {
+            if x > y {
+                Color::rgb(1.0, 1.0, 1.0)
+            } else {
+                Color::rgb(0.0, 0.0, 0.0)
+            }
+        };
+        let sampler = Sampler::new(Pattern::Grid, PixelFilter::Box, 16);
+        let mut canvas = Canvas::new(4, 4);
+        Renderer::new(0).render_supersampled(&mut canvas, &sampler, shade, |_, _| {});
+        let diagonal = canvas[Pixel::new(1, 1)].r();
+        assert!(diagonal > 0.2 && diagonal < 0.8, "{}", diagonal);
+        assert_eq!([255, 255, 255], canvas[Pixel::new(3, 0)].as_rgb_bytes());
+        assert_eq!([0, 0, 0], canvas[Pixel::new(0, 3)].as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_supersampling_is_independent_of_threads() {
+        let shade = |x: f32, y: f32, rng: &mut Rng| Color::rgb(x / 9.0, y / 7.0, rng.next_f32());
+        let sampler = Sampler::new(Pattern::Jittered, PixelFilter::Mitchell, 4);
+        let render = |threads| {
+            let mut renderer = Renderer::new(8);
+            renderer.set_threads(threads);
+            renderer.set_tile_size(3);
+            let mut canvas = Canvas::new(9, 7);
+            renderer.render_supersampled(&mut canvas, &sampler, shade, |_, _| {});
+            (0..63)
+                .map(|i| {
+                    let color = canvas[Pixel::new(i % 9, i / 9)];
+                    [color.r(), color.g(), color.b()].map(f32::to_bits)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(render(1), render(4));
+    }
+}
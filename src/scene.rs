@@ -0,0 +1,832 @@
+//! A human readable scene description format. Scenes are written in a small
+//! subset of YAML: a list of entries, each either adding something to the
+//! scene or defining a named value which later entries can refer to.
+//!
+//! ```text
+//! - define: white-material
+//!   value:
+//!     color: [1, 1, 1]
+//!     diffuse: 0.7
+//!
+//! - define: blue-material
+//!   extend: white-material
+//!   value:
+//!     color: [0.1, 0.1, 1]
+//!
+//! - define: standard-transform
+//!   value:
+//!     - [translate, 1, -1, 1]
+//!     - [scale, 0.5, 0.5, 0.5]
+//!
+//! - add: sphere
+//!   material: blue-material
+//!   transform:
+//!     - standard-transform
+//!     - [rotate-y, 1.5]
+//! ```
+//!
+//! An `extend` copies the definition it names and overrides its keys with
+//! the new ones. Names are replaced with the value they were defined as, and
+//! every error carries the line and column it was found at.
+
+use crate::color::{Color, Spectrum};
+use crate::matrix::Matrix;
+use crate::units::{Point3D, Unit3D, Vector3D};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// A line and column in a scene file, both counted from 1
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Something wrong with a scene file and where it was found
+#[derive(PartialEq, Debug, Clone)]
+pub struct SceneError {
+    pub position: Position,
+    pub message: String,
+}
+
+impl SceneError {
+    pub fn new<S: Into<String>>(position: Position, message: S) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.position, self.message)
+    }
+}
+
+impl Error for SceneError {}
+
+/// A value from a scene file along with where it was written
+#[derive(PartialEq, Debug, Clone)]
+pub enum Value {
+    Number(Unit3D, Position),
+    Text(String, Position),
+    List(Vec<Value>, Position),
+    /// Keys and values in the order they were written
+    Map(Vec<(String, Value)>, Position),
+}
+
+impl Value {
+    pub fn position(&self) -> Position {
+        match self {
+            Value::Number(_, position)
+            | Value::Text(_, position)
+            | Value::List(_, position)
+            | Value::Map(_, position) => *position,
+        }
+    }
+
+    pub fn as_number(&self) -> Result<Unit3D, SceneError> {
+        match self {
+            Value::Number(number, _) => Ok(*number),
+            _ => Err(self.expected("a number")),
+        }
+    }
+
+    pub fn as_text(&self) -> Result<&str, SceneError> {
+        match self {
+            Value::Text(text, _) => Ok(text),
+            _ => Err(self.expected("text")),
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&[Value], SceneError> {
+        match self {
+            Value::List(items, _) => Ok(items),
+            _ => Err(self.expected("a list")),
+        }
+    }
+
+    pub fn as_map(&self) -> Result<&[(String, Value)], SceneError> {
+        match self {
+            Value::Map(entries, _) => Ok(entries),
+            _ => Err(self.expected("a map")),
+        }
+    }
+
+    /// The value of `key` if this is a map containing it
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries, _) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Read a list of three numbers as a point
+    pub fn to_point(&self) -> Result<Point3D, SceneError> {
+        let [x, y, z] = self.numbers::<3>()?;
+        Ok(Point3D::new(x, y, z))
+    }
+
+    /// Read a list of three numbers as a vector
+    pub fn to_vector(&self) -> Result<Vector3D, SceneError> {
+        let [x, y, z] = self.numbers::<3>()?;
+        Ok(Vector3D::new(x, y, z))
+    }
+
+    /// Read a list of three numbers as a color
+    pub fn to_color(&self) -> Result<Color, SceneError> {
+        let [r, g, b] = self.numbers::<3>()?;
+        Ok(Color::rgb(r, g, b))
+    }
+
+    /// Read a list of three numbers as an amount of light, which unlike a
+    /// color may be brighter than 1
+    pub fn to_spectrum(&self) -> Result<Spectrum, SceneError> {
+        let [r, g, b] = self.numbers::<3>()?;
+        Ok(Spectrum::new(r, g, b))
+    }
+
+    /// Read a list of transforms, each a list naming the transform followed
+    /// by its arguments, into a single matrix. Transforms are applied in the
+    /// order they're listed and nested lists are applied where they appear.
+    pub fn to_transform(&self) -> Result<Matrix, SceneError> {
+        let mut transform = Matrix::identity();
+        for step in self.as_list()? {
+            let args = step.as_list()?;
+            let matrix = match args.first() {
+                Some(Value::Text(name, _)) => transform_step(name, step)?,
+                Some(_) => step.to_transform()?,
+                None => Matrix::identity(),
+            };
+            transform = matrix * transform;
+        }
+        Ok(transform)
+    }
+
+    fn numbers<const N: usize>(&self) -> Result<[Unit3D; N], SceneError> {
+        let items = self.as_list()?;
+        if items.len() != N {
+            return Err(self.expected(&format!("a list of {} numbers", N)));
+        }
+        let mut numbers = [0.0; N];
+        for (number, item) in numbers.iter_mut().zip(items) {
+            *number = item.as_number()?;
+        }
+        Ok(numbers)
+    }
+
+    fn expected(&self, what: &str) -> SceneError {
+        SceneError::new(self.position(), format!("expected {}", what))
+    }
+}
+
+/// The matrix for a single `[name, args...]` transform
+fn transform_step(name: &str, step: &Value) -> Result<Matrix, SceneError> {
+    let args = &step.as_list()?[1..];
+    let numbers = args
+        .iter()
+        .map(|arg| arg.as_number())
+        .collect::<Result<Vec<_>, _>>()?;
+    let wrong_count = |count: usize| {
+        SceneError::new(
+            step.position(),
+            format!(
+                "`{}` takes {} numbers, found {}",
+                name,
+                count,
+                numbers.len()
+            ),
+        )
+    };
+    let three = || match numbers[..] {
+        [x, y, z] => Ok(Point3D::new(x, y, z)),
+        _ => Err(wrong_count(3)),
+    };
+    let one = || match numbers[..] {
+        [angle] => Ok(angle),
+        _ => Err(wrong_count(1)),
+    };
+    match name {
+        "translate" => Ok(Matrix::translation(three()?)),
+        "scale" => Ok(Matrix::scaling(three()?)),
+        "rotate-x" => Ok(Matrix::rotation_x(one()?)),
+        "rotate-y" => Ok(Matrix::rotation_y(one()?)),
+        "rotate-z" => Ok(Matrix::rotation_z(one()?)),
+        _ => Err(SceneError::new(
+            step.position(),
+            format!("unknown transform `{}`", name),
+        )),
+    }
+}
+
+/// Something added to the scene, like a camera, light or shape
+#[derive(PartialEq, Debug, Clone)]
+pub struct Item {
+    kind: String,
+    properties: Value,
+}
+
+impl Item {
+    /// What was added, the value of the `add` key
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    pub fn position(&self) -> Position {
+        self.properties.position()
+    }
+
+    /// Every key of the entry except `add`, with names already replaced by
+    /// their definitions
+    pub fn properties(&self) -> &Value {
+        &self.properties
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.properties.get(key)
+    }
+
+    /// The value of `key`, or an error if the entry doesn't have it
+    pub fn require(&self, key: &str) -> Result<&Value, SceneError> {
+        self.get(key).ok_or_else(|| {
+            SceneError::new(
+                self.position(),
+                format!("`{}` needs a `{}`", self.kind, key),
+            )
+        })
+    }
+}
+
+/// The items of a scene file in the order they were added
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Scene {
+    items: Vec<Item>,
+}
+
+impl Scene {
+    /// Parse a scene file, resolving every `define` and `extend`
+    pub fn parse(source: &str) -> Result<Self, SceneError> {
+        let document = parse_document(source)?;
+        let entries = match &document {
+            Some(document) => document.as_list()?,
+            None => return Ok(Self::default()),
+        };
+
+        let mut defines: HashMap<String, Value> = HashMap::new();
+        let mut items = Vec::new();
+        for entry in entries {
+            let fields = entry.as_map()?;
+            if let Some(add) = entry.get("add") {
+                let kind = add.as_text()?.to_string();
+                let properties = fields
+                    .iter()
+                    .filter(|(key, _)| key != "add")
+                    .map(|(key, value)| (key.clone(), resolve(value, &defines)))
+                    .collect();
+                items.push(Item {
+                    kind,
+                    properties: Value::Map(properties, entry.position()),
+                });
+            } else if let Some(define) = entry.get("define") {
+                let name = define.as_text()?.to_string();
+                let value = entry.get("value").ok_or_else(|| {
+                    SceneError::new(entry.position(), format!("`{}` needs a `value`", name))
+                })?;
+                let mut value = resolve(value, &defines);
+                if let Some(base) = entry.get("extend") {
+                    value = extend(base, value, &defines)?;
+                }
+                defines.insert(name, value);
+            } else {
+                return Err(SceneError::new(
+                    entry.position(),
+                    "expected `add` or `define`",
+                ));
+            }
+        }
+        Ok(Self { items })
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    /// The items added as `kind`
+    pub fn items_of<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a Item> + 'a {
+        self.items.iter().filter(move |item| item.kind == kind)
+    }
+}
+
+/// Replace every name in `value` which has been defined with its definition
+fn resolve(value: &Value, defines: &HashMap<String, Value>) -> Value {
+    match value {
+        Value::Text(name, _) => defines.get(name).cloned().unwrap_or_else(|| value.clone()),
+        Value::List(items, position) => Value::List(
+            items.iter().map(|item| resolve(item, defines)).collect(),
+            *position,
+        ),
+        Value::Map(entries, position) => Value::Map(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), resolve(value, defines)))
+                .collect(),
+            *position,
+        ),
+        Value::Number(..) => value.clone(),
+    }
+}
+
+/// The definition named by `base` with the keys of `value` added to or
+/// replacing its own
+fn extend(
+    base: &Value,
+    value: Value,
+    defines: &HashMap<String, Value>,
+) -> Result<Value, SceneError> {
+    let name = base.as_text()?;
+    let base_value = defines.get(name).ok_or_else(|| {
+        SceneError::new(base.position(), format!("`{}` hasn't been defined", name))
+    })?;
+    let mut entries = base_value.as_map()?.to_vec();
+    let position = value.position();
+    for (key, value) in value.as_map()? {
+        match entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.clone(),
+            None => entries.push((key.clone(), value.clone())),
+        }
+    }
+    Ok(Value::Map(entries, position))
+}
+
+// ==========================================================================
+// Parser
+
+/// A line with its comment and indentation removed
+#[derive(Debug, Clone)]
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+impl Line {
+    fn position(&self, offset: usize) -> Position {
+        Position::new(self.number, self.indent + offset + 1)
+    }
+
+    fn is_item(&self) -> bool {
+        self.text == "-" || self.text.starts_with("- ")
+    }
+
+    /// The key and the text after the colon if the line is a map entry
+    fn entry(&self) -> Option<(&str, &str)> {
+        if self.text.starts_with('[') || self.text.starts_with('"') {
+            return None;
+        }
+        let colon = self
+            .text
+            .char_indices()
+            .find(|&(i, c)| c == ':' && self.text[i + 1..].chars().next().is_none_or(|c| c == ' '))?
+            .0;
+        let key = self.text[..colon].trim();
+        if key.is_empty() {
+            return None;
+        }
+        Some((key, self.text[colon + 1..].trim_start()))
+    }
+}
+
+/// Parse the whole document, `None` if it has no content
+fn parse_document(source: &str) -> Result<Option<Value>, SceneError> {
+    let mut lines = Vec::new();
+    for (number, raw) in source.lines().enumerate() {
+        let text = strip_comment(raw).trim_end();
+        let content = text.trim_start();
+        if content.is_empty() {
+            continue;
+        }
+        let indent = text.len() - content.len();
+        if text[..indent].contains('\t') {
+            return Err(SceneError::new(
+                Position::new(number + 1, 1),
+                "tabs can't be used for indentation",
+            ));
+        }
+        lines.push(Line {
+            number: number + 1,
+            indent,
+            text: content.to_string(),
+        });
+    }
+    if lines.is_empty() {
+        return Ok(None);
+    }
+
+    let mut parser = Parser { lines, index: 0 };
+    let indent = parser.lines[0].indent;
+    let document = parser.parse_node(indent)?;
+    if let Some(line) = parser.lines.get(parser.index) {
+        return Err(SceneError::new(line.position(0), "unexpected indentation"));
+    }
+    Ok(Some(document))
+}
+
+/// Everything before a `#` which isn't inside quotes
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    index: usize,
+}
+
+impl Parser {
+    /// Parse the block starting at the current line, which is at `indent`
+    fn parse_node(&mut self, indent: usize) -> Result<Value, SceneError> {
+        let line = &self.lines[self.index];
+        if line.is_item() {
+            self.parse_list(indent)
+        } else if line.entry().is_some() {
+            self.parse_map(indent)
+        } else {
+            let value = parse_inline(line)?;
+            self.index += 1;
+            Ok(value)
+        }
+    }
+
+    /// Parse the value of an entry or item whose text ended at the end of
+    /// `line`, which must be a block indented further on the next line.
+    fn parse_nested(&mut self, line: &Line, what: &str) -> Result<Value, SceneError> {
+        match self.lines.get(self.index) {
+            Some(next) if next.indent > line.indent => self.parse_node(next.indent),
+            // Lists are allowed at the same indent as the key they belong to
+            Some(next) if next.indent == line.indent && next.is_item() && what != "item" => {
+                self.parse_list(line.indent)
+            }
+            _ => Err(SceneError::new(
+                line.position(line.text.len()),
+                format!("missing {}", what),
+            )),
+        }
+    }
+
+    fn parse_list(&mut self, indent: usize) -> Result<Value, SceneError> {
+        let position = self.lines[self.index].position(0);
+        let mut items = Vec::new();
+        while let Some(line) = self.lines.get(self.index) {
+            if line.indent < indent || (line.indent == indent && !line.is_item()) {
+                break;
+            }
+            if line.indent > indent {
+                return Err(SceneError::new(line.position(0), "unexpected indentation"));
+            }
+
+            let line = line.clone();
+            let rest = line.text[1..].trim_start();
+            if rest.is_empty() {
+                self.index += 1;
+                items.push(self.parse_nested(&line, "item")?);
+            } else {
+                // Treat whatever follows the dash as a line of its own, so
+                // the keys of a map can start on the same line as the dash.
+                let offset = line.text.len() - rest.len();
+                self.lines[self.index] = Line {
+                    number: line.number,
+                    indent: line.indent + offset,
+                    text: rest.to_string(),
+                };
+                items.push(self.parse_node(line.indent + offset)?);
+            }
+        }
+        Ok(Value::List(items, position))
+    }
+
+    fn parse_map(&mut self, indent: usize) -> Result<Value, SceneError> {
+        let position = self.lines[self.index].position(0);
+        let mut entries: Vec<(String, Value)> = Vec::new();
+        while let Some(line) = self.lines.get(self.index) {
+            if line.indent < indent || (line.indent == indent && line.is_item()) {
+                break;
+            }
+            if line.indent > indent {
+                return Err(SceneError::new(line.position(0), "unexpected indentation"));
+            }
+
+            let line = line.clone();
+            let (key, rest) = line
+                .entry()
+                .ok_or_else(|| SceneError::new(line.position(0), "expected `key: value`"))?;
+            if entries.iter().any(|(k, _)| k == key) {
+                return Err(SceneError::new(
+                    line.position(0),
+                    format!("`{}` is given more than once", key),
+                ));
+            }
+            self.index += 1;
+            let value = if rest.is_empty() {
+                self.parse_nested(&line, &format!("value for `{}`", key))?
+            } else {
+                let offset = line.text.len() - rest.len();
+                parse_inline(&Line {
+                    number: line.number,
+                    indent: line.indent + offset,
+                    text: rest.to_string(),
+                })?
+            };
+            entries.push((key.to_string(), value));
+        }
+        Ok(Value::Map(entries, position))
+    }
+}
+
+/// Parse a value written on a single line: a number, some text, or a list
+/// in square brackets
+fn parse_inline(line: &Line) -> Result<Value, SceneError> {
+    let chars: Vec<char> = line.text.chars().collect();
+    let mut scanner = Scanner {
+        line,
+        chars: &chars,
+        at: 0,
+    };
+    let value = scanner.value(false)?;
+    scanner.skip_spaces();
+    if scanner.at < chars.len() {
+        return Err(scanner.error("unexpected text after value"));
+    }
+    Ok(value)
+}
+
+struct Scanner<'a> {
+    line: &'a Line,
+    chars: &'a [char],
+    at: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn error(&self, message: &str) -> SceneError {
+        SceneError::new(self.line.position(self.at), message)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(' ') {
+            self.at += 1;
+        }
+    }
+
+    fn value(&mut self, in_list: bool) -> Result<Value, SceneError> {
+        self.skip_spaces();
+        let position = self.line.position(self.at);
+        match self.peek() {
+            Some('[') => {
+                self.at += 1;
+                let mut items = Vec::new();
+                self.skip_spaces();
+                if self.peek() == Some(']') {
+                    self.at += 1;
+                    return Ok(Value::List(items, position));
+                }
+                loop {
+                    items.push(self.value(true)?);
+                    self.skip_spaces();
+                    match self.peek() {
+                        Some(',') => self.at += 1,
+                        Some(']') => {
+                            self.at += 1;
+                            return Ok(Value::List(items, position));
+                        }
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some('"') => {
+                self.at += 1;
+                let start = self.at;
+                while self.peek().is_some_and(|c| c != '"') {
+                    self.at += 1;
+                }
+                if self.peek().is_none() {
+                    return Err(SceneError::new(position, "unterminated string"));
+                }
+                let text: String = self.chars[start..self.at].iter().collect();
+                self.at += 1;
+                Ok(Value::Text(text, position))
+            }
+            _ => {
+                let start = self.at;
+                while self
+                    .peek()
+                    .is_some_and(|c| !(in_list && (c == ',' || c == ']')))
+                {
+                    self.at += 1;
+                }
+                let text: String = self.chars[start..self.at].iter().collect();
+                let text = text.trim_end();
+                if text.is_empty() {
+                    return Err(SceneError::new(position, "expected a value"));
+                }
+                Ok(match text.parse::<Unit3D>() {
+                    Ok(number) => Value::Number(number, position),
+                    Err(_) => Value::Text(text.to_string(), position),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Tuple;
+
+    const SCENE: &str = "
+# A test scene
+- add: camera
+  width: 100
+  height: 50
+  field-of-view: 0.785
+  from: [0, 1.5, -5]
+
+- define: white-material
+  value:
+    color: [1, 1, 1]
+    diffuse: 0.7
+
+- define: blue-material
+  extend: white-material
+  value:
+    color: [0.1, 0.1, 1]
+
+- define: standard-transform
+  value:
+  - [translate, 1, -1, 1]
+  - [scale, 0.5, 0.5, 0.5]
+
+- add: sphere
+  material: blue-material
+  transform:
+    - standard-transform
+    - [rotate-y, 1.5]
+";
+
+    fn error(source: &str) -> SceneError {
+        Scene::parse(source).unwrap_err()
+    }
+
+    #[test]
+    fn test_parses_items_in_order() {
+        let scene = Scene::parse(SCENE).unwrap();
+        let kinds: Vec<&str> = scene.items().iter().map(|item| item.kind()).collect();
+        assert_eq!(vec!["camera", "sphere"], kinds);
+
+        let camera = &scene.items()[0];
+        assert_eq!(Position::new(3, 3), camera.position());
+        assert_eq!(100.0, camera.require("width").unwrap().as_number().unwrap());
+        assert_eq!(
+            Point3D::new(0.0, 1.5, -5.0),
+            camera.require("from").unwrap().to_point().unwrap()
+        );
+        assert!(camera.get("add").is_none());
+    }
+
+    #[test]
+    fn test_extend_overrides_the_base_definition() {
+        let scene = Scene::parse(SCENE).unwrap();
+        let sphere = scene.items_of("sphere").next().unwrap();
+        let material = sphere.require("material").unwrap();
+        let color = material.get("color").unwrap().to_color().unwrap();
+        assert_eq!([26, 26, 255], color.as_rgb_bytes());
+        assert_eq!(0.7, material.get("diffuse").unwrap().as_number().unwrap());
+    }
+
+    #[test]
+    fn test_named_transforms_are_applied_in_order() {
+        let scene = Scene::parse(SCENE).unwrap();
+        let sphere = scene.items_of("sphere").next().unwrap();
+        let transform = sphere.require("transform").unwrap().to_transform().unwrap();
+        let expected = Matrix::rotation_y(1.5)
+            * Matrix::scaling(Point3D::new(0.5, 0.5, 0.5))
+            * Matrix::translation(Point3D::new(1.0, -1.0, 1.0));
+        let (a, b) = (
+            transform * Point3D::new(1.0, 2.0, 3.0),
+            expected * Point3D::new(1.0, 2.0, 3.0),
+        );
+        assert!((a.x() - b.x()).abs() < 0.0001);
+        assert!((a.y() - b.y()).abs() < 0.0001);
+        assert!((a.z() - b.z()).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_empty_scene() {
+        assert!(Scene::parse("# nothing here\n\n")
+            .unwrap()
+            .items()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_parses_quoted_text_and_nested_lists() {
+        let scene =
+            Scene::parse("- add: label\n  text: \"a # b, c\"\n  grid: [[1, 2], []]\n").unwrap();
+        let label = &scene.items()[0];
+        assert_eq!(
+            "a # b, c",
+            label.require("text").unwrap().as_text().unwrap()
+        );
+        let grid = label.require("grid").unwrap().as_list().unwrap();
+        assert_eq!(2, grid[0].as_list().unwrap().len());
+        assert!(grid[1].as_list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_errors_carry_line_and_column() {
+        let err = error("- add: camera\n  width: [1, 2\n");
+        assert_eq!(Position::new(2, 15), err.position);
+        assert_eq!("line 2, column 15: expected `,` or `]`", err.to_string());
+    }
+
+    #[test]
+    fn test_wrongly_typed_values_are_reported_where_they_are() {
+        let scene = Scene::parse("- add: light\n  at: [1, x, 3]\n").unwrap();
+        let err = scene.items()[0]
+            .require("at")
+            .unwrap()
+            .to_point()
+            .unwrap_err();
+        assert_eq!(Position::new(2, 11), err.position);
+        assert_eq!("expected a number", err.message);
+    }
+
+    #[test]
+    fn test_missing_keys_are_reported() {
+        let scene = Scene::parse("- add: sphere\n").unwrap();
+        let err = scene.items()[0].require("material").unwrap_err();
+        assert_eq!(
+            "line 1, column 3: `sphere` needs a `material`",
+            err.to_string()
+        );
+        assert_eq!("`x` needs a `value`", error("- define: x\n").message);
+    }
+
+    #[test]
+    fn test_bad_structure_is_reported() {
+        assert_eq!(
+            Position::new(3, 5),
+            error("- add: a\n  b: 1\n    c: 2\n").position
+        );
+        assert_eq!(
+            "expected `add` or `define`",
+            error("- colour: red\n").message
+        );
+        assert_eq!(
+            "`b` is given more than once",
+            error("- add: a\n  b: 1\n  b: 2\n").message
+        );
+        assert_eq!("missing value for `b`", error("- add: a\n  b:\n").message);
+        assert_eq!(
+            "`base` hasn't been defined",
+            error("- define: x\n  extend: base\n  value:\n    a: 1\n").message
+        );
+    }
+
+    #[test]
+    fn test_unknown_transforms_are_reported() {
+        let scene = Scene::parse("- add: cube\n  transform:\n    - [squash, 1]\n").unwrap();
+        let err = scene.items()[0]
+            .require("transform")
+            .unwrap()
+            .to_transform()
+            .unwrap_err();
+        assert_eq!(
+            "line 3, column 7: unknown transform `squash`",
+            err.to_string()
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Shapes, each in its own object space where it has a fixed size and sits
+//! at the origin. They are placed, scaled and turned by the transforms of
+//! the `SceneGraph` node holding them, so rays are moved into a shape's
+//! space before it is intersected and its normals are moved back out.
+//!
+//! Every crossing of a surface comes with the normal there, so shapes built
+//! out of other shapes, like a `Csg`, can hand on the normals of whichever
+//! part a ray crossed.
+
+use crate::bounds::BoundingBox;
+use crate::bvh::Bounded;
+use crate::ray::Ray;
+use crate::units::{Point3D, Tuple, Unit3D, Vector3D, EPSILON};
+use std::fmt::Debug;
+
+/// Where a ray crosses the surface of a shape
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Crossing {
+    /// How far along the ray the surface is
+    pub t: Unit3D,
+    /// The outward normal of the surface there, in the shape's space. It
+    /// isn't always unit length.
+    pub normal: Vector3D,
+}
+
+impl Crossing {
+    pub fn new(t: Unit3D, normal: Vector3D) -> Self {
+        Self { t, normal }
+    }
+}
+
+/// Something a ray can hit, which fits inside its `bounds`
+pub trait Shape: Bounded + Debug + Send + Sync {
+    /// Where `ray` crosses the surface, nearest first. Crossings behind the
+    /// ray's origin are included, so solids can tell whether the origin is
+    /// inside them.
+    fn intersect(&self, ray: &Ray) -> Vec<Crossing>;
+}
+
+// ==========================================================================
+// Sphere
+
+/// The sphere of radius 1 around the origin
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Sphere;
+
+impl Shape for Sphere {
+    fn intersect(&self, ray: &Ray) -> Vec<Crossing> {
+        sphere_crossings(ray, Point3D::new(0.0, 0.0, 0.0), 1.0)
+    }
+}
+
+/// Where `ray` crosses the sphere of `radius` around `center`, nearest
+/// first, with unit normals. For things which are spheres without being
+/// placed in a `SceneGraph`, like lights.
+pub fn sphere_crossings(ray: &Ray, center: Point3D, radius: Unit3D) -> Vec<Crossing> {
+    let to_ray = ray.origin - center;
+    let a = ray.direction.dot(ray.direction);
+    let b = 2.0 * ray.direction.dot(to_ray);
+    let c = to_ray.dot(to_ray) - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+    let root = discriminant.sqrt();
+    [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+        .iter()
+        .map(|&t| Crossing::new(t, (ray.position(t) - center) / radius))
+        .collect()
+}
+
+impl Bounded for Sphere {
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(Point3D::new(-1.0, -1.0, -1.0), Point3D::new(1.0, 1.0, 1.0))
+    }
+}
+
+// ==========================================================================
+// Plane
+
+/// The plane y = 0, stretching forever along x and z
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Plane;
+
+impl Shape for Plane {
+    fn intersect(&self, ray: &Ray) -> Vec<Crossing> {
+        if ray.direction.y().abs() < EPSILON {
+            return Vec::new();
+        }
+        vec![Crossing::new(
+            -ray.origin.y() / ray.direction.y(),
+            Vector3D::new(0.0, 1.0, 0.0),
+        )]
+    }
+}
+
+impl Bounded for Plane {
+    fn bounds(&self) -> BoundingBox {
+        let inf = Unit3D::INFINITY;
+        BoundingBox::new(Point3D::new(-inf, 0.0, -inf), Point3D::new(inf, 0.0, inf))
+    }
+}
+
+// ==========================================================================
+// Cube
+
+/// The cube from -1 to 1 along every axis
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Cube;
+
+impl Shape for Cube {
+    fn intersect(&self, ray: &Ray) -> Vec<Crossing> {
+        match self.bounds().intersect(ray.origin, ray.direction) {
+            Some((near, far)) => [near, far]
+                .iter()
+                .map(|&t| Crossing::new(t, Cube::normal_at(ray.position(t))))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Cube {
+    /// The normal of the face `point` is nearest, which at an edge or
+    /// corner is whichever is furthest out
+    fn normal_at(point: Point3D) -> Vector3D {
+        let (x, y, z) = (point.x(), point.y(), point.z());
+        let furthest = x.abs().max(y.abs()).max(z.abs());
+        if furthest == x.abs() {
+            Vector3D::new(x.signum(), 0.0, 0.0)
+        } else if furthest == y.abs() {
+            Vector3D::new(0.0, y.signum(), 0.0)
+        } else {
+            Vector3D::new(0.0, 0.0, z.signum())
+        }
+    }
+}
+
+impl Bounded for Cube {
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(Point3D::new(-1.0, -1.0, -1.0), Point3D::new(1.0, 1.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(origin: (f32, f32, f32), direction: (f32, f32, f32)) -> Ray {
+        Ray::new(
+            Point3D::new(origin.0, origin.1, origin.2),
+            Vector3D::new(direction.0, direction.1, direction.2),
+        )
+    }
+
+    fn distances<S: Shape>(shape: S, ray: Ray) -> Vec<Unit3D> {
+        shape
+            .intersect(&ray)
+            .iter()
+            .map(|crossing| crossing.t)
+            .collect()
+    }
+
+    #[test]
+    fn test_rays_cross_a_sphere_twice() {
+        let ahead = ray((0.0, 0.0, -5.0), (0.0, 0.0, 1.0));
+        assert_eq!(vec![4.0, 6.0], distances(Sphere, ahead));
+        assert_eq!(
+            vec![5.0, 5.0],
+            distances(Sphere, ray((0.0, 1.0, -5.0), (0.0, 0.0, 1.0)))
+        );
+        assert!(distances(Sphere, ray((0.0, 2.0, -5.0), (0.0, 0.0, 1.0))).is_empty());
+        // From inside, one crossing is behind the origin
+        assert_eq!(
+            vec![-1.0, 1.0],
+            distances(Sphere, ray((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)))
+        );
+        let normals: Vec<Vector3D> = Sphere
+            .intersect(&ahead)
+            .iter()
+            .map(|crossing| crossing.normal)
+            .collect();
+        assert_eq!(
+            vec![Vector3D::new(0.0, 0.0, -1.0), Vector3D::new(0.0, 0.0, 1.0)],
+            normals
+        );
+    }
+
+    #[test]
+    fn test_rays_cross_any_sphere() {
+        let crossings = sphere_crossings(
+            &ray((0.0, 0.0, -5.0), (0.0, 0.0, 1.0)),
+            Point3D::new(0.0, 0.0, 2.0),
+            3.0,
+        );
+        assert_eq!(
+            vec![
+                Crossing::new(4.0, Vector3D::new(0.0, 0.0, -1.0)),
+                Crossing::new(10.0, Vector3D::new(0.0, 0.0, 1.0))
+            ],
+            crossings
+        );
+    }
+
+    #[test]
+    fn test_rays_cross_a_plane_once() {
+        let down = ray((0.0, 1.0, 0.0), (0.0, -1.0, 0.0));
+        assert_eq!(
+            vec![Crossing::new(1.0, Vector3D::new(0.0, 1.0, 0.0))],
+            Plane.intersect(&down)
+        );
+        assert!(distances(Plane, ray((0.0, 1.0, 0.0), (1.0, 0.0, 0.0))).is_empty());
+        assert_eq!(Unit3D::INFINITY, Plane.bounds().max().x());
+    }
+
+    #[test]
+    fn test_rays_cross_a_cube() {
+        assert_eq!(
+            vec![4.0, 6.0],
+            distances(Cube, ray((5.0, 0.5, 0.0), (-1.0, 0.0, 0.0)))
+        );
+        assert_eq!(
+            vec![-1.0, 1.0],
+            distances(Cube, ray((0.0, 0.5, 0.0), (0.0, 0.0, 1.0)))
+        );
+        assert!(distances(Cube, ray((-2.0, 0.0, 0.0), (0.2673, 0.5345, 0.8018))).is_empty());
+        assert_eq!(
+            Vector3D::new(0.0, 0.0, -1.0),
+            Cube::normal_at(Point3D::new(-0.6, 0.3, -1.0))
+        );
+        assert_eq!(
+            Vector3D::new(1.0, 0.0, 0.0),
+            Cube::normal_at(Point3D::new(1.0, 1.0, 1.0))
+        );
+        let crossings = Cube.intersect(&ray((0.0, 5.0, 0.5), (0.0, -1.0, 0.0)));
+        assert_eq!(Vector3D::new(0.0, 1.0, 0.0), crossings[0].normal);
+        assert_eq!(Vector3D::new(0.0, -1.0, 0.0), crossings[1].normal);
+    }
+}
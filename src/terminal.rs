@@ -0,0 +1,207 @@
+//! Preview a `Canvas` in a terminal. Each character cell shows two pixels
+//! stacked vertically using the upper half block `▀`, its foreground color
+//! is the top pixel and its background color the bottom one, so pixels come
+//! out roughly square in most terminal fonts.
+
+use crate::canvas::{Canvas, Pixel};
+use crate::color::Color;
+use crate::filter::Filter;
+use std::env;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+/// How colors are written to the terminal
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ColorMode {
+    /// 24-bit RGB escape codes
+    TrueColor,
+    /// The xterm 256 color palette, for terminals without 24-bit color
+    Ansi256,
+}
+
+impl ColorMode {
+    /// Use 24-bit color if the terminal advertises it through `COLORTERM`,
+    /// otherwise fall back to the 256 color palette.
+    pub fn detect() -> Self {
+        match env::var("COLORTERM") {
+            Ok(value) if value == "truecolor" || value == "24bit" => ColorMode::TrueColor,
+            _ => ColorMode::Ansi256,
+        }
+    }
+
+    /// The escape code selecting `color` as the foreground or background
+    fn escape(&self, color: [u8; 3], background: bool) -> String {
+        let layer = if background { 48 } else { 38 };
+        match self {
+            ColorMode::TrueColor => {
+                format!("\x1b[{};2;{};{};{}m", layer, color[0], color[1], color[2])
+            }
+            ColorMode::Ansi256 => format!("\x1b[{};5;{}m", layer, ansi256(color)),
+        }
+    }
+}
+
+/// The width of the terminal in columns from the `COLUMNS` environment
+/// variable, or 80 if it isn't set.
+pub fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.trim().parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(80)
+}
+
+/// The index of the closest color to `rgb` in the xterm 256 color palette,
+/// choosing between the 6x6x6 color cube and the 24 step gray ramp.
+pub fn ansi256(rgb: [u8; 3]) -> u8 {
+    const LEVELS: [i32; 6] = [0, 95, 135, 175, 215, 255];
+    let nearest_level = |value: u8| {
+        (0..LEVELS.len())
+            .min_by_key(|&i| (LEVELS[i] - value as i32).abs())
+            .unwrap()
+    };
+    let distance = |a: [i32; 3]| (0..3).map(|i| (a[i] - rgb[i] as i32).pow(2)).sum::<i32>();
+
+    let cube = [
+        nearest_level(rgb[0]),
+        nearest_level(rgb[1]),
+        nearest_level(rgb[2]),
+    ];
+    let cube_color = [LEVELS[cube[0]], LEVELS[cube[1]], LEVELS[cube[2]]];
+    let cube_index = 16 + 36 * cube[0] + 6 * cube[1] + cube[2];
+
+    let average = rgb.iter().map(|&c| c as i32).sum::<i32>() / 3;
+    let gray_step = ((average - 8).max(0) / 10).min(23);
+    let gray = 8 + 10 * gray_step;
+
+    if distance([gray, gray, gray]) < distance(cube_color) {
+        (232 + gray_step) as u8
+    } else {
+        cube_index as u8
+    }
+}
+
+impl Canvas {
+    /// Render the canvas as text for a terminal `columns` characters wide.
+    /// Canvases wider than the terminal are scaled down to fit, keeping
+    /// their aspect ratio. Every line ends by resetting the colors.
+    pub fn to_ansi(&self, columns: usize, mode: ColorMode) -> String {
+        let scaled;
+        let canvas = if self.width() > columns && columns > 0 {
+            let height = (self.height() * columns + self.width() / 2) / self.width();
+            scaled = self.resize(columns, height.max(1), Filter::Bilinear);
+            &scaled
+        } else {
+            self
+        };
+
+        let mut out = String::new();
+        let mut row = 0;
+        while row < canvas.height() {
+            let (mut last_fg, mut last_bg) = (None, None);
+            for x in 0..canvas.width() {
+                let top = canvas.visual_color(x, row);
+                if last_fg != Some(top) {
+                    out.push_str(&mode.escape(top, false));
+                    last_fg = Some(top);
+                }
+                if row + 1 < canvas.height() {
+                    let bottom = canvas.visual_color(x, row + 1);
+                    if last_bg != Some(bottom) {
+                        out.push_str(&mode.escape(bottom, true));
+                        last_bg = Some(bottom);
+                    }
+                }
+                out.push(UPPER_HALF_BLOCK);
+            }
+            let _ = writeln!(out, "\x1b[0m");
+            row += 2;
+        }
+        out
+    }
+
+    /// Print the canvas to stdout scaled to the width of the terminal
+    pub fn preview(&self) -> io::Result<()> {
+        let text = self.to_ansi(terminal_width(), ColorMode::detect());
+        io::stdout().write_all(text.as_bytes())
+    }
+
+    /// The color bytes of the pixel `row` rows from the top of the image
+    fn visual_color(&self, x: usize, row: usize) -> [u8; 3] {
+        let y = self.visual_row(row as i64) as usize;
+        let color: Color = self[Pixel::new(x, y)];
+        color.as_rgb_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Origin;
+
+    #[test]
+    fn test_renders_two_pixels_per_cell_in_truecolor() {
+        let mut canvas = Canvas::with_origin(1, 2, Origin::TopLeft);
+        canvas[Pixel::new(0, 0)] = Color::rgb(1.0, 0.0, 0.0);
+        canvas[Pixel::new(0, 1)] = Color::rgb(0.0, 0.0, 1.0);
+        assert_eq!(
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m\u{2580}\x1b[0m\n",
+            canvas.to_ansi(80, ColorMode::TrueColor)
+        );
+    }
+
+    #[test]
+    fn test_the_top_of_the_image_is_printed_first() {
+        let mut canvas = Canvas::new(1, 2);
+        canvas[Pixel::new(0, 1)] = Color::rgb(1.0, 0.0, 0.0);
+        let text = canvas.to_ansi(80, ColorMode::TrueColor);
+        assert!(text.starts_with("\x1b[38;2;255;0;0m\x1b[48;2;0;0;0m"));
+    }
+
+    #[test]
+    fn test_repeated_colors_are_not_re_sent() {
+        let canvas = Canvas::new(3, 2);
+        let text = canvas.to_ansi(80, ColorMode::TrueColor);
+        assert_eq!(1, text.matches("\x1b[38;").count());
+        assert_eq!(3, text.matches(UPPER_HALF_BLOCK).count());
+    }
+
+    #[test]
+    fn test_odd_rows_leave_the_last_background_alone() {
+        let canvas = Canvas::new(1, 3);
+        let text = canvas.to_ansi(80, ColorMode::TrueColor);
+        let last_line = text.lines().last().unwrap();
+        assert!(!last_line.contains("\x1b[48;"));
+        assert_eq!(2, text.lines().count());
+    }
+
+    #[test]
+    fn test_wide_canvases_are_scaled_to_fit() {
+        let canvas = Canvas::new(160, 40);
+        let text = canvas.to_ansi(80, ColorMode::Ansi256);
+        assert_eq!(10, text.lines().count());
+        for line in text.lines() {
+            assert_eq!(80, line.matches(UPPER_HALF_BLOCK).count());
+        }
+    }
+
+    #[test]
+    fn test_uses_the_256_color_palette() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas[Pixel::new(0, 0)] = Color::rgb(1.0, 0.0, 0.0);
+        assert!(canvas
+            .to_ansi(80, ColorMode::Ansi256)
+            .starts_with("\x1b[38;5;196m"));
+    }
+
+    #[test]
+    fn test_finds_the_nearest_256_color() {
+        assert_eq!(16, ansi256([0, 0, 0]));
+        assert_eq!(231, ansi256([255, 255, 255]));
+        assert_eq!(196, ansi256([255, 0, 0]));
+        assert_eq!(21, ansi256([0, 0, 255]));
+        assert_eq!(244, ansi256([128, 128, 128]));
+    }
+}
@@ -0,0 +1,229 @@
+//! Bitmap text rendering onto a `Canvas` using the built-in `font`, and a
+//! burn-in overlay for stamping metadata like render time or frame number
+//! into a corner of an image.
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::font::{self, ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH, LINE_HEIGHT};
+use crate::units::Point3D;
+use std::fmt::Display;
+
+/// A corner of the image as it appears when viewed, whatever the canvas
+/// origin.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Lines of `label: value` text burned into a corner of a canvas over a
+/// background panel.
+#[derive(Debug, Clone)]
+pub struct BurnIn {
+    corner: Corner,
+    scale: usize,
+    foreground: Color,
+    background: Color,
+    lines: Vec<String>,
+}
+
+impl BurnIn {
+    /// Create an empty overlay for `corner` with text magnified by `scale`,
+    /// drawn in white over a translucent black panel.
+    pub fn new(corner: Corner, scale: usize) -> Self {
+        Self {
+            corner,
+            scale,
+            foreground: Color::rgb(1.0, 1.0, 1.0),
+            background: Color::rgba(0.0, 0.0, 0.0, 0.5),
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn set_colors(&mut self, foreground: Color, background: Color) {
+        self.foreground = foreground;
+        self.background = background;
+    }
+
+    /// Add a `label: value` line to the overlay
+    pub fn add<T: Display>(&mut self, label: &str, value: T) {
+        self.lines.push(format!("{}: {}", label, value));
+    }
+
+    pub fn corner(&self) -> Corner {
+        self.corner
+    }
+
+    /// The text of the overlay, one line per entry
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+impl Canvas {
+    /// Draw `text` with the top left corner of its first character at
+    /// `position` in canvas coordinates. Each font pixel is drawn as a
+    /// `scale` x `scale` block and newlines start a new line below.
+    pub fn draw_text(&mut self, position: Point3D, text: &str, color: Color, scale: usize) {
+        let (x, y) = self.to_signed(position);
+        self.stamp_text(x, y, text, color, scale);
+    }
+
+    /// Draw `overlay` into its corner of the canvas, leaving a small margin
+    /// to the edges of the image.
+    pub fn burn_in(&mut self, overlay: &BurnIn) {
+        let text = overlay.text();
+        let (text_width, text_height) = font::measure(&text, overlay.scale);
+        if text_width == 0 {
+            return;
+        }
+
+        let margin = 2 * overlay.scale as i64;
+        let padding = 2 * overlay.scale as i64;
+        let panel_width = text_width as i64 + 2 * padding;
+        let panel_height = text_height as i64 + 2 * padding;
+
+        // Measured in pixels from the visual top left of the image
+        let left = match overlay.corner {
+            Corner::TopLeft | Corner::BottomLeft => margin,
+            Corner::TopRight | Corner::BottomRight => self.width() as i64 - margin - panel_width,
+        };
+        let top = match overlay.corner {
+            Corner::TopLeft | Corner::TopRight => margin,
+            Corner::BottomLeft | Corner::BottomRight => {
+                self.height() as i64 - margin - panel_height
+            }
+        };
+
+        for row in top..top + panel_height {
+            let y = self.visual_row(row);
+            self.plot_span(left, left + panel_width - 1, y, overlay.background);
+        }
+        let y = self.visual_row(top + padding);
+        self.stamp_text(left + padding, y, &text, overlay.foreground, overlay.scale);
+    }
+
+    /// Draw `text` with its top left corner at signed pixel coordinates
+    fn stamp_text(&mut self, x: i64, y: i64, text: &str, color: Color, scale: usize) {
+        let down = self.down();
+        let scale = scale as i64;
+        for (line, line_text) in text.split('\n').enumerate() {
+            let top = y + down * (line * LINE_HEIGHT) as i64 * scale;
+            for (column, c) in line_text.chars().enumerate() {
+                let left = x + (column * ADVANCE) as i64 * scale;
+                let glyph = font::glyph(c);
+                for row in 0..GLYPH_HEIGHT {
+                    for col in 0..GLYPH_WIDTH {
+                        if !font::is_set(glyph, col, row) {
+                            continue;
+                        }
+                        for sy in 0..scale {
+                            let py = top + down * (row as i64 * scale + sy);
+                            let px = left + col as i64 * scale;
+                            self.plot_span(px, px + scale - 1, py, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Origin, Pixel};
+
+    fn white() -> Color {
+        Color::rgb(1.0, 1.0, 1.0)
+    }
+
+    fn is_lit(canvas: &Canvas, x: usize, y: usize) -> bool {
+        canvas[Pixel::new(x, y)].as_rgb_bytes() != [0, 0, 0]
+    }
+
+    #[test]
+    fn test_draws_a_character_down_from_its_top_left_corner() {
+        let mut canvas = Canvas::with_origin(10, 10, Origin::TopLeft);
+        canvas.draw_text(Point3D::new(1.0, 1.0, 0.0), "L", white(), 1);
+        assert!(is_lit(&canvas, 1, 1));
+        assert!(is_lit(&canvas, 1, 7));
+        assert!(is_lit(&canvas, 5, 7));
+        assert!(!is_lit(&canvas, 5, 1));
+    }
+
+    #[test]
+    fn test_text_is_upright_on_bottom_left_canvases() {
+        let mut canvas = Canvas::new(10, 10);
+        canvas.draw_text(Point3D::new(1.0, 8.0, 0.0), "L", white(), 1);
+        assert!(is_lit(&canvas, 1, 8));
+        assert!(is_lit(&canvas, 1, 2));
+        assert!(is_lit(&canvas, 5, 2));
+        assert!(!is_lit(&canvas, 5, 8));
+    }
+
+    #[test]
+    fn test_scaled_text_draws_blocks_per_font_pixel() {
+        let mut canvas = Canvas::with_origin(20, 20, Origin::TopLeft);
+        canvas.draw_text(Point3D::new(0.0, 0.0, 0.0), "L", white(), 2);
+        assert!(is_lit(&canvas, 0, 0));
+        assert!(is_lit(&canvas, 1, 1));
+        assert!(is_lit(&canvas, 9, 13));
+        assert!(!is_lit(&canvas, 10, 13));
+        assert!(!is_lit(&canvas, 0, 14));
+    }
+
+    #[test]
+    fn test_newlines_start_a_new_line() {
+        let mut canvas = Canvas::with_origin(10, 20, Origin::TopLeft);
+        canvas.draw_text(Point3D::new(0.0, 0.0, 0.0), "L\nL", white(), 1);
+        assert!(is_lit(&canvas, 0, LINE_HEIGHT));
+        assert!(is_lit(&canvas, 4, LINE_HEIGHT + GLYPH_HEIGHT - 1));
+    }
+
+    #[test]
+    fn test_text_is_clipped_at_the_canvas_edge() {
+        let mut canvas = Canvas::with_origin(4, 4, Origin::TopLeft);
+        canvas.draw_text(Point3D::new(-2.0, -2.0, 0.0), "WWW", white(), 1);
+        // The right hand stroke of the first W, two rows down the glyph
+        assert!(is_lit(&canvas, 2, 0));
+        assert!(!is_lit(&canvas, 0, 0));
+    }
+
+    #[test]
+    fn test_burn_in_collects_labelled_lines() {
+        let mut overlay = BurnIn::new(Corner::TopLeft, 1);
+        overlay.add("frame", 12);
+        overlay.add("samples", 64);
+        assert_eq!("frame: 12\nsamples: 64", overlay.text());
+    }
+
+    #[test]
+    fn test_burn_in_is_drawn_into_its_corner() {
+        let mut canvas = Canvas::new(100, 50);
+        let mut overlay = BurnIn::new(Corner::BottomRight, 1);
+        overlay.set_colors(white(), Color::rgb(0.0, 0.0, 1.0));
+        overlay.add("t", "1s");
+        canvas.burn_in(&overlay);
+
+        // The panel is drawn at the bottom of the image, which is the first
+        // row of a bottom left canvas, inset by the margin.
+        assert_eq!([0, 0, 255], canvas[Pixel::new(97, 2)].as_rgb_bytes());
+        assert!(!is_lit(&canvas, 98, 2));
+        assert!(!is_lit(&canvas, 97, 1));
+        assert!(!is_lit(&canvas, 2, 47));
+    }
+
+    #[test]
+    fn test_empty_burn_in_draws_nothing() {
+        let mut canvas = Canvas::new(20, 20);
+        canvas.burn_in(&BurnIn::new(Corner::TopLeft, 1));
+        for y in 0..20 {
+            for x in 0..20 {
+                assert!(!is_lit(&canvas, x, y));
+            }
+        }
+    }
+}
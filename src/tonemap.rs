@@ -0,0 +1,105 @@
+//! Tone mapping, squeezing the brightness of rendered colors into the range
+//! an 8 bit image can show.
+
+use crate::canvas::Canvas;
+use crate::color::{Color, Spectrum};
+
+/// A curve mapping rendered brightness to displayed brightness
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ToneMap {
+    /// Cut off anything brighter than white
+    Clamp,
+    /// `c / (1 + c)`, which rolls off highlights smoothly but darkens the
+    /// whole image
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+}
+
+impl ToneMap {
+    /// The tone map called `name`, as given on a command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "clamp" | "none" => Some(ToneMap::Clamp),
+            "reinhard" => Some(ToneMap::Reinhard),
+            "aces" | "filmic" => Some(ToneMap::Aces),
+            _ => None,
+        }
+    }
+
+    /// Map a single channel value
+    pub fn map(&self, value: f32) -> f32 {
+        let value = value.max(0.0);
+        let mapped = match self {
+            ToneMap::Clamp => value,
+            ToneMap::Reinhard => value / (1.0 + value),
+            ToneMap::Aces => {
+                (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)
+            }
+        };
+        mapped.clamp(0.0, 1.0)
+    }
+
+    /// Map each color channel, leaving alpha alone
+    pub fn apply(&self, color: Color) -> Color {
+        Color::rgba(
+            self.map(color.r()),
+            self.map(color.g()),
+            self.map(color.b()),
+            color.a(),
+        )
+    }
+
+    /// Map the channels of an unclamped `spectrum` into an opaque color
+    pub fn apply_spectrum(&self, spectrum: Spectrum) -> Color {
+        Color::rgb(
+            self.map(spectrum.r()),
+            self.map(spectrum.g()),
+            self.map(spectrum.b()),
+        )
+    }
+}
+
+impl Canvas {
+    /// Tone map every pixel of the canvas
+    pub fn tone_map(&mut self, tone_map: ToneMap) {
+        for row in self.rows_mut() {
+            for pixel in row.iter_mut() {
+                *pixel = tone_map.apply(*pixel);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Pixel;
+
+    #[test]
+    fn test_tone_maps_from_names() {
+        assert_eq!(Some(ToneMap::Aces), ToneMap::from_name("ACES"));
+        assert_eq!(Some(ToneMap::Clamp), ToneMap::from_name("none"));
+        assert_eq!(None, ToneMap::from_name("sepia"));
+    }
+
+    #[test]
+    fn test_curves_stay_in_range() {
+        for &tone_map in &[ToneMap::Clamp, ToneMap::Reinhard, ToneMap::Aces] {
+            assert_eq!(0.0, tone_map.map(-1.0));
+            assert!(tone_map.map(100.0) <= 1.0);
+            assert!(tone_map.map(0.2) < tone_map.map(0.4));
+        }
+        assert_eq!(0.5, ToneMap::Reinhard.map(1.0));
+    }
+
+    #[test]
+    fn test_tone_maps_a_canvas() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas[Pixel::new(0, 0)] = Color::rgba(1.0, 0.0, 1.0, 0.5);
+        canvas.tone_map(ToneMap::Reinhard);
+        let color = canvas[Pixel::new(0, 0)];
+        assert_eq!([128, 0, 128], color.as_rgb_bytes());
+        assert_eq!(0.5, color.a());
+    }
+}
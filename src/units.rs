@@ -223,6 +223,120 @@ impl fmt::Display for Point3D {
     }
 }
 
+// ==========================================================================
+// Quaternion
+
+/// A rotation as a unit quaternion `w + xi + yj + zk`. Unlike rotation
+/// matrices they can be blended smoothly with `slerp`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion(Unit3D, Unit3D, Unit3D, Unit3D);
+
+impl Quaternion {
+    pub fn new(w: Unit3D, x: Unit3D, y: Unit3D, z: Unit3D) -> Self {
+        Self(w, x, y, z)
+    }
+
+    /// The quaternion which doesn't rotate at all
+    pub fn identity() -> Self {
+        Self(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// A rotation of `radians` around `axis`, clockwise when looking back
+    /// along the axis towards the origin like `Matrix::rotation_x`
+    pub fn from_axis_angle(axis: Vector3D, radians: Unit3D) -> Self {
+        let axis = axis.normalize();
+        let (sin, cos) = (radians / 2.0).sin_cos();
+        Self(cos, axis.x() * sin, axis.y() * sin, axis.z() * sin)
+    }
+
+    pub fn w(&self) -> Unit3D {
+        self.0
+    }
+
+    pub fn x(&self) -> Unit3D {
+        self.1
+    }
+
+    pub fn y(&self) -> Unit3D {
+        self.2
+    }
+
+    pub fn z(&self) -> Unit3D {
+        self.3
+    }
+
+    pub fn dot(&self, other: Self) -> Unit3D {
+        self.0 * other.0 + self.1 * other.1 + self.2 * other.2 + self.3 * other.3
+    }
+
+    pub fn magnitude(&self) -> Unit3D {
+        self.dot(*self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Self {
+        let magnitude = self.magnitude();
+        Self(
+            self.0 / magnitude,
+            self.1 / magnitude,
+            self.2 / magnitude,
+            self.3 / magnitude,
+        )
+    }
+
+    /// The opposite rotation, for unit quaternions
+    pub fn conjugate(&self) -> Self {
+        Self(self.0, -self.1, -self.2, -self.3)
+    }
+
+    /// Rotate `vector`
+    pub fn rotate(&self, vector: Vector3D) -> Vector3D {
+        let rotated = *self * Self(0.0, vector.x(), vector.y(), vector.z()) * self.conjugate();
+        Vector3D(rotated.1, rotated.2, rotated.3)
+    }
+
+    /// The rotation `t` of the way from this one to `other`, turning at a
+    /// steady rate the short way round
+    pub fn slerp(&self, other: Self, t: Unit3D) -> Self {
+        // q and -q are the same rotation, so pick the nearer one
+        let mut cos = self.dot(other);
+        let other = if cos < 0.0 {
+            cos = -cos;
+            Self(-other.0, -other.1, -other.2, -other.3)
+        } else {
+            other
+        };
+        let (from, to) = if cos > 1.0 - EPSILON {
+            // Nearly the same, where a straight line is just as good
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Self(
+            self.0 * from + other.0 * to,
+            self.1 * from + other.1 * to,
+            self.2 * from + other.2 * to,
+            self.3 * from + other.3 * to,
+        )
+        .normalize()
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Self;
+
+    // Combine two rotations, `rhs` first
+    fn mul(self, rhs: Self) -> Self {
+        Self(
+            self.0 * rhs.0 - self.1 * rhs.1 - self.2 * rhs.2 - self.3 * rhs.3,
+            self.0 * rhs.1 + self.1 * rhs.0 + self.2 * rhs.3 - self.3 * rhs.2,
+            self.0 * rhs.2 - self.1 * rhs.3 + self.2 * rhs.0 + self.3 * rhs.1,
+            self.0 * rhs.3 + self.1 * rhs.2 - self.2 * rhs.1 + self.3 * rhs.0,
+        )
+    }
+}
+
 // ==========================================================================
 // Unit Tests
 
@@ -248,7 +362,7 @@ mod tests {
     fn test_adding_two_vectors_equals_a_new_vector() {
         let a = Vector3D(1.0, 2.0, 3.0);
         let b = Vector3D(4.0, 5.0, 6.0);
-        assert_eq!(Vector3D(5.0, 7.0, 9.0), a.clone() + b.clone());
+        assert_eq!(Vector3D(5.0, 7.0, 9.0), a + b);
         assert_eq!(Vector3D(5.0, 7.0, 9.0), b + a);
     }
 
@@ -256,7 +370,7 @@ mod tests {
     fn test_adding_a_vector_to_a_point_equals_a_point() {
         let point_a = Point3D(3.0, -2.0, 5.0);
         let vector_b = Vector3D(-2.0, 3.0, 1.0);
-        assert_eq!(Point3D(1.0, 1.0, 6.0), point_a.clone() + vector_b.clone());
+        assert_eq!(Point3D(1.0, 1.0, 6.0), point_a + vector_b);
         assert_eq!(Point3D(1.0, 1.0, 6.0), vector_b + point_a);
     }
 
@@ -361,4 +475,41 @@ mod tests {
             format!("{}", Vector3D(1.0, 2.0, 3.0))
         );
     }
+
+    fn vectors_close(a: Vector3D, b: Vector3D) -> bool {
+        (a - b).magnitude() < 0.0001
+    }
+
+    #[test]
+    fn test_quaternions_rotate_vectors() {
+        let quarter = std::f32::consts::FRAC_PI_2;
+        let turn = Quaternion::from_axis_angle(Vector3D(0.0, 1.0, 0.0), quarter);
+        let forward = Vector3D(0.0, 0.0, 1.0);
+        assert!(vectors_close(Vector3D(1.0, 0.0, 0.0), turn.rotate(forward)));
+        let back = turn.conjugate().rotate(Vector3D(1.0, 0.0, 0.0));
+        assert!(vectors_close(Vector3D(0.0, 0.0, 1.0), back));
+
+        let twice = turn * turn;
+        let behind = Vector3D(0.0, 0.0, -1.0);
+        assert!(vectors_close(behind, twice.rotate(forward)));
+        let still = Quaternion::identity().rotate(Vector3D(1.0, 2.0, 3.0));
+        assert_eq!(Vector3D(1.0, 2.0, 3.0), still);
+    }
+
+    #[test]
+    fn test_slerp_turns_at_a_steady_rate() {
+        let axis = Vector3D(0.0, 0.0, 1.0);
+        let start = Quaternion::identity();
+        let end = Quaternion::from_axis_angle(axis, 2.0);
+        let half = start.slerp(end, 0.5);
+        let expected = Quaternion::from_axis_angle(axis, 1.0);
+        assert!((half.dot(expected).abs() - 1.0).abs() < 0.0001);
+        assert!((half.magnitude() - 1.0).abs() < 0.0001);
+        assert_eq!(start, start.slerp(end, 0.0));
+
+        // The short way round, even when the quaternions point apart
+        let negated = Quaternion::new(-end.w(), -end.x(), -end.y(), -end.z());
+        let short = start.slerp(negated, 0.5);
+        assert!((short.dot(expected).abs() - 1.0).abs() < 0.0001);
+    }
 }
@@ -0,0 +1,370 @@
+//! Splitting a `Canvas` into rectangular tiles and mutable views onto them.
+//! A view borrows the pixels of its tile from the parent canvas so writes
+//! land directly in the canvas, and the views from `tiles_mut` never
+//! overlap so each one can be handed to a different thread.
+
+use crate::canvas::{Canvas, Pixel};
+use crate::color::Color;
+use std::ops::{Index, IndexMut};
+
+/// A rectangle of pixels with its first corner at `x`, `y`
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Rect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    pub fn y(&self) -> usize {
+        self.y
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The number of pixels in the rectangle
+    pub fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn contains(&self, position: Pixel) -> bool {
+        position.x() >= self.x
+            && position.y() >= self.y
+            && position.x() < self.x + self.width
+            && position.y() < self.y + self.height
+    }
+
+    /// The part of the rectangle inside `other`, which may be empty
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        Rect::new(x, y, right.saturating_sub(x), bottom.saturating_sub(y))
+    }
+}
+
+/// An iterator over the tiles covering a canvas, a row of tiles at a time
+/// starting from the first pixel row. Tiles on the far edges are cut short
+/// where the canvas isn't a whole number of tiles across.
+#[derive(Debug, Clone)]
+pub struct Tiles {
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    x: usize,
+    y: usize,
+}
+
+impl Iterator for Tiles {
+    type Item = Rect;
+
+    fn next(&mut self) -> Option<Rect> {
+        if self.y >= self.height || self.width == 0 {
+            return None;
+        }
+
+        let tile = Rect::new(
+            self.x,
+            self.y,
+            self.tile_width.min(self.width - self.x),
+            self.tile_height.min(self.height - self.y),
+        );
+        self.x += self.tile_width;
+        if self.x >= self.width {
+            self.x = 0;
+            self.y += self.tile_height;
+        }
+        Some(tile)
+    }
+}
+
+/// A mutable view onto a rectangle of a canvas. Pixels are addressed
+/// relative to the corner of the view.
+#[derive(Debug)]
+pub struct CanvasViewMut<'a> {
+    rect: Rect,
+    rows: Vec<&'a mut [Color]>,
+}
+
+impl<'a> CanvasViewMut<'a> {
+    /// The rectangle of the parent canvas covered by the view
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    pub fn width(&self) -> usize {
+        self.rect.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.rect.height
+    }
+
+    /// The pixel of the parent canvas at `position` in the view
+    pub fn to_canvas(&self, position: Pixel) -> Pixel {
+        Pixel::new(self.rect.x + position.x(), self.rect.y + position.y())
+    }
+
+    pub fn get(&self, position: Pixel) -> Option<&Color> {
+        self.rows
+            .get(position.y())
+            .and_then(|row| row.get(position.x()))
+    }
+
+    pub fn get_mut(&mut self, position: Pixel) -> Option<&mut Color> {
+        self.rows
+            .get_mut(position.y())
+            .and_then(|row| row.get_mut(position.x()))
+    }
+
+    pub fn set_pixel(&mut self, position: Pixel, color: Color) {
+        match self.get_mut(position) {
+            Some(pixel) => *pixel = color,
+            None => panic!(
+                "attempt to set pixel ({}, {}) outside {}x{} view!",
+                position.x(),
+                position.y(),
+                self.rect.width,
+                self.rect.height,
+            ),
+        }
+    }
+
+    /// Set every pixel in the view to `color`
+    pub fn fill(&mut self, color: Color) {
+        for row in self.rows.iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel = color;
+            }
+        }
+    }
+}
+
+impl<'a> Index<Pixel> for CanvasViewMut<'a> {
+    type Output = Color;
+
+    fn index(&self, position: Pixel) -> &Self::Output {
+        &self.rows[position.y()][position.x()]
+    }
+}
+
+impl<'a> IndexMut<Pixel> for CanvasViewMut<'a> {
+    fn index_mut(&mut self, position: Pixel) -> &mut Self::Output {
+        &mut self.rows[position.y()][position.x()]
+    }
+}
+
+impl Canvas {
+    /// The rectangle covering the whole canvas
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width(), self.height())
+    }
+
+    /// Split the canvas into tiles of `tile_width` x `tile_height` pixels
+    pub fn tiles(&self, tile_width: usize, tile_height: usize) -> Tiles {
+        if tile_width == 0 || tile_height == 0 {
+            panic!("tiles must be at least 1x1! {}x{}", tile_width, tile_height);
+        }
+
+        Tiles {
+            width: self.width(),
+            height: self.height(),
+            tile_width,
+            tile_height,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    /// A mutable view onto `rect`, clipped to the canvas
+    pub fn view_mut(&mut self, rect: Rect) -> CanvasViewMut<'_> {
+        let rect = rect.intersect(&self.bounds());
+        if rect.area() == 0 {
+            return CanvasViewMut {
+                rect,
+                rows: Vec::new(),
+            };
+        }
+
+        let rows = self
+            .rows_mut()
+            .skip(rect.y)
+            .take(rect.height)
+            .map(|row| &mut row[rect.x..rect.x + rect.width])
+            .collect();
+        CanvasViewMut { rect, rows }
+    }
+
+    /// Mutable views onto every tile of the canvas, in the same order as
+    /// `tiles`. The views don't overlap so they can be written to at the
+    /// same time.
+    pub fn tiles_mut(&mut self, tile_width: usize, tile_height: usize) -> Vec<CanvasViewMut<'_>> {
+        let mut views: Vec<CanvasViewMut> = self
+            .tiles(tile_width, tile_height)
+            .map(|rect| CanvasViewMut {
+                rect,
+                rows: Vec::with_capacity(rect.height),
+            })
+            .collect();
+        let across = self.width().div_ceil(tile_width);
+
+        for (y, row) in self.rows_mut().enumerate() {
+            let band = y / tile_height;
+            for (column, pixels) in row.chunks_mut(tile_width).enumerate() {
+                views[band * across + column].rows.push(pixels);
+            }
+        }
+        views
+    }
+
+    /// Copy `source` onto the canvas with its first pixel at `at`, clipping
+    /// whatever falls outside. Rows are flipped if needed so the source
+    /// appears the same way up as it would on its own.
+    pub fn blit(&mut self, source: &Canvas, at: Pixel) {
+        let step = self.down() * source.down();
+        for row in 0..source.height() {
+            let y = at.y() as i64 + step * row as i64;
+            for col in 0..source.width() {
+                let x = (at.x() + col) as i64;
+                if let Some(pixel) = self.signed_pixel(x, y) {
+                    self[pixel] = source[Pixel::new(col, row)];
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Origin;
+    use crate::units::Point3D;
+
+    fn white() -> Color {
+        Color::rgb(1.0, 1.0, 1.0)
+    }
+
+    #[test]
+    fn test_tiles_cover_the_canvas_without_overlapping() {
+        let canvas = Canvas::new(10, 7);
+        let tiles: Vec<Rect> = canvas.tiles(4, 4).collect();
+        assert_eq!(6, tiles.len());
+        assert_eq!(Rect::new(0, 0, 4, 4), tiles[0]);
+        assert_eq!(Rect::new(8, 0, 2, 4), tiles[2]);
+        assert_eq!(Rect::new(8, 4, 2, 3), tiles[5]);
+        assert_eq!(70, tiles.iter().map(|t| t.area()).sum::<usize>());
+    }
+
+    #[test]
+    fn test_an_empty_canvas_has_no_tiles() {
+        assert_eq!(0, Canvas::new(0, 5).tiles(2, 2).count());
+        assert_eq!(0, Canvas::new(5, 0).tiles(2, 2).count());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tiles_must_not_be_empty() {
+        Canvas::new(10, 10).tiles(0, 4);
+    }
+
+    #[test]
+    fn test_rects_intersect() {
+        let a = Rect::new(0, 0, 10, 10);
+        assert_eq!(Rect::new(5, 5, 5, 5), a.intersect(&Rect::new(5, 5, 10, 10)));
+        assert_eq!(0, a.intersect(&Rect::new(20, 20, 5, 5)).area());
+    }
+
+    #[test]
+    fn test_views_write_into_the_parent_canvas() {
+        let mut canvas = Canvas::new(10, 10);
+        {
+            let mut view = canvas.view_mut(Rect::new(2, 3, 4, 4));
+            view[Pixel::new(0, 0)] = white();
+            view.set_pixel(Pixel::new(3, 3), white());
+            assert_eq!(Pixel::new(5, 6), view.to_canvas(Pixel::new(3, 3)));
+            assert_eq!(None, view.get(Pixel::new(4, 0)));
+        }
+        assert_eq!([255, 255, 255], canvas[Pixel::new(2, 3)].as_rgb_bytes());
+        assert_eq!([255, 255, 255], canvas[Pixel::new(5, 6)].as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_views_are_clipped_to_the_canvas() {
+        let mut canvas = Canvas::new(10, 10);
+        let view = canvas.view_mut(Rect::new(8, 8, 4, 4));
+        assert_eq!(Rect::new(8, 8, 2, 2), view.rect());
+        let outside = canvas.view_mut(Rect::new(20, 0, 4, 4));
+        assert_eq!(0, outside.rect().area());
+        assert_eq!(None, outside.get(Pixel::new(0, 0)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_setting_a_pixel_outside_a_view_panics() {
+        let mut canvas = Canvas::new(10, 10);
+        let mut view = canvas.view_mut(Rect::new(0, 0, 2, 2));
+        view.set_pixel(Pixel::new(2, 0), white());
+    }
+
+    #[test]
+    fn test_tile_views_can_all_be_written_at_once() {
+        let mut canvas = Canvas::new(5, 3);
+        let mut views = canvas.tiles_mut(2, 2);
+        assert_eq!(6, views.len());
+        for (index, view) in views.iter_mut().enumerate() {
+            let shade = index as f32 / 10.0;
+            view.fill(Color::rgb(shade, shade, shade));
+        }
+        assert_eq!([0, 0, 0], canvas[Pixel::new(1, 1)].as_rgb_bytes());
+        assert_eq!([51, 51, 51], canvas[Pixel::new(4, 0)].as_rgb_bytes());
+        assert_eq!([128, 128, 128], canvas[Pixel::new(4, 2)].as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_blits_one_canvas_onto_another() {
+        let mut source = Canvas::new(2, 2);
+        source.fill_rect(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(1.0, 0.0, 0.0),
+            white(),
+        );
+        let mut canvas = Canvas::new(5, 5);
+        canvas.blit(&source, Pixel::new(4, 1));
+        assert_eq!([255, 255, 255], canvas[Pixel::new(4, 1)].as_rgb_bytes());
+        assert_eq!([0, 0, 0], canvas[Pixel::new(4, 2)].as_rgb_bytes());
+    }
+
+    #[test]
+    fn test_blitting_keeps_the_source_the_right_way_up() {
+        let mut source = Canvas::with_origin(1, 2, Origin::TopLeft);
+        source[Pixel::new(0, 0)] = white();
+        let mut canvas = Canvas::new(3, 3);
+        canvas.blit(&source, Pixel::new(1, 2));
+        // The top row of the source lands on the top row of the canvas and
+        // the second row below it.
+        assert_eq!([255, 255, 255], canvas[Pixel::new(1, 2)].as_rgb_bytes());
+        assert_eq!([0, 0, 0], canvas[Pixel::new(1, 1)].as_rgb_bytes());
+    }
+}